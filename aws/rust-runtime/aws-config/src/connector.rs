@@ -10,6 +10,9 @@ use std::sync::Arc;
 use aws_smithy_async::rt::sleep::AsyncSleep;
 use aws_smithy_client::erase::DynConnector;
 use aws_smithy_client::http_connector::HttpSettings;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use aws_smithy_client::hyper_ext::ProxyConfig;
+use aws_types::os_shim_internal::Env;
 
 // unused when all crate features are disabled
 /// Unwrap an [`Option<DynConnector>`](aws_smithy_client::erase::DynConnector), and panic with a helpful error message if it's `None`
@@ -30,31 +33,52 @@ fn base(
     hyper
 }
 
+/// Read the proxies connections go through from the `HTTPS_PROXY`, `http_proxy` and `NO_PROXY`
+/// variables of `env`
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn proxy_config(env: &Env) -> ProxyConfig {
+    ProxyConfig::from_env_fn(|name| env.get(name).ok())
+}
+
 /// Given `HttpSettings` and an `AsyncSleep`, create a `DynConnector` from defaults depending on what cargo features are activated.
-#[cfg(feature = "rustls")]
+///
+/// Connections go through the proxies set by the `HTTPS_PROXY`, `http_proxy` and `NO_PROXY`
+/// environment variables, if any.
 pub fn default_connector(
     settings: &HttpSettings,
     sleep: Option<Arc<dyn AsyncSleep>>,
 ) -> Option<DynConnector> {
-    let hyper = base(settings, sleep).build(aws_smithy_client::conns::https());
+    default_connector_with_env(settings, sleep, &Env::real())
+}
+
+/// Like [`default_connector`], but the proxies are read from `env`.
+#[cfg(feature = "rustls")]
+pub(crate) fn default_connector_with_env(
+    settings: &HttpSettings,
+    sleep: Option<Arc<dyn AsyncSleep>>,
+    env: &Env,
+) -> Option<DynConnector> {
+    let hyper = base(settings, sleep).build_https(proxy_config(env));
     Some(DynConnector::new(hyper))
 }
 
-/// Given `HttpSettings` and an `AsyncSleep`, create a `DynConnector` from defaults depending on what cargo features are activated.
+/// Like [`default_connector`], but the proxies are read from `env`.
 #[cfg(all(not(feature = "rustls"), feature = "native-tls"))]
-pub fn default_connector(
+pub(crate) fn default_connector_with_env(
     settings: &HttpSettings,
     sleep: Option<Arc<dyn AsyncSleep>>,
+    env: &Env,
 ) -> Option<DynConnector> {
-    let hyper = base(settings, sleep).build(aws_smithy_client::conns::native_tls());
+    let hyper = base(settings, sleep).build_native_tls(proxy_config(env));
     Some(DynConnector::new(hyper))
 }
 
-/// Given `HttpSettings` and an `AsyncSleep`, create a `DynConnector` from defaults depending on what cargo features are activated.
+/// Like [`default_connector`], but the proxies are read from `env`.
 #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
-pub fn default_connector(
+pub(crate) fn default_connector_with_env(
     _settings: &HttpSettings,
     _sleep: Option<Arc<dyn AsyncSleep>>,
+    _env: &Env,
 ) -> Option<DynConnector> {
     None
 }
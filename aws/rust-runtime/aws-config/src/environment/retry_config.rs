@@ -125,6 +125,13 @@ mod test {
                 .build(),
             RetryConfig::new().with_retry_mode(RetryMode::Standard)
         );
+        assert_eq!(
+            test_provider(&[(ENV_VAR_RETRY_MODE, "adaptive")])
+                .retry_config_builder()
+                .unwrap()
+                .build(),
+            RetryConfig::new().with_retry_mode(RetryMode::Adaptive)
+        );
     }
 
     #[test]
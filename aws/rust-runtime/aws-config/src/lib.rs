@@ -118,7 +118,7 @@ pub use loader::ConfigLoader;
 mod loader {
     use std::sync::Arc;
 
-    use crate::connector::default_connector;
+    use crate::connector::default_connector_with_env;
     use aws_smithy_async::rt::sleep::{default_async_sleep, AsyncSleep};
     use aws_smithy_client::http_connector::{HttpConnector, HttpSettings};
     use aws_smithy_types::retry::RetryConfig;
@@ -333,7 +333,11 @@ mod loader {
             } else {
                 let settings = HttpSettings::default().with_timeout_config(timeout_config.clone());
                 let sleep_impl = sleep_impl.clone();
-                HttpConnector::Prebuilt(default_connector(&settings, sleep_impl))
+                HttpConnector::Prebuilt(default_connector_with_env(
+                    &settings,
+                    sleep_impl,
+                    &conf.env(),
+                ))
             };
 
             let credentials_provider = if let Some(provider) = self.credentials_provider {
@@ -17,7 +17,7 @@ use std::error::Error;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 
-use crate::connector::default_connector;
+use crate::connector::default_connector_with_env;
 use http::Uri;
 use hyper::client::connect::Connection;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -35,7 +35,8 @@ pub struct ProviderConfig {
     env: Env,
     fs: Fs,
     time_source: TimeSource,
-    connector: HttpConnector,
+    /// The connector, or `None` for the default connector of the `env`
+    connector: Option<HttpConnector>,
     sleep: Option<Arc<dyn AsyncSleep>>,
     region: Option<Region>,
 }
@@ -53,17 +54,11 @@ impl Debug for ProviderConfig {
 
 impl Default for ProviderConfig {
     fn default() -> Self {
-        let connector = HttpConnector::ConnectorFn(Arc::new(
-            |settings: &HttpSettings, sleep: Option<Arc<dyn AsyncSleep>>| {
-                default_connector(settings, sleep)
-            },
-        ));
-
         Self {
             env: Env::default(),
             fs: Fs::default(),
             time_source: TimeSource::default(),
-            connector,
+            connector: None,
             sleep: default_async_sleep(),
             region: None,
         }
@@ -84,7 +79,7 @@ impl ProviderConfig {
             env: Env::from_slice(&[]),
             fs: Fs::from_raw_map(HashMap::new()),
             time_source: TimeSource::manual(&ManualTimeSource::new(UNIX_EPOCH)),
-            connector: HttpConnector::Prebuilt(None),
+            connector: Some(HttpConnector::Prebuilt(None)),
             sleep: None,
             region: None,
         }
@@ -123,7 +118,7 @@ impl ProviderConfig {
             env: Env::default(),
             fs: Fs::default(),
             time_source: TimeSource::default(),
-            connector: HttpConnector::Prebuilt(None),
+            connector: Some(HttpConnector::Prebuilt(None)),
             sleep: None,
             region: None,
         }
@@ -164,13 +159,15 @@ impl ProviderConfig {
 
     #[allow(dead_code)]
     pub(crate) fn default_connector(&self) -> Option<DynConnector> {
-        self.connector
-            .connector(&HttpSettings::default(), self.sleep.clone())
+        self.connector(&HttpSettings::default())
     }
 
     #[allow(dead_code)]
     pub(crate) fn connector(&self, settings: &HttpSettings) -> Option<DynConnector> {
-        self.connector.connector(settings, self.sleep.clone())
+        match &self.connector {
+            Some(connector) => connector.connector(settings, self.sleep.clone()),
+            None => default_connector_with_env(settings, self.sleep.clone(), &self.env),
+        }
     }
 
     #[allow(dead_code)]
@@ -228,7 +225,7 @@ impl ProviderConfig {
     /// This method is expected to change to support HTTP configuration.
     pub fn with_http_connector(self, connector: DynConnector) -> Self {
         ProviderConfig {
-            connector: HttpConnector::Prebuilt(Some(connector)),
+            connector: Some(HttpConnector::Prebuilt(Some(connector))),
             ..self
         }
     }
@@ -256,7 +253,7 @@ impl ProviderConfig {
             Some(DynConnector::new(builder.build(connector.clone())))
         };
         ProviderConfig {
-            connector: HttpConnector::ConnectorFn(Arc::new(connector_fn)),
+            connector: Some(HttpConnector::ConnectorFn(Arc::new(connector_fn))),
             ..self
         }
     }
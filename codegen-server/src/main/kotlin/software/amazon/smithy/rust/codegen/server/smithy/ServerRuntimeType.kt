@@ -5,6 +5,12 @@
 
 package software.amazon.smithy.rust.codegen.server.smithy
 
+import software.amazon.smithy.aws.traits.protocols.AwsJson1_0Trait
+import software.amazon.smithy.aws.traits.protocols.AwsJson1_1Trait
+import software.amazon.smithy.aws.traits.protocols.RestJson1Trait
+import software.amazon.smithy.aws.traits.protocols.RestXmlTrait
+import software.amazon.smithy.codegen.core.CodegenException
+import software.amazon.smithy.model.shapes.ShapeId
 import software.amazon.smithy.rust.codegen.rustlang.CargoDependency
 import software.amazon.smithy.rust.codegen.rustlang.InlineDependency
 import software.amazon.smithy.rust.codegen.smithy.RuntimeConfig
@@ -28,6 +34,24 @@ object ServerRuntimeType {
     fun RequestSpecModule(runtimeConfig: RuntimeConfig) =
         RuntimeType("request_spec", CargoDependency.SmithyHttpServer(runtimeConfig), "${runtimeConfig.crateSrcPrefix}_http_server::routing")
 
+    /**
+     * The variant of the runtime's `Protocol` enum for the service's [protocol], used to serialize the errors raised by
+     * the framework itself in the protocol's wire format.
+     */
+    fun Protocol(protocol: ShapeId, runtimeConfig: RuntimeConfig): RuntimeType {
+        val variant = when (protocol) {
+            RestJson1Trait.ID -> "RestJson1"
+            RestXmlTrait.ID -> "RestXml"
+            AwsJson1_0Trait.ID -> "AwsJson10"
+            AwsJson1_1Trait.ID -> "AwsJson11"
+            else -> throw CodegenException("Protocol $protocol is not supported by the server runtime")
+        }
+        return RuntimeType(variant, CargoDependency.SmithyHttpServer(runtimeConfig), "${runtimeConfig.crateSrcPrefix}_http_server::protocols::Protocol")
+    }
+
+    fun RuntimeError(runtimeConfig: RuntimeConfig) =
+        RuntimeType("RuntimeError", CargoDependency.SmithyHttpServer(runtimeConfig), "${runtimeConfig.crateSrcPrefix}_http_server::runtime_error")
+
     fun serverOperationHandler(runtimeConfig: RuntimeConfig) =
         forInlineDependency(ServerInlineDependency.serverOperationHandler(runtimeConfig))
 }
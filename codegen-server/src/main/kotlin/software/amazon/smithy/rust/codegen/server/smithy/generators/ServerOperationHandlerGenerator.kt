@@ -45,6 +45,7 @@ class ServerOperationHandlerGenerator(
         "SmithyHttpServer" to CargoDependency.SmithyHttpServer(runtimeConfig).asType(),
         "SmithyRejection" to ServerHttpProtocolGenerator.smithyRejection(runtimeConfig),
         "Phantom" to ServerRuntimeType.Phantom,
+        "Protocol" to ServerRuntimeType.Protocol(codegenContext.protocol, runtimeConfig),
         "ServerOperationHandler" to ServerRuntimeType.serverOperationHandler(runtimeConfig),
         "http" to RuntimeType.http,
     )
@@ -69,13 +70,8 @@ class ServerOperationHandlerGenerator(
             } else {
                 "impl<B, Fun, Fut> #{ServerOperationHandler}::Handler<B, (), $inputName> for Fun"
             }
-            val storeErrorInExtensions = """{
-                let error = aws_smithy_http_server::ExtensionRejection::new(r.to_string());
-                let mut response = r.into_response();
-                response.extensions_mut().insert(error);
-                return response.map($serverCrate::body::boxed);
-                }
-            """.trimIndent()
+            // Rejections are serialized in the wire format of the protocol, and stored in the response extensions.
+            val returnRuntimeError = "return e.into_response().map($serverCrate::body::boxed)"
             writer.rustBlockTemplate(
                 """
                 ##[#{AsyncTrait}::async_trait]
@@ -86,9 +82,9 @@ class ServerOperationHandlerGenerator(
                 *codegenScope
             ) {
                 val callImpl = if (state) {
-                    """let state = match $serverCrate::Extension::<S>::from_request(&mut req).await {
-                    Ok(v) => v,
-                    Err(r) => $storeErrorInExtensions
+                    """let state = match $serverCrate::runtime_error::extract::<$serverCrate::Extension<S>, B>(#{Protocol}, &mut req).await {
+                        Ok(v) => v,
+                        Err(e) => $returnRuntimeError,
                     };
                     let input_inner = input_wrapper.into();
                     let output_inner = self(input_inner, state).await;"""
@@ -101,11 +97,10 @@ class ServerOperationHandlerGenerator(
                     type Sealed = #{ServerOperationHandler}::sealed::Hidden;
                     async fn call(self, req: #{http}::Request<B>) -> #{http}::Response<#{SmithyHttpServer}::body::BoxBody> {
                         let mut req = #{AxumCore}::extract::RequestParts::new(req);
-                        use #{AxumCore}::extract::FromRequest;
                         use #{AxumCore}::response::IntoResponse;
-                        let input_wrapper = match $inputWrapperName::from_request(&mut req).await {
+                        let input_wrapper = match $serverCrate::runtime_error::extract::<$inputWrapperName, B>(#{Protocol}, &mut req).await {
                             Ok(v) => v,
-                            Err(r) => $storeErrorInExtensions
+                            Err(e) => $returnRuntimeError,
                         };
                         $callImpl
                         let output_wrapper: $outputWrapperName = output_inner.into();
@@ -6,6 +6,7 @@
 package software.amazon.smithy.rust.codegen.server.smithy.generators
 
 import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.model.traits.EndpointTrait
 import software.amazon.smithy.rust.codegen.rustlang.Attribute
 import software.amazon.smithy.rust.codegen.rustlang.CargoDependency
 import software.amazon.smithy.rust.codegen.rustlang.RustWriter
@@ -20,6 +21,7 @@ import software.amazon.smithy.rust.codegen.smithy.CodegenContext
 import software.amazon.smithy.rust.codegen.smithy.RuntimeType
 import software.amazon.smithy.rust.codegen.smithy.protocols.HttpBindingResolver
 import software.amazon.smithy.rust.codegen.util.dq
+import software.amazon.smithy.rust.codegen.util.getTrait
 import software.amazon.smithy.rust.codegen.util.toSnakeCase
 
 /**
@@ -37,6 +39,7 @@ class ServerOperationRegistryGenerator(
         "Router" to ServerRuntimeType.Router(runtimeConfig),
         "SmithyHttpServer" to CargoDependency.SmithyHttpServer(runtimeConfig).asType(),
         "ServerOperationHandler" to ServerRuntimeType.serverOperationHandler(runtimeConfig),
+        "Protocol" to ServerRuntimeType.Protocol(codegenContext.protocol, runtimeConfig),
         "Tower" to ServerCargoDependency.Tower.asType(),
         "Phantom" to ServerRuntimeType.Phantom,
         "StdError" to RuntimeType.StdError
@@ -225,7 +228,7 @@ class ServerOperationRegistryGenerator(
                 rustTemplate(
                     """
                     $requestSpecs
-                    #{Router}::from_box_clone_service_iter($towerServices)
+                    #{Router}::from_box_clone_service_iter($towerServices).with_protocol(#{Protocol})
                     """.trimIndent(),
                     *codegenScope
                 )
@@ -249,7 +252,6 @@ class ServerOperationRegistryGenerator(
         val httpTrait = httpBindingResolver.httpTrait(this)
         val namespace = ServerRuntimeType.RequestSpecModule(runtimeConfig).fullyQualifiedName()
 
-        // TODO: Support the `endpoint` trait: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
         val pathSegments = httpTrait.uri.segments.map {
             "$namespace::PathSegment::" +
                 if (it.isGreedyLabel) "Greedy"
@@ -262,16 +264,28 @@ class ServerOperationRegistryGenerator(
                 else "KeyValue(String::from(\"${it.key}\"), String::from(\"${it.value}\"))"
         }
 
+        val pathAndQuery = """
+            $namespace::PathAndQuerySpec::new(
+                $namespace::PathSpec::from_vector_unchecked(vec![${pathSegments.joinToString()}]),
+                $namespace::QuerySpec::from_vector_unchecked(vec![${querySegments.joinToString()}])
+            )
+        """.trimIndent()
+        // Operations with the `endpoint` trait are only routed requests sent to a host starting with their host prefix:
+        // https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
+        val uriSpec = getTrait<EndpointTrait>()?.let { endpointTrait ->
+            val hostPrefixSegments = endpointTrait.hostPrefix.segments.map {
+                "$namespace::HostPrefixSegment::" +
+                    if (it.isLabel) "Label(String::from(\"${it.content}\"))"
+                    else "Literal(String::from(\"${it.content}\"))"
+            }
+            "$namespace::UriSpec::new_with_host_prefix(vec![${hostPrefixSegments.joinToString()}], $pathAndQuery)"
+        } ?: "$namespace::UriSpec::new($pathAndQuery)"
+
         return """
             $namespace::RequestSpec::new(
                 http::Method::${httpTrait.method},
-                $namespace::UriSpec::new(
-                    $namespace::PathAndQuerySpec::new(
-                        $namespace::PathSpec::from_vector_unchecked(vec![${pathSegments.joinToString()}]),
-                        $namespace::QuerySpec::from_vector_unchecked(vec![${querySegments.joinToString()}])
-                    )
-                )
-            )
+                $uriSpec
+            ).with_operation_name("${id.name}")
         """.trimIndent()
     }
 }
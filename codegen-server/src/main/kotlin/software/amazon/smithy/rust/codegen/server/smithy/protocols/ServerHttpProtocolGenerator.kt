@@ -15,6 +15,7 @@ import software.amazon.smithy.model.shapes.OperationShape
 import software.amazon.smithy.model.shapes.Shape
 import software.amazon.smithy.model.shapes.StringShape
 import software.amazon.smithy.model.shapes.StructureShape
+import software.amazon.smithy.model.traits.EndpointTrait
 import software.amazon.smithy.model.traits.ErrorTrait
 import software.amazon.smithy.model.traits.HttpErrorTrait
 import software.amazon.smithy.rust.codegen.rustlang.Attribute
@@ -119,7 +120,9 @@ private class ServerHttpProtocolImplGenerator(
         "LazyStatic" to CargoDependency.LazyStatic.asType(),
         "Nom" to ServerCargoDependency.Nom.asType(),
         "PercentEncoding" to CargoDependency.PercentEncoding.asType(),
+        "Protocol" to ServerRuntimeType.Protocol(codegenContext.protocol, runtimeConfig),
         "Regex" to CargoDependency.Regex.asType(),
+        "RuntimeError" to ServerRuntimeType.RuntimeError(runtimeConfig),
         "SerdeUrlEncoded" to ServerCargoDependency.SerdeUrlEncoded.asType(),
         "SmithyHttp" to CargoDependency.SmithyHttp(runtimeConfig).asType(),
         "SmithyHttpServer" to CargoDependency.SmithyHttpServer(runtimeConfig).asType(),
@@ -190,7 +193,7 @@ private class ServerHttpProtocolImplGenerator(
                         match #{serialize_response}(o) {
                             Ok(response) => response,
                             Err(e) => {
-                                e.into_response()
+                                #{RuntimeError}::new(#{Protocol}, e).into_response()
                             }
                         }
                     },
@@ -201,7 +204,7 @@ private class ServerHttpProtocolImplGenerator(
                                 response
                             },
                             Err(e) => {
-                                e.into_response()
+                                #{RuntimeError}::new(#{Protocol}, e).into_response()
                             }
                         }
                     }
@@ -236,7 +239,7 @@ private class ServerHttpProtocolImplGenerator(
                 """
                 let mut response = match #{serialize_response}(self.0) {
                     Ok(response) => response,
-                    Err(e) => e.into_response()
+                    Err(e) => #{RuntimeError}::new(#{Protocol}, e).into_response()
                 };
                 $httpExtensions
                 response
@@ -577,6 +580,7 @@ private class ServerHttpProtocolImplGenerator(
             }
         }
         serverRenderUriPathParser(this, operationShape)
+        serverRenderHostLabelParser(this, operationShape, inputShape)
         serverRenderQueryStringParser(this, operationShape)
 
         val err = if (StructureGenerator.fallibleBuilder(inputShape, symbolProvider)) {
@@ -729,6 +733,34 @@ private class ServerHttpProtocolImplGenerator(
         }
     }
 
+    /*
+     * Binds the labels of the `endpoint` trait's host prefix to the `@hostLabel` members of the input.
+     * The router stores the values it captured from the request's host in the `HostLabels` request extension when it
+     * routes the request to the operation:
+     * https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
+     */
+    private fun serverRenderHostLabelParser(writer: RustWriter, operationShape: OperationShape, inputShape: StructureShape) {
+        val hostLabels = operationShape.getTrait<EndpointTrait>()?.hostPrefix?.labels.orEmpty()
+        if (hostLabels.isEmpty()) {
+            return
+        }
+        writer.rustBlockTemplate(
+            "if let Some(host_labels) = request.extensions().and_then(|extensions| extensions.get::<#{SmithyHttpServer}::HostLabels>())",
+            *codegenScope
+        ) {
+            for (label in hostLabels) {
+                val member = inputShape.getMember(label.content).get()
+                rust(
+                    """
+                    if let Some(value) = host_labels.get(${label.content.dq()}) {
+                        input = input.${member.setterName()}(${symbolProvider.toOptional(member, "value.to_owned()")});
+                    }
+                    """.trimIndent()
+                )
+            }
+        }
+    }
+
     // The `httpQueryParams` trait can be applied to structure members that target:
     //     * a map of string,
     //     * a map of list of string; or
@@ -5,6 +5,9 @@
 
 use std::sync::Arc;
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::interceptor::{Interceptor, Interceptors};
+use crate::metrics::{MetricsSink, NoOpMetricsSink};
 use crate::{bounds, erase, retry, Client, TriState, MISSING_SLEEP_IMPL_RECOMMENDATION};
 use aws_smithy_async::rt::sleep::{default_async_sleep, AsyncSleep};
 use aws_smithy_http::body::SdkBody;
@@ -23,6 +26,9 @@ pub struct Builder<C = (), M = (), R = retry::Standard> {
     retry_policy: R,
     timeout_config: TimeoutConfig,
     sleep_impl: TriState<Arc<dyn AsyncSleep>>,
+    circuit_breaker: Option<CircuitBreaker>,
+    interceptors: Interceptors,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
 }
 
 // It'd be nice to include R where R: Default here, but then the caller ends up always having to
@@ -62,6 +68,9 @@ impl<M, R> Builder<(), M, R> {
             middleware: self.middleware,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 
@@ -118,6 +127,9 @@ impl<C, R> Builder<C, (), R> {
             timeout_config: self.timeout_config,
             middleware,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 
@@ -169,6 +181,9 @@ impl<C, M> Builder<C, M, retry::Standard> {
             timeout_config: self.timeout_config,
             middleware: self.middleware,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 }
@@ -203,6 +218,47 @@ impl<C, M> Builder<C, M> {
 }
 
 impl<C, M, R> Builder<C, M, R> {
+    /// Set the [`CircuitBreaker`] that fails requests fast while their endpoint is failing.
+    ///
+    /// There is no circuit breaker by default.
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = circuit_breaker;
+    }
+
+    /// Set the [`CircuitBreaker`] that fails requests fast while their endpoint is failing.
+    ///
+    /// There is no circuit breaker by default.
+    pub fn circuit_breaker(mut self, circuit_breaker: Option<CircuitBreaker>) -> Self {
+        self.set_circuit_breaker(circuit_breaker);
+        self
+    }
+
+    /// Add an [`Interceptor`], called after the interceptors already added.
+    pub fn add_interceptor(&mut self, interceptor: impl Interceptor + 'static) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    /// Add an [`Interceptor`], called after the interceptors already added.
+    pub fn interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.add_interceptor(interceptor);
+        self
+    }
+
+    /// Set the [`MetricsSink`] receiving the metrics of every operation.
+    ///
+    /// By default, metrics are discarded.
+    pub fn set_metrics_sink(&mut self, metrics_sink: Option<Arc<dyn MetricsSink>>) {
+        self.metrics_sink = metrics_sink;
+    }
+
+    /// Set the [`MetricsSink`] receiving the metrics of every operation.
+    ///
+    /// By default, metrics are discarded.
+    pub fn metrics_sink(mut self, metrics_sink: Option<Arc<dyn MetricsSink>>) -> Self {
+        self.set_metrics_sink(metrics_sink);
+        self
+    }
+
     /// Use a connector that wraps the current connector.
     pub fn map_connector<F, C2>(self, map: F) -> Builder<C2, M, R>
     where
@@ -214,6 +270,9 @@ impl<C, M, R> Builder<C, M, R> {
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 
@@ -228,6 +287,9 @@ impl<C, M, R> Builder<C, M, R> {
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 
@@ -256,6 +318,11 @@ impl<C, M, R> Builder<C, M, R> {
             middleware: self.middleware,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self
+                .metrics_sink
+                .unwrap_or_else(|| Arc::new(NoOpMetricsSink::new())),
         }
     }
 }
@@ -0,0 +1,732 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Circuit breaking for the requests of a client
+//!
+//! A [`CircuitBreaker`] keeps a circuit for every endpoint, or more generally every
+//! [`RetryPartition`], requests are sent to:
+//! - While the circuit is **closed**, requests are sent normally. Once
+//!   [`failure_threshold`](CircuitBreakerConfig::with_failure_threshold) consecutive attempts have
+//!   failed, the circuit opens.
+//! - While the circuit is **open**, requests fail fast with an [`SdkError::ConstructionFailure`]
+//!   holding a [`CircuitOpenError`], without being sent. After the
+//!   [`cool_down`](CircuitBreakerConfig::with_cool_down) period, the circuit becomes half-open.
+//! - While the circuit is **half-open**, a limited number of probe requests are sent. If a probe
+//!   succeeds, the circuit closes; if it fails, the circuit opens again. The outcome of the requests
+//!   that were sent before the circuit opened doesn't change its state.
+//!
+//! Requests are assigned a circuit right before they are dispatched, once the middleware has
+//! resolved their endpoint.
+//!
+//! An attempt fails when it could not be dispatched, when it timed out, or when the
+//! [`ClassifyResponse`] of its operation classifies it as a [transient](ErrorKind::TransientError),
+//! [server](ErrorKind::ServerError) or [throttling](ErrorKind::ThrottlingError) error.
+//!
+//! State transitions are emitted as `tracing` events.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::retry::{RetryPartition, RetryPartitioning};
+use crate::timeout::is_attempt_timeout;
+use aws_smithy_http::operation::{self, Operation};
+use aws_smithy_http::result::{SdkError, SdkSuccess};
+use aws_smithy_http::retry::ClassifyResponse;
+use aws_smithy_types::retry::{ErrorKind, RetryKind};
+use pin_project_lite::pin_project;
+use tower::{BoxError, Layer, Service};
+
+/// Circuit breaker configuration
+///
+/// Without specific use cases, users should generally rely on the default values set by
+/// [`CircuitBreakerConfig::default`].
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    cool_down: Duration,
+    half_open_probes: u32,
+    partitioning: RetryPartitioning,
+}
+
+impl CircuitBreakerConfig {
+    /// Override the number of consecutive failed attempts that open a circuit. Defaults to `5`.
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    /// Override how long requests fail fast once a circuit is open. Defaults to 30 seconds.
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Override the number of probe requests that can be in flight while a circuit is half-open.
+    /// Defaults to `1`.
+    pub fn with_half_open_probes(mut self, half_open_probes: u32) -> Self {
+        self.half_open_probes = half_open_probes;
+        self
+    }
+
+    /// Override how requests are assigned a circuit
+    ///
+    /// By default, requests are assigned a circuit by the host of their resolved endpoint, see
+    /// [`RetryPartitioning::EndpointHost`].
+    pub fn with_partitioning(mut self, partitioning: RetryPartitioning) -> Self {
+        self.partitioning = partitioning;
+        self
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+            half_open_probes: 1,
+            partitioning: RetryPartitioning::EndpointHost,
+        }
+    }
+}
+
+/// The state of a circuit
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    Closed,
+    /// Requests fail fast without being sent.
+    Open,
+    /// A limited number of probe requests are sent to find out whether the endpoint recovered.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed {
+        consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probes_in_flight: u32,
+        since: Instant,
+    },
+}
+
+/// How a request was let through its circuit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Admission {
+    /// The circuit was closed.
+    Request,
+    /// The circuit was half-open since `since`, and the request is one of its probes.
+    Probe { since: Instant },
+    /// The circuit was open, so the request was not sent.
+    Rejected,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Circuit::Closed {
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Circuit breaker shared by all the requests of a client
+///
+/// Cloning a `CircuitBreaker` yields a handle to the same circuits.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Arc<Mutex<HashMap<RetryPartition, Circuit>>>,
+}
+
+impl CircuitBreaker {
+    /// Create a new circuit breaker whose circuits are all closed.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Default::default(),
+        }
+    }
+
+    /// The current state of the circuit of `partition`.
+    pub fn state(&self, partition: &RetryPartition) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        match circuits.get(partition) {
+            None | Some(Circuit::Closed { .. }) => CircuitState::Closed,
+            Some(Circuit::Open { until }) if Instant::now() >= *until => CircuitState::HalfOpen,
+            Some(Circuit::Open { .. }) => CircuitState::Open,
+            Some(Circuit::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Check whether a request can be sent to `partition` at `now`. Requests sent while the circuit
+    /// is half-open count as probes until their outcome is recorded.
+    fn try_acquire(&self, partition: &RetryPartition, now: Instant) -> Admission {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(partition.clone()).or_default();
+        if let Circuit::Open { until } = circuit {
+            if now < *until {
+                return Admission::Rejected;
+            }
+            tracing::info!(partition = %partition, "circuit half-open, sending probe requests");
+            *circuit = Circuit::HalfOpen {
+                probes_in_flight: 0,
+                since: now,
+            };
+        }
+        match circuit {
+            Circuit::HalfOpen {
+                probes_in_flight,
+                since,
+            } => {
+                // Probes that were cancelled never record their outcome, so they stop counting
+                // against the limit after a cool-down period.
+                if now >= *since + self.config.cool_down {
+                    *probes_in_flight = 0;
+                    *since = now;
+                }
+                if *probes_in_flight >= self.config.half_open_probes {
+                    return Admission::Rejected;
+                }
+                *probes_in_flight += 1;
+                Admission::Probe { since: *since }
+            }
+            _ => Admission::Request,
+        }
+    }
+
+    /// Release the probe slot taken by a request that was admitted but not sent.
+    fn release(&self, partition: &RetryPartition, admission: Admission) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(Circuit::HalfOpen {
+            probes_in_flight,
+            since,
+        }) = circuits.get_mut(partition)
+        {
+            if admission == (Admission::Probe { since: *since }) {
+                *probes_in_flight = probes_in_flight.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Record the outcome of a request admitted by the circuit of `partition`, at `now`.
+    fn record(&self, partition: &RetryPartition, admission: Admission, failed: bool, now: Instant) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(partition.clone()).or_default();
+        match circuit {
+            Circuit::Closed {
+                consecutive_failures,
+            } => {
+                if !failed {
+                    *consecutive_failures = 0;
+                    return;
+                }
+                *consecutive_failures += 1;
+                if *consecutive_failures >= self.config.failure_threshold {
+                    tracing::warn!(
+                        partition = %partition,
+                        consecutive_failures = *consecutive_failures,
+                        cool_down = ?self.config.cool_down,
+                        "circuit opened, failing requests fast"
+                    );
+                    *circuit = Circuit::Open {
+                        until: now + self.config.cool_down,
+                    };
+                }
+            }
+            Circuit::HalfOpen { since, .. }
+                if admission == (Admission::Probe { since: *since }) =>
+            {
+                if failed {
+                    tracing::warn!(
+                        partition = %partition,
+                        cool_down = ?self.config.cool_down,
+                        "probe request failed, circuit opened again"
+                    );
+                    *circuit = Circuit::Open {
+                        until: now + self.config.cool_down,
+                    };
+                } else {
+                    tracing::info!(partition = %partition, "probe request succeeded, circuit closed");
+                    *circuit = Circuit::default();
+                }
+            }
+            // Requests sent before the circuit opened, including the probes of an earlier
+            // half-open period, don't change its state.
+            Circuit::HalfOpen { .. } | Circuit::Open { .. } => {}
+        }
+    }
+}
+
+/// The error requests fail with while their circuit is open
+#[derive(Debug)]
+pub struct CircuitOpenError {
+    partition: RetryPartition,
+}
+
+impl CircuitOpenError {
+    /// The partition whose circuit is open.
+    pub fn partition(&self) -> &RetryPartition {
+        &self.partition
+    }
+}
+
+impl fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the circuit of the `{}` partition is open, the request was not sent",
+            self.partition
+        )
+    }
+}
+
+impl Error for CircuitOpenError {}
+
+/// The circuit breaker an attempt goes through
+///
+/// It is attached to the attempt by the [`CircuitBreakerService`], and checked by
+/// [`check_circuit`] right before the attempt is dispatched, once its endpoint is resolved.
+#[derive(Clone, Debug)]
+pub struct CircuitCheck {
+    circuit_breaker: CircuitBreaker,
+    admission: Arc<Mutex<Option<(RetryPartition, Admission)>>>,
+}
+
+/// Check the circuit of `request`, failing it if the circuit is open
+///
+/// This is called with every attempt right before it is dispatched, and does nothing for the
+/// attempts that don't go through a [`CircuitBreakerService`].
+pub(crate) fn check_circuit(request: &operation::Request) -> Result<(), BoxError> {
+    let check = match request.http().extensions().get::<CircuitCheck>() {
+        Some(check) => check,
+        None => return Ok(()),
+    };
+    let partition = check.circuit_breaker.config.partitioning.partition(request);
+    let admission = check
+        .circuit_breaker
+        .try_acquire(&partition, Instant::now());
+    *check.admission.lock().unwrap() = Some((partition.clone(), admission));
+    if admission == Admission::Rejected {
+        tracing::debug!(partition = %partition, "circuit open, failing request fast");
+        return Err(Box::new(CircuitOpenError { partition }));
+    }
+    Ok(())
+}
+
+/// Whether an attempt, classified as `retry_kind`, shows that its endpoint is failing.
+fn is_failure<T, E>(result: Result<&SdkSuccess<T>, &SdkError<E>>, retry_kind: RetryKind) -> bool {
+    match result {
+        Err(SdkError::DispatchFailure(_)) => true,
+        // Attempts that timed out never reached the retry classifier of the operation
+        Err(err) if is_attempt_timeout(err) => true,
+        _ => matches!(
+            retry_kind,
+            RetryKind::Error(ErrorKind::TransientError)
+                | RetryKind::Error(ErrorKind::ServerError)
+                | RetryKind::Error(ErrorKind::ThrottlingError)
+        ),
+    }
+}
+
+/// A layer that wraps services in a [`CircuitBreakerService`]
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerLayer {
+    circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl CircuitBreakerLayer {
+    /// Create a new `CircuitBreakerLayer`
+    ///
+    /// Requests are never failed fast if `circuit_breaker` is `None`.
+    pub fn new(circuit_breaker: Option<CircuitBreaker>) -> Self {
+        Self { circuit_breaker }
+    }
+}
+
+impl<S> Layer<S> for CircuitBreakerLayer {
+    type Service = CircuitBreakerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreakerService {
+            inner,
+            circuit_breaker: self.circuit_breaker.clone(),
+        }
+    }
+}
+
+/// A service that fails requests fast while the circuit of their endpoint is open
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerService<S> {
+    inner: S,
+    circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl<S, H, R, T, E> Service<Operation<H, R>> for CircuitBreakerService<S>
+where
+    S: Service<Operation<H, R>, Response = SdkSuccess<T>, Error = SdkError<E>>,
+    R: ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = CircuitBreakerFuture<S::Future, R>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        let circuit_breaker = match &self.circuit_breaker {
+            Some(circuit_breaker) => circuit_breaker.clone(),
+            None => {
+                return CircuitBreakerFuture::Unchecked {
+                    future: self.inner.call(req),
+                }
+            }
+        };
+        let check = CircuitCheck {
+            circuit_breaker,
+            admission: Default::default(),
+        };
+        // The circuit is only checked once the middleware has resolved the endpoint of the
+        // attempt. Extensions are not cloned with the operation, so each attempt gets its own.
+        let (mut request, parts) = req.into_request_response();
+        request.http_mut().extensions_mut().insert(check.clone());
+        let req = Operation::from_parts(request, parts);
+        let classifier = req.retry_policy().clone();
+        CircuitBreakerFuture::Checked {
+            future: self.inner.call(req),
+            check,
+            classifier,
+        }
+    }
+}
+
+pin_project! {
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    // This allow is needed because otherwise Clippy will get mad we didn't document the
+    // generated CircuitBreakerFutureProj
+    #[allow(missing_docs)]
+    #[project = CircuitBreakerFutureProj]
+    /// A future generated by a [`CircuitBreakerService`], that records the outcome of the request
+    /// in its circuit.
+    pub enum CircuitBreakerFuture<F, R> {
+        /// A request whose circuit is checked before it is dispatched, and whose outcome will be
+        /// recorded
+        Checked {
+            #[pin]
+            future: F,
+            check: CircuitCheck,
+            classifier: R,
+        },
+        /// A request sent by a service without a circuit breaker
+        Unchecked {
+            #[pin]
+            future: F,
+        },
+    }
+}
+
+impl<F, R, T, E> Future for CircuitBreakerFuture<F, R>
+where
+    F: Future<Output = Result<SdkSuccess<T>, SdkError<E>>>,
+    R: ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            CircuitBreakerFutureProj::Unchecked { future } => future.poll(cx),
+            CircuitBreakerFutureProj::Checked {
+                future,
+                check,
+                classifier,
+            } => {
+                let result = match future.poll(cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => return Poll::Pending,
+                };
+                let (partition, admission) = match check.admission.lock().unwrap().take() {
+                    Some(admission) => admission,
+                    // The attempt failed before it reached the circuit check
+                    None => return Poll::Ready(result),
+                };
+                let circuit_breaker = &check.circuit_breaker;
+                match (&result, admission) {
+                    (_, Admission::Rejected) => {
+                        return Poll::Ready(Err(SdkError::ConstructionFailure(Box::new(
+                            CircuitOpenError { partition },
+                        ))))
+                    }
+                    // The attempt was admitted, but failed before being sent
+                    (Err(SdkError::ConstructionFailure(_)), _) => {
+                        circuit_breaker.release(&partition, admission)
+                    }
+                    _ => {
+                        let failed =
+                            is_failure(result.as_ref(), classifier.classify(result.as_ref()));
+                        circuit_breaker.record(&partition, admission, failed, Instant::now());
+                    }
+                }
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_http::result::ConnectorError;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn partition() -> RetryPartition {
+        RetryPartition::new("example.com")
+    }
+
+    fn circuit_breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            CircuitBreakerConfig::default()
+                .with_failure_threshold(3)
+                .with_cool_down(Duration::from_secs(10)),
+        )
+    }
+
+    #[test]
+    fn consecutive_failures_open_the_circuit() {
+        let circuit_breaker = circuit_breaker();
+        let now = Instant::now();
+        for failed in [true, true, false, true, true] {
+            let admission = circuit_breaker.try_acquire(&partition(), now);
+            assert_eq!(Admission::Request, admission);
+            circuit_breaker.record(&partition(), admission, failed, now);
+        }
+        assert_eq!(CircuitState::Closed, circuit_breaker.state(&partition()));
+
+        circuit_breaker.record(&partition(), Admission::Request, true, now);
+        assert_eq!(CircuitState::Open, circuit_breaker.state(&partition()));
+        assert_eq!(
+            Admission::Rejected,
+            circuit_breaker.try_acquire(&partition(), now + Duration::from_secs(9))
+        );
+        // other partitions are unaffected
+        assert_eq!(
+            Admission::Request,
+            circuit_breaker.try_acquire(&RetryPartition::new("other.com"), now)
+        );
+    }
+
+    #[test]
+    fn half_open_circuits_send_probes() {
+        let circuit_breaker = circuit_breaker();
+        let now = Instant::now();
+        for _ in 0..3 {
+            circuit_breaker.record(&partition(), Admission::Request, true, now);
+        }
+
+        // a failed probe opens the circuit again
+        let after_cool_down = now + Duration::from_secs(10);
+        let probe = circuit_breaker.try_acquire(&partition(), after_cool_down);
+        assert_eq!(
+            Admission::Probe {
+                since: after_cool_down
+            },
+            probe
+        );
+        assert_eq!(
+            Admission::Rejected,
+            circuit_breaker.try_acquire(&partition(), after_cool_down)
+        );
+        circuit_breaker.record(&partition(), probe, true, after_cool_down);
+        assert_eq!(
+            Admission::Rejected,
+            circuit_breaker.try_acquire(&partition(), after_cool_down)
+        );
+
+        // a successful probe closes it
+        let after_second_cool_down = after_cool_down + Duration::from_secs(10);
+        let probe = circuit_breaker.try_acquire(&partition(), after_second_cool_down);
+        circuit_breaker.record(&partition(), probe, false, after_second_cool_down);
+        assert_eq!(CircuitState::Closed, circuit_breaker.state(&partition()));
+        assert_eq!(
+            Admission::Request,
+            circuit_breaker.try_acquire(&partition(), after_second_cool_down)
+        );
+    }
+
+    #[test]
+    fn only_probes_close_half_open_circuits() {
+        let circuit_breaker = circuit_breaker();
+        let now = Instant::now();
+        let slow_request = circuit_breaker.try_acquire(&partition(), now);
+        for _ in 0..3 {
+            circuit_breaker.record(&partition(), Admission::Request, true, now);
+        }
+        let after_cool_down = now + Duration::from_secs(10);
+        let probe = circuit_breaker.try_acquire(&partition(), after_cool_down);
+
+        // a request sent before the circuit opened succeeds while the probe is in flight
+        circuit_breaker.record(&partition(), slow_request, false, after_cool_down);
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.state(&partition()));
+        // so does a probe of an earlier half-open period
+        let stale_probe = Admission::Probe { since: now };
+        circuit_breaker.record(&partition(), stale_probe, false, after_cool_down);
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.state(&partition()));
+
+        // a probe that was not sent frees its slot
+        circuit_breaker.release(&partition(), probe);
+        let probe = circuit_breaker.try_acquire(&partition(), after_cool_down);
+        assert!(matches!(probe, Admission::Probe { .. }));
+        circuit_breaker.record(&partition(), probe, false, after_cool_down);
+        assert_eq!(CircuitState::Closed, circuit_breaker.state(&partition()));
+    }
+
+    /// Classifies `429` responses as throttling errors, and `502`, `503` and `504` responses as
+    /// server errors.
+    #[derive(Clone)]
+    struct TestClassifier;
+
+    impl ClassifyResponse<SdkSuccess<()>, SdkError<Infallible>> for TestClassifier {
+        fn classify(&self, result: Result<&SdkSuccess<()>, &SdkError<Infallible>>) -> RetryKind {
+            match result.map(|success| success.raw.http().status().as_u16()) {
+                Ok(429) => RetryKind::Error(ErrorKind::ThrottlingError),
+                Ok(502) | Ok(503) | Ok(504) => RetryKind::Error(ErrorKind::ServerError),
+                Ok(_) => RetryKind::Unnecessary,
+                Err(_) => RetryKind::UnretryableFailure,
+            }
+        }
+    }
+
+    fn operation() -> Operation<(), TestClassifier> {
+        let request = http::Request::builder()
+            .uri("https://example.com")
+            .body(SdkBody::empty())
+            .unwrap();
+        Operation::new(operation::Request::new(request), ()).with_retry_policy(TestClassifier)
+    }
+
+    fn response(status: u16) -> SdkSuccess<()> {
+        let response = http::Response::builder()
+            .status(status)
+            .body(SdkBody::empty())
+            .unwrap();
+        SdkSuccess {
+            raw: operation::Response::new(response),
+            parsed: (),
+        }
+    }
+
+    /// An inner service that checks the circuit of its requests like the dispatch service of the
+    /// client, and responds with `status` or fails to dispatch them.
+    fn dispatching_service(
+        calls: Arc<AtomicUsize>,
+        status: Option<u16>,
+    ) -> impl Service<
+        Operation<(), TestClassifier>,
+        Response = SdkSuccess<()>,
+        Error = SdkError<Infallible>,
+    > + Clone {
+        tower::service_fn(move |req: Operation<(), TestClassifier>| {
+            let checked = check_circuit(req.request());
+            let calls = calls.clone();
+            async move {
+                checked.map_err(SdkError::ConstructionFailure)?;
+                calls.fetch_add(1, Ordering::SeqCst);
+                match status {
+                    Some(status) => Ok(response(status)),
+                    None => Err(SdkError::DispatchFailure(ConnectorError::io(
+                        "connection refused".into(),
+                    ))),
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn open_circuits_fail_fast() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = CircuitBreakerLayer::new(Some(circuit_breaker()))
+            .layer(dispatching_service(calls.clone(), None));
+
+        for _ in 0..3 {
+            let err = svc.clone().oneshot(operation()).await.unwrap_err();
+            assert!(matches!(err, SdkError::DispatchFailure(_)), "{:?}", err);
+        }
+        let err = svc.clone().oneshot(operation()).await.unwrap_err();
+        match err {
+            SdkError::ConstructionFailure(err) => {
+                let err = err.downcast_ref::<CircuitOpenError>().unwrap();
+                assert_eq!("example.com", err.partition().name());
+            }
+            err => panic!("expected the request to fail fast, got {:?}", err),
+        }
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn classified_errors_are_failures() {
+        let circuit_breaker = circuit_breaker();
+        let send = |status: u16| {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let svc = CircuitBreakerLayer::new(Some(circuit_breaker.clone()))
+                .layer(dispatching_service(calls, Some(status)));
+            svc.oneshot(operation())
+        };
+        // a `500` that is not classified as a retryable error doesn't count as a failure
+        for status in [503, 429, 500, 502, 504, 404] {
+            send(status).await.unwrap();
+        }
+        assert_eq!(CircuitState::Closed, circuit_breaker.state(&partition()));
+
+        for status in [429, 503] {
+            send(status).await.unwrap();
+        }
+        assert_eq!(CircuitState::Closed, circuit_breaker.state(&partition()));
+        send(429).await.unwrap();
+        assert_eq!(CircuitState::Open, circuit_breaker.state(&partition()));
+    }
+
+    #[test]
+    fn failures_follow_the_retry_classification() {
+        let ok = response(200);
+        assert!(!is_failure::<(), ()>(Ok(&ok), RetryKind::Unnecessary));
+        for kind in [
+            ErrorKind::TransientError,
+            ErrorKind::ServerError,
+            ErrorKind::ThrottlingError,
+        ] {
+            assert!(is_failure::<(), ()>(Ok(&ok), RetryKind::Error(kind)));
+        }
+        assert!(!is_failure::<(), ()>(
+            Ok(&ok),
+            RetryKind::Error(ErrorKind::ClientError)
+        ));
+
+        let server_error = response(500);
+        assert!(!is_failure::<(), ()>(
+            Ok(&server_error),
+            RetryKind::UnretryableFailure
+        ));
+        let dispatch_failure = SdkError::DispatchFailure(ConnectorError::io("reset".into()));
+        assert!(is_failure::<(), ()>(
+            Err(&dispatch_failure),
+            RetryKind::UnretryableFailure
+        ));
+    }
+}
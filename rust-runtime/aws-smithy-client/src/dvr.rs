@@ -8,17 +8,26 @@
 //! Warning: Extremely experimental, API likely to change.
 //!
 //! DVR is an extremely experimental record & replay framework that supports multi-frame HTTP request / response traffic.
+//!
+//! Traffic is recorded in the [`V1`](Version::V1) format, which captures response trailers, the
+//! delays between events, and why connections and bodies failed. Traffic recorded in the
+//! [`V0`](Version::V0) format can still be replayed, and can be upgraded with
+//! [`NetworkTraffic::migrate`](NetworkTraffic::migrate).
 
 use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::time::Duration;
 
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 use aws_smithy_types::base64;
 pub use record::RecordingConnection;
-pub use replay::ReplayingConnection;
+pub use redaction::{RedactionPolicy, REDACTED};
+pub use replay::{ReplayingConnection, RequestMatching};
 
 mod record;
+mod redaction;
 mod replay;
 
 /// A complete traffic recording
@@ -36,6 +45,32 @@ impl NetworkTraffic {
     pub fn events(&self) -> &Vec<Event> {
         &self.events
     }
+
+    /// Serialization version of the recording
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Upgrade a recording to the latest version
+    ///
+    /// V0 recordings don't record why a connection or a body failed: these failures are migrated
+    /// as [`ErrorKind::Other`](ErrorKind::Other) failures.
+    pub fn migrate(mut self) -> Self {
+        if self.version == Version::V0 {
+            for event in &mut self.events {
+                if let Action::Eof {
+                    ok: false,
+                    error: error @ None,
+                    ..
+                } = &mut event.action
+                {
+                    *error = Some(Error::new(ErrorKind::Other, "body failed"));
+                }
+            }
+            self.version = Version::V1;
+        }
+        self
+    }
 }
 
 /// Serialization version of DVR data
@@ -43,6 +78,8 @@ impl NetworkTraffic {
 pub enum Version {
     /// Initial network traffic version
     V0,
+    /// Network traffic with trailers, delays between events, failure kinds and compressed bodies
+    V1,
 }
 
 /// A network traffic recording may contain multiple different connections occurring simultaneously
@@ -57,6 +94,24 @@ pub struct ConnectionId(usize);
 pub struct Event {
     connection_id: ConnectionId,
     action: Action,
+    /// Time elapsed since the previous event of the connection, in milliseconds (V1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delay_ms: Option<u64>,
+}
+
+impl Event {
+    fn new(connection_id: ConnectionId, action: Action, delay: Duration) -> Self {
+        Self {
+            connection_id,
+            action,
+            delay_ms: Some(delay.as_millis() as u64).filter(|ms| *ms > 0),
+        }
+    }
+
+    /// Take the delay to wait for before replaying this event
+    fn take_delay(&mut self) -> Option<Duration> {
+        self.delay_ms.take().map(Duration::from_millis)
+    }
 }
 
 /// An initial HTTP request, roughly equivalent to `http::Request<()>`
@@ -66,7 +121,7 @@ pub struct Event {
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct Request {
     uri: String,
-    headers: HashMap<String, Vec<String>>,
+    headers: Headers,
     method: String,
 }
 
@@ -78,25 +133,76 @@ pub struct Request {
 pub struct Response {
     status: u16,
     version: String,
-    headers: HashMap<String, Vec<String>>,
+    headers: Headers,
 }
 
-impl From<&Request> for http::Request<()> {
-    fn from(request: &Request) -> Self {
-        let mut builder = http::Request::builder().uri(request.uri.as_str());
-        for (k, values) in request.headers.iter() {
-            for v in values {
-                builder = builder.header(k, v);
+/// HTTP headers or trailers
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Headers(HashMap<String, Vec<HeaderValue>>);
+
+/// A header value, recorded as a string unless it isn't valid UTF-8
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(untagged)]
+enum HeaderValue {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl From<&http::HeaderMap> for Headers {
+    fn from(headers: &http::HeaderMap) -> Self {
+        let mut out: HashMap<_, Vec<_>> = HashMap::new();
+        for (header_name, header_value) in headers.iter() {
+            let entry = out.entry(header_name.to_string()).or_default();
+            entry.push(match std::str::from_utf8(header_value.as_bytes()) {
+                Ok(text) => HeaderValue::Text(text.to_string()),
+                Err(_) => HeaderValue::Binary {
+                    base64: base64::encode(header_value.as_bytes()),
+                },
+            });
+        }
+        Headers(out)
+    }
+}
+
+impl From<&Headers> for http::HeaderMap {
+    fn from(headers: &Headers) -> Self {
+        let mut out = http::HeaderMap::new();
+        for (name, values) in headers.0.iter() {
+            let name = http::header::HeaderName::from_bytes(name.as_bytes())
+                .expect("recorded header names are valid");
+            for value in values {
+                let value = match value {
+                    HeaderValue::Text(text) => http::HeaderValue::from_bytes(text.as_bytes()),
+                    HeaderValue::Binary { base64 } => http::HeaderValue::from_bytes(
+                        &base64::decode(base64).expect("binary header values are base64 encoded"),
+                    ),
+                };
+                out.append(
+                    name.clone(),
+                    value.expect("recorded header values are valid"),
+                );
             }
         }
-        builder.method(request.method.as_str()).body(()).unwrap()
+        out
+    }
+}
+
+impl From<&Request> for http::Request<()> {
+    fn from(request: &Request) -> Self {
+        let mut req = http::Request::builder()
+            .uri(request.uri.as_str())
+            .method(request.method.as_str())
+            .body(())
+            .unwrap();
+        *req.headers_mut() = (&request.headers).into();
+        req
     }
 }
 
 impl<'a, B> From<&'a http::Request<B>> for Request {
     fn from(req: &'a http::Request<B>) -> Self {
         let uri = req.uri().to_string();
-        let headers = headers_to_map(req.headers());
+        let headers = req.headers().into();
         let method = req.method().to_string();
         Self {
             uri,
@@ -106,20 +212,11 @@ impl<'a, B> From<&'a http::Request<B>> for Request {
     }
 }
 
-fn headers_to_map(headers: &http::HeaderMap<http::HeaderValue>) -> HashMap<String, Vec<String>> {
-    let mut out: HashMap<_, Vec<_>> = HashMap::new();
-    for (header_name, header_value) in headers.iter() {
-        let entry = out.entry(header_name.to_string()).or_default();
-        entry.push(header_value.to_str().unwrap().to_string());
-    }
-    out
-}
-
 impl<'a, B> From<&'a http::Response<B>> for Response {
     fn from(resp: &'a http::Response<B>) -> Self {
         let status = resp.status().as_u16();
         let version = format!("{:?}", resp.version());
-        let headers = headers_to_map(resp.headers());
+        let headers = resp.headers().into();
         Self {
             status,
             version,
@@ -130,7 +227,104 @@ impl<'a, B> From<&'a http::Response<B>> for Response {
 
 /// Error response wrapper
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
-pub struct Error(String);
+#[serde(from = "ErrorRepr")]
+pub struct Error {
+    message: String,
+    kind: ErrorKind,
+}
+
+/// V0 errors only recorded a message
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorRepr {
+    Message(String),
+    Error {
+        message: String,
+        #[serde(default)]
+        kind: ErrorKind,
+    },
+}
+
+impl From<ErrorRepr> for Error {
+    fn from(repr: ErrorRepr) -> Self {
+        match repr {
+            ErrorRepr::Message(message) => Error::new(ErrorKind::Other, message),
+            ErrorRepr::Error { message, kind } => Error::new(kind, message),
+        }
+    }
+}
+
+impl Error {
+    fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind,
+        }
+    }
+
+    /// Record `err`, classifying it by the first IO error of its source chain
+    fn from_error(err: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(err);
+        let mut kind = ErrorKind::Other;
+        while let Some(err) = source {
+            if let Some(io_err) = err.downcast_ref::<io::Error>() {
+                kind = ErrorKind::from(io_err.kind());
+                break;
+            }
+            source = err.source();
+        }
+        Self::new(kind, err.to_string())
+    }
+
+    /// The kind of failure
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    fn into_io_error(self) -> io::Error {
+        let kind = match self.kind {
+            ErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+            ErrorKind::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            ErrorKind::BrokenPipe => io::ErrorKind::BrokenPipe,
+            ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            ErrorKind::TimedOut => io::ErrorKind::TimedOut,
+            ErrorKind::Other => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, self.message)
+    }
+}
+
+/// Why a connection or a body failed
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The connection was reset by the peer
+    ConnectionReset,
+    /// The connection was aborted by the peer
+    ConnectionAborted,
+    /// The connection was closed while writing
+    BrokenPipe,
+    /// The connection was closed before the end of the body
+    UnexpectedEof,
+    /// The connection timed out
+    TimedOut,
+    /// Any other failure
+    #[default]
+    Other,
+}
+
+impl From<io::ErrorKind> for ErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::ConnectionReset => ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted => ErrorKind::ConnectionAborted,
+            io::ErrorKind::BrokenPipe => ErrorKind::BrokenPipe,
+            io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+            io::ErrorKind::TimedOut => ErrorKind::TimedOut,
+            _ => ErrorKind::Other,
+        }
+    }
+}
 
 /// Network Action
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -156,12 +350,23 @@ pub enum Action {
         direction: Direction,
     },
 
+    /// Trailers sent after the body data (V1)
+    Trailers {
+        /// Trailer names and values
+        trailers: Headers,
+        /// Direction: request vs. response
+        direction: Direction,
+    },
+
     /// End of data
     Eof {
         /// Succesful vs. failed termination
         ok: bool,
         /// Direction: request vs. response
         direction: Direction,
+        /// Why the body failed when `ok` is false (V1)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<Error>,
     },
 }
 
@@ -186,6 +391,22 @@ impl Direction {
     }
 }
 
+/// Compression of the large data segments of a recording
+///
+/// Data segments of at least 1 KiB are compressed, then base64 encoded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BodyCompression {
+    /// Record data uncompressed
+    None,
+    /// Compress data with gzip
+    Gzip,
+    /// Compress data with zstd
+    Zstd,
+}
+
+const COMPRESSION_THRESHOLD: usize = 1024;
+
 /// HTTP Body Data Abstraction
 ///
 /// When the data is a UTF-8 encoded string, it will be serialized as a string for readability.
@@ -198,6 +419,12 @@ pub enum BodyData {
 
     /// Base64 encoded binary data
     Base64(String),
+
+    /// Base64 encoded gzip compressed data (V1)
+    Gzip(String),
+
+    /// Base64 encoded zstd compressed data (V1)
+    Zstd(String),
 }
 
 impl BodyData {
@@ -205,7 +432,7 @@ impl BodyData {
     pub fn into_bytes(self) -> Vec<u8> {
         match self {
             BodyData::Utf8(string) => string.into_bytes(),
-            BodyData::Base64(string) => base64::decode(string).unwrap(),
+            other => other.copy_to_vec(),
         }
     }
 
@@ -214,6 +441,37 @@ impl BodyData {
         match self {
             BodyData::Utf8(string) => string.as_bytes().into(),
             BodyData::Base64(string) => base64::decode(string).unwrap(),
+            BodyData::Gzip(string) => {
+                let mut data = vec![];
+                flate2::read::GzDecoder::new(base64::decode(string).unwrap().as_slice())
+                    .read_to_end(&mut data)
+                    .expect("gzip data is valid");
+                data
+            }
+            BodyData::Zstd(string) => zstd::decode_all(base64::decode(string).unwrap().as_slice())
+                .expect("zstd data is valid"),
+        }
+    }
+
+    /// Record `data`, compressing it if it is large enough
+    fn compressed(data: Bytes, compression: BodyCompression) -> Self {
+        if data.len() < COMPRESSION_THRESHOLD {
+            return BodyData::from(data);
+        }
+        match compression {
+            BodyCompression::None => BodyData::from(data),
+            BodyCompression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder
+                    .write_all(&data)
+                    .and_then(|_| encoder.finish())
+                    .map(|compressed| BodyData::Gzip(base64::encode(compressed)))
+                    .expect("compressing in memory data cannot fail")
+            }
+            BodyCompression::Zstd => BodyData::Zstd(base64::encode(
+                zstd::encode_all(data.as_ref(), 0).expect("compressing in memory data cannot fail"),
+            )),
         }
     }
 }
@@ -232,12 +490,24 @@ mod tests {
     use std::error::Error;
     use std::fs;
 
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
     use aws_smithy_http::body::SdkBody;
     use aws_smithy_http::byte_stream::ByteStream;
 
-    use crate::dvr::{NetworkTraffic, RecordingConnection, ReplayingConnection};
+    use aws_smithy_http::result::ConnectorError;
     use bytes::Bytes;
-    use http::Uri;
+    use http::{HeaderMap, Uri};
+    use http_body::Body;
+    use tower::Service;
+
+    use crate::dvr::{
+        Action, BodyCompression, BodyData, ErrorKind, NetworkTraffic, RecordingConnection,
+        RedactionPolicy, ReplayingConnection, RequestMatching, Version,
+    };
 
     #[tokio::test]
     async fn turtles_all_the_way_down() -> Result<(), Box<dyn Error>> {
@@ -250,7 +520,6 @@ mod tests {
         let req = http::Request::post("https://www.example.com")
             .body(SdkBody::from("hello world"))
             .unwrap();
-        use tower::Service;
         let mut resp = connection.call(req).await.expect("ok");
         let body = std::mem::replace(resp.body_mut(), SdkBody::taken());
         let data = ByteStream::new(body).collect().await.unwrap().into_bytes();
@@ -273,4 +542,383 @@ mod tests {
         );
         Ok(())
     }
+
+    async fn send(
+        connection: &mut impl tower::Service<
+            http::Request<SdkBody>,
+            Response = http::Response<SdkBody>,
+            Error = ConnectorError,
+        >,
+        uri: &str,
+        body: &'static str,
+    ) -> String {
+        let req = http::Request::post(uri)
+            .header("authorization", "AWS4-HMAC-SHA256 Signature=abcd")
+            .body(SdkBody::from(body))
+            .unwrap();
+        let resp = connection.call(req).await.expect("ok");
+        let data = ByteStream::new(resp.into_body())
+            .collect()
+            .await
+            .unwrap()
+            .into_bytes();
+        String::from_utf8(data.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn redacted_recordings_replay_out_of_order() {
+        // echo the request body, and return a secret
+        let inner = tower::service_fn(|req: http::Request<SdkBody>| async move {
+            let body = ByteStream::new(req.into_body()).collect().await.unwrap();
+            Ok::<_, ConnectorError>(
+                http::Response::builder()
+                    .header("set-cookie", "session=1234")
+                    .body(SdkBody::from(format!(
+                        r#"{{"Body":"{}","Credentials":{{"SecretAccessKey":"secret"}}}}"#,
+                        String::from_utf8(body.into_bytes().to_vec()).unwrap()
+                    )))
+                    .unwrap(),
+            )
+        });
+        let mut connection =
+            RecordingConnection::new(inner).with_redaction_policy(RedactionPolicy::credentials());
+        send(
+            &mut connection,
+            "https://example.com/a?b=1&X-Amz-Signature=abcd",
+            "a",
+        )
+        .await;
+        send(&mut connection, "https://example.com/b", "b").await;
+
+        let traffic = serde_json::to_string(&connection.network_traffic()).unwrap();
+        for secret in ["AWS4-HMAC-SHA256", "abcd", "session=1234", "\"secret\""] {
+            assert!(!traffic.contains(secret), "{} was recorded", secret);
+        }
+        let traffic: NetworkTraffic = serde_json::from_str(&traffic).unwrap();
+
+        let mut replayer = ReplayingConnection::new(traffic.events().clone())
+            .with_request_matching(RequestMatching::NormalizedQuery);
+        assert_eq!(
+            r#"{"Body":"b","Credentials":{"SecretAccessKey":"**REDACTED**"}}"#,
+            send(&mut replayer, "https://example.com/b", "b").await
+        );
+        assert_eq!(
+            r#"{"Body":"a","Credentials":{"SecretAccessKey":"**REDACTED**"}}"#,
+            send(
+                &mut replayer,
+                "https://example.com/a?X-Amz-Signature=efgh&b=1",
+                "a"
+            )
+            .await
+        );
+        let requests = replayer.take_requests().await;
+        assert_eq!("/a", requests[0].uri().path());
+        assert_eq!("/b", requests[1].uri().path());
+    }
+
+    #[tokio::test]
+    async fn redacted_query_params_match_any_value() {
+        let inner = tower::service_fn(|req: http::Request<SdkBody>| async move {
+            ByteStream::new(req.into_body()).collect().await.unwrap();
+            Ok::<_, ConnectorError>(http::Response::new(SdkBody::from("ok")))
+        });
+        let mut connection =
+            RecordingConnection::new(inner).with_redaction_policy(RedactionPolicy::credentials());
+        send(
+            &mut connection,
+            "https://example.com/a?b=1&X-Amz-Signature=abcd",
+            "a",
+        )
+        .await;
+
+        for request_matching in [
+            RequestMatching::MethodAndUri,
+            RequestMatching::MethodUriAndBody,
+            RequestMatching::NormalizedQuery,
+        ] {
+            let mut replaying = replayer(&connection).with_request_matching(request_matching);
+            assert_eq!(
+                "ok",
+                send(
+                    &mut replaying,
+                    "https://example.com/a?b=1&X-Amz-Signature=efgh",
+                    "a"
+                )
+                .await
+            );
+
+            let mut replaying = replayer(&connection).with_request_matching(request_matching);
+            let req = http::Request::post("https://example.com/a?b=2&X-Amz-Signature=efgh")
+                .body(SdkBody::from("a"))
+                .unwrap();
+            replaying.call(req).await.expect_err("query doesn't match");
+        }
+    }
+
+    #[tokio::test]
+    async fn redacted_request_bodies_match_any_value() {
+        let inner = tower::service_fn(|req: http::Request<SdkBody>| async move {
+            ByteStream::new(req.into_body()).collect().await.unwrap();
+            Ok::<_, ConnectorError>(http::Response::new(SdkBody::from("ok")))
+        });
+        let policy = RedactionPolicy::credentials();
+        let mut connection = RecordingConnection::new(inner).with_redaction_policy(policy.clone());
+        send(
+            &mut connection,
+            "https://example.com/a",
+            r#"{"Name":"a","SessionToken":"abcd"}"#,
+        )
+        .await;
+
+        for request_matching in [
+            RequestMatching::MethodUriAndBody,
+            RequestMatching::NormalizedQuery,
+        ] {
+            let mut replaying = replayer(&connection)
+                .with_request_matching(request_matching)
+                .with_redaction_policy(policy.clone());
+            assert_eq!(
+                "ok",
+                send(
+                    &mut replaying,
+                    "https://example.com/a",
+                    r#"{"Name":"a","SessionToken":"efgh"}"#
+                )
+                .await
+            );
+            // the request is recorded as it was sent
+            let requests = replaying.take_requests().await;
+            assert_eq!(
+                &Bytes::from_static(br#"{"Name":"a","SessionToken":"efgh"}"#),
+                requests[0].body()
+            );
+
+            let mut replaying = replayer(&connection)
+                .with_request_matching(request_matching)
+                .with_redaction_policy(policy.clone());
+            let req = http::Request::post("https://example.com/a")
+                .body(SdkBody::from(r#"{"Name":"b","SessionToken":"abcd"}"#))
+                .unwrap();
+            replaying.call(req).await.expect_err("body doesn't match");
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_must_match_a_recording() {
+        let network_traffic = fs::read_to_string("test-data/example.com.json").unwrap();
+        let network_traffic: NetworkTraffic = serde_json::from_str(&network_traffic).unwrap();
+        let mut replayer = ReplayingConnection::new(network_traffic.events.clone())
+            .with_request_matching(RequestMatching::MethodUriAndBody);
+        let req = http::Request::post("https://www.example.com")
+            .body(SdkBody::from("goodbye world"))
+            .unwrap();
+        replayer.call(req).await.expect_err("body doesn't match");
+    }
+
+    /// Serialize and deserialize the traffic recorded by `connection`, then replay it
+    fn replayer<S>(connection: &RecordingConnection<S>) -> ReplayingConnection {
+        let traffic = serde_json::to_string(&connection.network_traffic()).unwrap();
+        let traffic: NetworkTraffic = serde_json::from_str(&traffic).unwrap();
+        assert_eq!(Version::V1, traffic.version());
+        ReplayingConnection::new(traffic.events().clone())
+    }
+
+    fn request() -> http::Request<SdkBody> {
+        http::Request::get("https://www.example.com")
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    /// Read a body with its trailers, keeping the kind of IO errors
+    async fn read_body(
+        mut body: SdkBody,
+    ) -> (Vec<Result<Bytes, Option<io::ErrorKind>>>, Option<HeaderMap>) {
+        let mut data = vec![];
+        while let Some(chunk) = body.data().await {
+            let failed = chunk.is_err();
+            data.push(chunk.map_err(|err| err.downcast_ref::<io::Error>().map(|err| err.kind())));
+            if failed {
+                return (data, None);
+            }
+        }
+        (data, body.trailers().await.unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_trailers_and_headers_are_replayed() {
+        let inner = tower::service_fn(|_req: http::Request<SdkBody>| async move {
+            let (mut sender, body) = hyper::Body::channel();
+            tokio::spawn(async move {
+                sender.send_data(Bytes::from_static(b"a")).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                sender.send_data(Bytes::from_static(b"b")).await.unwrap();
+                let mut trailers = HeaderMap::new();
+                trailers.insert("x-checksum", "1234".parse().unwrap());
+                sender.send_trailers(trailers).await.unwrap();
+            });
+            Ok::<_, ConnectorError>(
+                http::Response::builder()
+                    .header(
+                        "x-utf8",
+                        http::HeaderValue::from_bytes("café".as_bytes()).unwrap(),
+                    )
+                    .header("x-binary", http::HeaderValue::from_bytes(b"\xff").unwrap())
+                    .body(SdkBody::from(body))
+                    .unwrap(),
+            )
+        });
+        let mut connection = RecordingConnection::new(inner);
+        let resp = connection.call(request()).await.unwrap();
+        let recorded = read_body(resp.into_body()).await;
+
+        let mut replaying = replayer(&connection).with_replayed_delays(true);
+        let resp = replaying.call(request()).await.unwrap();
+        assert_eq!("café".as_bytes(), resp.headers()["x-utf8"].as_bytes());
+        assert_eq!(b"\xff", resp.headers()["x-binary"].as_bytes());
+        let start = tokio::time::Instant::now();
+        let (data, trailers) = read_body(resp.into_body()).await;
+        aws_smithy_async::assert_elapsed!(start, Duration::from_millis(100));
+        assert_eq!(recorded, (data.clone(), trailers.clone()));
+        assert_eq!(
+            vec![Ok(Bytes::from_static(b"a")), Ok(Bytes::from_static(b"b"))],
+            data
+        );
+        assert_eq!("1234", trailers.unwrap()["x-checksum"]);
+
+        // delays are only replayed when enabled
+        let start = tokio::time::Instant::now();
+        let resp = replayer(&connection).call(request()).await.unwrap();
+        assert_eq!(recorded, read_body(resp.into_body()).await);
+        aws_smithy_async::assert_elapsed!(start, Duration::from_millis(0));
+    }
+
+    /// Body returning `data`, then failing with a connection reset
+    struct ResetBody(Option<Bytes>);
+
+    impl Body for ResetBody {
+        type Data = Bytes;
+        type Error = io::Error;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            Poll::Ready(Some(
+                self.0
+                    .take()
+                    .ok_or_else(|| io::ErrorKind::ConnectionReset.into()),
+            ))
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+            Poll::Ready(Ok(None))
+        }
+    }
+
+    #[tokio::test]
+    async fn failures_are_replayed() {
+        let inner = tower::service_fn(|req: http::Request<SdkBody>| async move {
+            if req.uri().path() == "/timeout" {
+                return Err(ConnectorError::timeout("connect timed out".into()));
+            }
+            let body = ResetBody(Some(Bytes::from_static(b"partial")));
+            Ok(http::Response::new(SdkBody::from_dyn(
+                http_body::combinators::BoxBody::new(body.map_err(|err| err.into())),
+            )))
+        });
+        let mut connection = RecordingConnection::new(inner);
+        let resp = connection.call(request()).await.unwrap();
+        read_body(resp.into_body()).await;
+        let req = http::Request::get("https://www.example.com/timeout")
+            .body(SdkBody::empty())
+            .unwrap();
+        connection.call(req).await.expect_err("timeout");
+
+        let mut replayer = replayer(&connection);
+        let resp = replayer.call(request()).await.unwrap();
+        assert_eq!(
+            vec![
+                Ok(Bytes::from_static(b"partial")),
+                Err(Some(io::ErrorKind::ConnectionReset))
+            ],
+            read_body(resp.into_body()).await.0
+        );
+        let req = http::Request::get("https://www.example.com/timeout")
+            .body(SdkBody::empty())
+            .unwrap();
+        let err = replayer.call(req).await.expect_err("timeout");
+        assert!(err.is_timeout(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_compressed() {
+        for compression in [BodyCompression::Gzip, BodyCompression::Zstd] {
+            let body = Bytes::from("x".repeat(4096));
+            let response = body.clone();
+            let inner = tower::service_fn(move |_req: http::Request<SdkBody>| {
+                let response = response.clone();
+                async move { Ok::<_, ConnectorError>(http::Response::new(SdkBody::from(response))) }
+            });
+            let mut connection = RecordingConnection::new(inner).with_body_compression(compression);
+            let resp = connection.call(request()).await.unwrap();
+            read_body(resp.into_body()).await;
+            let recorded = connection
+                .events()
+                .iter()
+                .filter_map(|event| match &event.action {
+                    Action::Data { data, .. } => Some(data.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            match (compression, &recorded[..]) {
+                (BodyCompression::Gzip, [BodyData::Gzip(data)])
+                | (BodyCompression::Zstd, [BodyData::Zstd(data)]) => {
+                    assert!(data.len() < 1024, "{} wasn't compressed", data)
+                }
+                other => panic!("unexpected recording: {:?}", other),
+            }
+
+            let resp = replayer(&connection).call(request()).await.unwrap();
+            assert_eq!(vec![Ok(body)], read_body(resp.into_body()).await.0);
+        }
+    }
+
+    #[test]
+    fn v0_traffic_is_migrated() {
+        let network_traffic = fs::read_to_string("test-data/example.com.json").unwrap();
+        let network_traffic: NetworkTraffic = serde_json::from_str(&network_traffic).unwrap();
+        assert_eq!(Version::V0, network_traffic.version());
+        let events = network_traffic.events().clone();
+        let migrated = network_traffic.migrate();
+        assert_eq!(Version::V1, migrated.version());
+        assert_eq!(&events, migrated.events());
+
+        let network_traffic: NetworkTraffic = serde_json::from_str(
+            r#"{
+              "events": [
+                { "connection_id": 0, "action": { "Response": { "response": { "Err": "dispatch failure" } } } },
+                { "connection_id": 1, "action": { "Eof": { "ok": false, "direction": "Response" } } }
+              ],
+              "docs": null,
+              "version": "V0"
+            }"#,
+        )
+        .unwrap();
+        let migrated = network_traffic.migrate();
+        match &migrated.events()[0].action {
+            Action::Response { response: Err(err) } => assert_eq!(ErrorKind::Other, err.kind()),
+            other => panic!("unexpected action: {:?}", other),
+        }
+        match &migrated.events()[1].action {
+            Action::Eof {
+                ok: false,
+                error: Some(err),
+                ..
+            } => assert_eq!(ErrorKind::Other, err.kind()),
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
 }
@@ -9,25 +9,37 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::task::{Context, Poll};
 
+use std::any::Any;
+use std::fmt::Display;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
 use http_body::Body;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tower::Service;
 
 use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
 
-use crate::dvr::{self, Action, BodyData, ConnectionId, Direction, Error, NetworkTraffic, Version};
+use crate::dvr::{
+    self, Action, BodyCompression, BodyData, ConnectionId, Direction, Error, ErrorKind,
+    NetworkTraffic, RedactionPolicy, Version,
+};
 
 use super::Event;
-use std::fmt::Display;
 
 /// Recording Connection Wrapper
 ///
 /// RecordingConnection wraps an inner connection and records all traffic, enabling traffic replay.
+/// Secrets can be removed from the recorded traffic with a [`RedactionPolicy`](RedactionPolicy).
 #[derive(Clone, Debug)]
 pub struct RecordingConnection<S> {
     pub(crate) data: Arc<Mutex<Vec<Event>>>,
     pub(crate) num_events: Arc<AtomicUsize>,
     pub(crate) inner: S,
+    pub(crate) redaction_policy: Arc<RedactionPolicy>,
+    pub(crate) body_compression: BodyCompression,
 }
 
 impl RecordingConnection<crate::conns::Https> {
@@ -38,6 +50,8 @@ impl RecordingConnection<crate::conns::Https> {
             data: Default::default(),
             inner: crate::conns::https(),
             num_events: Arc::new(AtomicUsize::new(0)),
+            redaction_policy: Default::default(),
+            body_compression: BodyCompression::None,
         }
     }
 }
@@ -49,9 +63,23 @@ impl<S> RecordingConnection<S> {
             data: Default::default(),
             inner: connection,
             num_events: Arc::new(AtomicUsize::new(0)),
+            redaction_policy: Default::default(),
+            body_compression: BodyCompression::None,
         }
     }
 
+    /// Redact the secrets matched by `policy` from the recorded traffic
+    pub fn with_redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = Arc::new(policy);
+        self
+    }
+
+    /// Compress large data segments to keep recordings of large bodies small
+    pub fn with_body_compression(mut self, compression: BodyCompression) -> Self {
+        self.body_compression = compression;
+        self
+    }
+
     /// Return the traffic recorded by this connection
     pub fn events(&self) -> MutexGuard<'_, Vec<Event>> {
         self.data.lock().unwrap()
@@ -62,23 +90,89 @@ impl<S> RecordingConnection<S> {
         NetworkTraffic {
             events: self.events().clone(),
             docs: Some("todo docs".into()),
-            version: Version::V0,
+            version: Version::V1,
         }
     }
 
     fn next_id(&self) -> ConnectionId {
         ConnectionId(self.num_events.fetch_add(1, Ordering::Relaxed))
     }
+
+    fn recorder(&self, connection_id: ConnectionId, direction: Direction) -> EventRecorder {
+        EventRecorder {
+            connection_id,
+            direction,
+            event_bus: self.data.clone(),
+            redaction_policy: self.redaction_policy.clone(),
+            body_compression: self.body_compression,
+            last_event: Instant::now(),
+            buffered: None,
+        }
+    }
 }
 
-fn record_body(
-    body: &mut SdkBody,
-    event_id: ConnectionId,
+/// Records the events of one direction of a connection
+struct EventRecorder {
+    connection_id: ConnectionId,
     direction: Direction,
     event_bus: Arc<Mutex<Vec<Event>>>,
-) -> JoinHandle<()> {
+    redaction_policy: Arc<RedactionPolicy>,
+    body_compression: BodyCompression,
+    last_event: Instant,
+    /// Body data waiting to be redacted
+    buffered: Option<BytesMut>,
+}
+
+impl EventRecorder {
+    fn record(&mut self, action: Action) {
+        let now = Instant::now();
+        self.event_bus.lock().unwrap().push(Event::new(
+            self.connection_id,
+            action,
+            now - self.last_event,
+        ));
+        self.last_event = now;
+    }
+
+    fn record_data(&mut self, data: Bytes) {
+        match &mut self.buffered {
+            Some(buffered) => buffered.extend_from_slice(&data),
+            None => self.record(Action::Data {
+                data: BodyData::compressed(data, self.body_compression),
+                direction: self.direction,
+            }),
+        }
+    }
+
+    /// Record the data buffered so far, once redacted
+    fn flush_data(&mut self) {
+        if let Some(data) = self.buffered.take().filter(|data| !data.is_empty()) {
+            let data = self.redaction_policy.redact_body(data.freeze());
+            self.record(Action::Data {
+                data: BodyData::compressed(data, self.body_compression),
+                direction: self.direction,
+            });
+        }
+    }
+
+    fn record_eof(&mut self, error: Option<Error>) {
+        self.flush_data();
+        self.record(Action::Eof {
+            ok: error.is_none(),
+            direction: self.direction,
+            error,
+        });
+    }
+}
+
+fn record_body(body: &mut SdkBody, mut recorder: EventRecorder) -> JoinHandle<()> {
     let (sender, output_body) = hyper::Body::channel();
     let real_body = std::mem::replace(body, SdkBody::from(output_body));
+    // Bodies can only be redacted once complete: when the policy redacts bodies, the data is
+    // recorded as a single segment when the body ends.
+    if recorder.redaction_policy.redacts_bodies() {
+        recorder.buffered = Some(BytesMut::new());
+    }
     tokio::spawn(async move {
         let mut real_body = real_body;
         let mut sender = sender;
@@ -86,45 +180,44 @@ fn record_body(
             let data = real_body.data().await;
             match data {
                 Some(Ok(data)) => {
-                    event_bus.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Data {
-                            data: BodyData::from(data.clone()),
-                            direction,
-                        },
-                    });
+                    recorder.record_data(data.clone());
                     // This happens if the real connection is closed during recording.
                     // Need to think more carefully if this is the correct thing to log in this
                     // case.
                     if sender.send_data(data).await.is_err() {
-                        event_bus.lock().unwrap().push(Event {
-                            connection_id: event_id,
-                            action: Action::Eof {
-                                direction: direction.opposite(),
-                                ok: false,
-                            },
+                        recorder.record(Action::Eof {
+                            direction: recorder.direction.opposite(),
+                            ok: false,
+                            error: None,
                         })
                     };
                 }
                 None => {
-                    event_bus.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Eof {
-                            ok: true,
-                            direction,
-                        },
-                    });
+                    recorder.flush_data();
+                    match real_body.trailers().await {
+                        Ok(Some(trailers)) => {
+                            let mut recorded = dvr::Headers::from(&trailers);
+                            recorder.redaction_policy.redact_headers(&mut recorded);
+                            recorder.record(Action::Trailers {
+                                trailers: recorded,
+                                direction: recorder.direction,
+                            });
+                            // the receiver may have stopped reading after the data
+                            let _ = sender.send_trailers(trailers).await;
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            recorder.record_eof(Some(Error::from_error(&*err)));
+                            sender.abort();
+                            break;
+                        }
+                    }
+                    recorder.record_eof(None);
                     drop(sender);
                     break;
                 }
-                Some(Err(_err)) => {
-                    event_bus.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Eof {
-                            ok: false,
-                            direction,
-                        },
-                    });
+                Some(Err(err)) => {
+                    recorder.record_eof(Some(Error::from_error(&*err)));
                     sender.abort();
                     break;
                 }
@@ -133,6 +226,15 @@ fn record_body(
     })
 }
 
+/// Record a connection failure, classifying it when it is a [`ConnectorError`](ConnectorError)
+fn record_error<E: Display + 'static>(err: &E) -> Error {
+    match (err as &dyn Any).downcast_ref::<ConnectorError>() {
+        Some(err) if err.is_timeout() => Error::new(ErrorKind::TimedOut, err.to_string()),
+        Some(err) => Error::from_error(err),
+        None => Error::new(ErrorKind::Other, err.to_string()),
+    }
+}
+
 impl<S, ResponseBody> tower::Service<http::Request<SdkBody>> for RecordingConnection<S>
 where
     S: Service<http::Request<SdkBody>, Response = http::Response<ResponseBody>>
@@ -162,23 +264,20 @@ where
         // the channel should be closed.
 
         // Phase 1: the initial http request
-        self.data.lock().unwrap().push(Event {
-            connection_id: event_id,
-            action: Action::Request {
-                request: dvr::Request::from(&req),
-            },
-        });
+        let mut request = dvr::Request::from(&req);
+        self.redaction_policy.redact_request(&mut request);
+        self.data.lock().unwrap().push(Event::new(
+            event_id,
+            Action::Request { request },
+            Duration::ZERO,
+        ));
 
         // Phase 2: Swap out the real request body for one that will log all traffic that passes
         // through it
         // This will also handle phase three when the request body runs out of data.
-        record_body(
-            req.body_mut(),
-            event_id,
-            Direction::Request,
-            self.data.clone(),
-        );
-        let events = self.data.clone();
+        record_body(req.body_mut(), self.recorder(event_id, Direction::Request));
+        // the delay of the response event is the latency of the inner connection
+        let mut recorder = self.recorder(event_id, Direction::Response);
         // create a channel we'll use to stream the data while reading it
         let resp_fut = self.inner.call(req);
         let fut = async move {
@@ -189,23 +288,19 @@ where
                     let mut resp = resp.map(|body| body.into());
 
                     // push the initial response event
-                    events.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Response {
-                            response: Ok(dvr::Response::from(&resp)),
-                        },
+                    let mut response = dvr::Response::from(&resp);
+                    recorder.redaction_policy.redact_response(&mut response);
+                    recorder.record(Action::Response {
+                        response: Ok(response),
                     });
 
                     // instrument the body and record traffic
-                    record_body(resp.body_mut(), event_id, Direction::Response, events);
+                    record_body(resp.body_mut(), recorder);
                     Ok(resp)
                 }
                 Err(e) => {
-                    events.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Response {
-                            response: Err(Error(format!("{}", &e))),
-                        },
+                    recorder.record(Action::Response {
+                        response: Err(record_error(&e)),
                     });
                     Err(e)
                 }
@@ -0,0 +1,227 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::dvr;
+
+/// Value recorded in place of redacted data
+pub const REDACTED: &str = "**REDACTED**";
+
+/// Secrets to remove from traffic before it is recorded
+///
+/// A [`RecordingConnection`](super::RecordingConnection) applies its policy to every request and
+/// response it records. The traffic sent over the inner connection is never modified.
+///
+/// ```rust
+/// use aws_smithy_client::dvr::{RecordingConnection, RedactionPolicy};
+/// # fn wrap<S>(connection: S) -> RecordingConnection<S> {
+/// RecordingConnection::new(connection).with_redaction_policy(
+///     RedactionPolicy::credentials()
+///         .header("x-api-key")
+///         .json_body_path("Session.Password"),
+/// )
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RedactionPolicy {
+    headers: Vec<String>,
+    query_params: Vec<String>,
+    json_body_paths: Vec<Vec<String>>,
+}
+
+impl RedactionPolicy {
+    /// Create a policy that doesn't redact anything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a policy that redacts the credentials sent to and returned by AWS services
+    ///
+    /// This redacts:
+    /// - the `authorization`, `x-amz-security-token`, `cookie` and `set-cookie` headers
+    /// - the `X-Amz-Signature`, `X-Amz-Credential` and `X-Amz-Security-Token` query params of
+    ///   presigned requests
+    /// - the `SecretAccessKey`, `SessionToken` and `Token` fields of JSON bodies, including when
+    ///   nested under `Credentials`
+    pub fn credentials() -> Self {
+        Self::new()
+            .header("authorization")
+            .header("x-amz-security-token")
+            .header("cookie")
+            .header("set-cookie")
+            .query_param("X-Amz-Signature")
+            .query_param("X-Amz-Credential")
+            .query_param("X-Amz-Security-Token")
+            .json_body_path("SecretAccessKey")
+            .json_body_path("SessionToken")
+            .json_body_path("Token")
+            .json_body_path("Credentials.SecretAccessKey")
+            .json_body_path("Credentials.SessionToken")
+    }
+
+    /// Redact the values of a request or response header
+    ///
+    /// Header names are matched case-insensitively.
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Redact the values of a query param of request URIs
+    pub fn query_param(mut self, name: impl Into<String>) -> Self {
+        self.query_params.push(name.into());
+        self
+    }
+
+    /// Redact a field of JSON request and response bodies
+    ///
+    /// `path` is a dot-separated list of object keys, e.g. `Credentials.SecretAccessKey`. When the
+    /// path goes through an array, the field is redacted in every element of the array. Bodies
+    /// that aren't valid JSON are recorded unchanged.
+    pub fn json_body_path(mut self, path: impl AsRef<str>) -> Self {
+        self.json_body_paths
+            .push(path.as_ref().split('.').map(str::to_string).collect());
+        self
+    }
+
+    pub(super) fn redact_request(&self, request: &mut dvr::Request) {
+        self.redact_headers(&mut request.headers);
+        if !self.query_params.is_empty() {
+            request.uri = redact_query(&request.uri, &self.query_params);
+        }
+    }
+
+    pub(super) fn redact_response(&self, response: &mut dvr::Response) {
+        self.redact_headers(&mut response.headers);
+    }
+
+    /// Whether bodies must be buffered to be redacted
+    pub(super) fn redacts_bodies(&self) -> bool {
+        !self.json_body_paths.is_empty()
+    }
+
+    pub(super) fn redact_body(&self, body: Bytes) -> Bytes {
+        if !self.redacts_bodies() {
+            return body;
+        }
+        let mut json: Value = match serde_json::from_slice(&body) {
+            Ok(json) => json,
+            Err(_) => return body,
+        };
+        let mut redacted = false;
+        for path in &self.json_body_paths {
+            redacted |= redact_json_path(&mut json, path);
+        }
+        if redacted {
+            serde_json::to_vec(&json)
+                .expect("a JSON value can always be serialized")
+                .into()
+        } else {
+            body
+        }
+    }
+
+    pub(super) fn redact_headers(&self, headers: &mut dvr::Headers) {
+        for (name, values) in headers.0.iter_mut() {
+            if self
+                .headers
+                .iter()
+                .any(|redacted| name.eq_ignore_ascii_case(redacted))
+            {
+                for value in values.iter_mut() {
+                    *value = dvr::HeaderValue::Text(REDACTED.to_string());
+                }
+            }
+        }
+    }
+}
+
+fn redact_query(uri: &str, params: &[String]) -> String {
+    let (base, query) = match uri.split_once('?') {
+        Some(parts) => parts,
+        None => return uri.to_string(),
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if params.iter().any(|param| param == name) => {
+                format!("{}={}", name, REDACTED)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", base, query)
+}
+
+/// Redact the field at `path`, returning whether a field was redacted
+fn redact_json_path(value: &mut Value, path: &[String]) -> bool {
+    match value {
+        Value::Array(items) => items.iter_mut().fold(false, |redacted, item| {
+            redact_json_path(item, path) | redacted
+        }),
+        Value::Object(fields) => {
+            let (key, rest) = match path.split_first() {
+                Some(parts) => parts,
+                None => return false,
+            };
+            match fields.get_mut(key) {
+                Some(field) if rest.is_empty() => {
+                    *field = Value::String(REDACTED.to_string());
+                    true
+                }
+                Some(field) => redact_json_path(field, rest),
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{redact_query, RedactionPolicy};
+
+    #[test]
+    fn redact_query_params() {
+        let params = vec!["X-Amz-Signature".to_string()];
+        assert_eq!(
+            "https://s3.amazonaws.com/key?X-Amz-Date=1&X-Amz-Signature=**REDACTED**",
+            redact_query(
+                "https://s3.amazonaws.com/key?X-Amz-Date=1&X-Amz-Signature=abcd",
+                &params
+            )
+        );
+        assert_eq!(
+            "https://s3.amazonaws.com/key",
+            redact_query("https://s3.amazonaws.com/key", &params)
+        );
+    }
+
+    #[test]
+    fn redact_json_bodies() {
+        let policy = RedactionPolicy::new()
+            .json_body_path("Credentials.SecretAccessKey")
+            .json_body_path("Items.Password");
+        assert_eq!(
+            Bytes::from_static(
+                br#"{"Credentials":{"AccessKeyId":"AKID","SecretAccessKey":"**REDACTED**"},"Items":[{"Password":"**REDACTED**"},{"Name":"a"}]}"#
+            ),
+            policy.redact_body(Bytes::from_static(
+                br#"{"Credentials":{"AccessKeyId":"AKID","SecretAccessKey":"secret"},"Items":[{"Password":"hunter2"},{"Name":"a"}]}"#
+            ))
+        );
+
+        // bodies without redacted fields are recorded verbatim
+        let body = Bytes::from_static(b"{ \"AccessKeyId\": \"AKID\" }");
+        assert_eq!(body, policy.redact_body(body.clone()));
+        let body = Bytes::from_static(b"<Password>hunter2</Password>");
+        assert_eq!(body, policy.redact_body(body.clone()));
+    }
+}
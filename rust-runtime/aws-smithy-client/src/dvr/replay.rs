@@ -3,18 +3,26 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
-use crate::dvr::{Action, ConnectionId, Direction, Event};
+use crate::dvr::{
+    self, Action, ConnectionId, Direction, ErrorKind, Event, RedactionPolicy, REDACTED,
+};
 use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::result::ConnectorError;
 use bytes::{Bytes, BytesMut};
-use http::{Request, Version};
+use http::{HeaderMap, Request, Uri, Version};
+use http_body::combinators::BoxBody;
 use http_body::Body;
 use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use tokio::task::JoinHandle;
+use tokio::time::Sleep;
+
+type BoxError = Box<dyn Error + Send + Sync>;
 
 /// Wrapper type to enable optionally waiting for a future to complete
 #[derive(Debug)]
@@ -41,13 +49,103 @@ impl<T> Waitable<T> {
     }
 }
 
+/// How a [`ReplayingConnection`](ReplayingConnection) picks the recorded connection that replies
+/// to a request
+///
+/// Except for `InOrder`, every strategy replies with the first recorded connection that matches
+/// the request and hasn't been replayed yet, so requests may be sent in any order. Matching by
+/// body waits for the request body to be fully sent before replying. Bodies are compared once the
+/// [`RedactionPolicy`](super::RedactionPolicy) of the connection has been applied to the request,
+/// so that fields redacted from the recording match any value. Likewise, a query param redacted
+/// from the recording matches any value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RequestMatching {
+    /// Reply to the n-th request with the n-th recorded connection, regardless of the request
+    InOrder,
+    /// Match requests with the same method and URI
+    MethodAndUri,
+    /// Match requests with the same method, URI and body
+    MethodUriAndBody,
+    /// Match requests with the same method, URI and body, regardless of the order of the query
+    /// params
+    NormalizedQuery,
+}
+
+impl RequestMatching {
+    fn matches(
+        self,
+        recorded: &Request<Bytes>,
+        actual: &Request<Bytes>,
+        actual_body: &Bytes,
+    ) -> bool {
+        if recorded.method() != actual.method() {
+            return false;
+        }
+        match self {
+            RequestMatching::InOrder => true,
+            RequestMatching::MethodAndUri => uris_match(recorded.uri(), actual.uri(), false),
+            RequestMatching::MethodUriAndBody => {
+                uris_match(recorded.uri(), actual.uri(), false) && recorded.body() == actual_body
+            }
+            RequestMatching::NormalizedQuery => {
+                uris_match(recorded.uri(), actual.uri(), true) && recorded.body() == actual_body
+            }
+        }
+    }
+}
+
+/// Compare two URIs, treating redacted query params of the `recorded` URI as wildcards
+///
+/// If `normalize_query` is set, the query params may be in any order.
+fn uris_match(recorded: &Uri, actual: &Uri, normalize_query: bool) -> bool {
+    recorded.scheme() == actual.scheme()
+        && recorded.authority() == actual.authority()
+        && recorded.path() == actual.path()
+        && query_params_match(recorded.query(), actual.query(), normalize_query)
+}
+
+fn query_params_match(recorded: Option<&str>, actual: Option<&str>, normalize: bool) -> bool {
+    fn params(query: Option<&str>, normalize: bool) -> Vec<(&str, &str)> {
+        let mut params = query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .collect::<Vec<_>>();
+        if normalize {
+            // the sort is stable: repeated params keep their relative order
+            params.sort_by_key(|(name, _)| *name);
+        }
+        params
+    }
+    let (recorded, actual) = (params(recorded, normalize), params(actual, normalize));
+    recorded.len() == actual.len()
+        && recorded.iter().zip(actual.iter()).all(
+            |((recorded_name, recorded_value), (actual_name, actual_value))| {
+                recorded_name == actual_name
+                    && (recorded_value == actual_value || *recorded_value == REDACTED)
+            },
+        )
+}
+
 /// Replay traffic recorded by a [`RecordingConnection`](super::RecordingConnection)
+///
+/// By default, requests are replied to in the order the traffic was recorded. Use
+/// [`with_request_matching`](ReplayingConnection::with_request_matching) to reply to requests
+/// sent in a different order, e.g. by concurrent tasks.
+///
+/// Traffic is replayed without delays unless
+/// [`with_replayed_delays`](ReplayingConnection::with_replayed_delays) is enabled.
 #[derive(Clone, Debug)]
 pub struct ReplayingConnection {
     live_events: Arc<Mutex<HashMap<ConnectionId, VecDeque<Event>>>>,
     verifiable_events: Arc<HashMap<ConnectionId, Request<Bytes>>>,
     num_events: Arc<AtomicUsize>,
     recorded_requests: Arc<Mutex<HashMap<ConnectionId, Waitable<http::Request<Bytes>>>>>,
+    request_matching: RequestMatching,
+    redaction_policy: Arc<RedactionPolicy>,
+    replay_delays: bool,
 }
 
 impl ReplayingConnection {
@@ -55,6 +153,49 @@ impl ReplayingConnection {
         ConnectionId(self.num_events.fetch_add(1, Ordering::Relaxed))
     }
 
+    /// Take the events of the first connection not replayed yet that matches `request`
+    fn take_matching(&self, request: &Request<Bytes>) -> Option<(ConnectionId, VecDeque<Event>)> {
+        // the recorded bodies were redacted by the recording connection
+        let body = self.redaction_policy.redact_body(request.body().clone());
+        let mut live_events = self.live_events.lock().unwrap();
+        let event_id = live_events
+            .keys()
+            .filter(|id| {
+                self.request_matching
+                    .matches(&self.verifiable_events[id], request, &body)
+            })
+            .min_by_key(|id| id.0)
+            .copied()?;
+        live_events
+            .remove(&event_id)
+            .map(|events| (event_id, events))
+    }
+
+    /// Choose how requests are matched with the recorded connections that reply to them
+    pub fn with_request_matching(mut self, request_matching: RequestMatching) -> Self {
+        self.request_matching = request_matching;
+        self
+    }
+
+    /// Match request bodies once redacted by `policy`
+    ///
+    /// This should be the policy of the [`RecordingConnection`](super::RecordingConnection) that
+    /// recorded the traffic.
+    pub fn with_redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = Arc::new(policy);
+        self
+    }
+
+    /// Wait for the recorded delays between events before replaying them
+    ///
+    /// The response is returned after the recorded latency of the connection, and body data
+    /// after the recorded delays between chunks. Delays are only recorded in the
+    /// [`V1`](super::Version::V1) format.
+    pub fn with_replayed_delays(mut self, replay_delays: bool) -> Self {
+        self.replay_delays = replay_delays;
+        self
+    }
+
     /// Validate actual requests against expected requests
     pub async fn validate(
         self,
@@ -99,11 +240,13 @@ impl ReplayingConnection {
     /// Return all the recorded requests for further analysis
     pub async fn take_requests(self) -> Vec<http::Request<Bytes>> {
         let mut recorded_requests = self.recorded_requests.lock().unwrap();
+        let mut conn_ids = recorded_requests.keys().copied().collect::<Vec<_>>();
+        conn_ids.sort_by_key(|id| id.0);
         let mut out = Vec::with_capacity(recorded_requests.len());
-        for conn_id in 0..recorded_requests.len() {
+        for conn_id in conn_ids {
             out.push(
                 recorded_requests
-                    .remove(&ConnectionId(conn_id))
+                    .remove(&conn_id)
                     .expect("should exist")
                     .take()
                     .await,
@@ -149,58 +292,197 @@ impl ReplayingConnection {
             num_events: Arc::new(AtomicUsize::new(0)),
             recorded_requests: Default::default(),
             verifiable_events,
+            request_matching: RequestMatching::InOrder,
+            redaction_policy: Default::default(),
+            replay_delays: false,
         }
     }
 }
 
-async fn replay_body(events: VecDeque<Event>, mut sender: hyper::body::Sender) {
-    for event in events {
+/// Response body replaying the response events of a connection
+///
+/// Data is returned after its recorded delay, if any, and the body fails the way it failed when it
+/// was recorded.
+struct ReplayBody {
+    events: VecDeque<Event>,
+    delay: Option<Pin<Box<Sleep>>>,
+    trailers: Option<HeaderMap>,
+}
+
+impl Body for ReplayBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(delay) = &mut this.delay {
+                match delay.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.delay = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            let mut event = match this.events.pop_front() {
+                Some(event) => event,
+                None => return Poll::Ready(None),
+            };
+            let direction = match &event.action {
+                Action::Data { direction, .. }
+                | Action::Trailers { direction, .. }
+                | Action::Eof { direction, .. } => *direction,
+                Action::Request { .. } | Action::Response { .. } => {
+                    panic!("invalid event in a body: {:?}", event)
+                }
+            };
+            // request events are only recorded for validation
+            if direction == Direction::Request {
+                continue;
+            }
+            if let Some(delay) = event.take_delay() {
+                this.delay = Some(Box::pin(tokio::time::sleep(delay)));
+                this.events.push_front(event);
+                continue;
+            }
+            match event.action {
+                Action::Data { data, .. } => {
+                    return Poll::Ready(Some(Ok(Bytes::from(data.into_bytes()))))
+                }
+                Action::Trailers { trailers, .. } => this.trailers = Some((&trailers).into()),
+                Action::Eof { ok: true, .. } => {
+                    this.events.clear();
+                    return Poll::Ready(None);
+                }
+                Action::Eof {
+                    ok: false, error, ..
+                } => {
+                    this.events.clear();
+                    let error =
+                        error.unwrap_or_else(|| dvr::Error::new(ErrorKind::Other, "body failed"));
+                    return Poll::Ready(Some(Err(error.into_io_error().into())));
+                }
+                Action::Request { .. } | Action::Response { .. } => unreachable!(),
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.get_mut().trailers.take()))
+    }
+}
+
+/// Convert a recorded connection failure into the error returned by the connection
+fn connector_error(error: dvr::Error) -> ConnectorError {
+    match error.kind() {
+        ErrorKind::TimedOut => ConnectorError::timeout(error.into_io_error().into()),
+        ErrorKind::Other => ConnectorError::other(error.message.into(), None),
+        _ => ConnectorError::io(error.into_io_error().into()),
+    }
+}
+
+fn convert_version(version: &str) -> Version {
+    match version {
+        "HTTP/1.1" => Version::HTTP_11,
+        "HTTP/2.0" => Version::HTTP_2,
+        _ => panic!("unsupported: {}", version),
+    }
+}
+
+async fn read_body(mut req: Request<SdkBody>) -> Request<Bytes> {
+    let mut data_read = vec![];
+    while let Some(data) = req.body_mut().data().await {
+        data_read.extend_from_slice(data.expect("in memory request should not fail").as_ref())
+    }
+    req.map(|_| Bytes::from(data_read))
+}
+
+async fn replay(
+    event_id: ConnectionId,
+    mut events: VecDeque<Event>,
+    mut recorded_request: Waitable<Request<Bytes>>,
+    recording: Arc<Mutex<HashMap<ConnectionId, Waitable<Request<Bytes>>>>>,
+    replay_delays: bool,
+) -> Result<http::Response<SdkBody>, ConnectorError> {
+    if !replay_delays {
+        for event in events.iter_mut() {
+            event.take_delay();
+        }
+    }
+    let _initial_request = events.pop_front().unwrap();
+    let resp = loop {
+        let mut event = events
+            .pop_front()
+            .expect("no events, needed a response event");
         match event.action {
-            Action::Request { .. } => panic!(),
-            Action::Response { .. } => panic!(),
-            Action::Data {
-                data,
-                direction: Direction::Response,
+            // to ensure deterministic behavior if the request EOF happens first in the log,
+            // wait for the request body to be done before returning a response.
+            Action::Eof {
+                direction: Direction::Request,
+                ..
             } => {
-                sender
-                    .send_data(Bytes::from(data.into_bytes()))
-                    .await
-                    .expect("this is in memory traffic that should not fail to send");
+                recorded_request.wait().await;
+            }
+            Action::Request { .. } => panic!("invalid"),
+            Action::Response { .. } => {
+                // the delay of the response is the latency of the recorded connection
+                if let Some(delay) = event.take_delay() {
+                    tokio::time::sleep(delay).await;
+                }
+                match event.action {
+                    Action::Response {
+                        response: Err(error),
+                    } => break Err(connector_error(error)),
+                    Action::Response {
+                        response: Ok(response),
+                    } => {
+                        let mut resp = http::Response::builder()
+                            .status(response.status)
+                            .version(convert_version(&response.version))
+                            .body(SdkBody::from_dyn(BoxBody::new(ReplayBody {
+                                events,
+                                delay: None,
+                                trailers: None,
+                            })))
+                            .expect("valid builder");
+                        *resp.headers_mut() = (&response.headers).into();
+                        break Ok(resp);
+                    }
+                    _ => unreachable!(),
+                }
             }
+
             Action::Data {
-                data: _data,
                 direction: Direction::Request,
-            } => {}
-            Action::Eof {
+                data: _data,
+            } => {
+                tracing::info!("get request data");
+            }
+            Action::Trailers {
                 direction: Direction::Request,
                 ..
             } => {}
             Action::Eof {
                 direction: Direction::Response,
-                ok: true,
                 ..
-            } => {
-                drop(sender);
-                break;
-            }
-            Action::Eof {
+            } => panic!("got eof before response"),
+
+            Action::Data {
                 direction: Direction::Response,
-                ok: false,
                 ..
-            } => {
-                sender.abort();
-                break;
             }
+            | Action::Trailers {
+                direction: Direction::Response,
+                ..
+            } => panic!("got response data before response"),
         }
-    }
-}
-
-fn convert_version(version: &str) -> Version {
-    match version {
-        "HTTP/1.1" => Version::HTTP_11,
-        "HTTP/2.0" => Version::HTTP_2,
-        _ => panic!("unsupported: {}", version),
-    }
+    };
+    recording.lock().unwrap().insert(event_id, recorded_request);
+    resp
 }
 
 impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
@@ -216,9 +498,31 @@ impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, mut req: Request<SdkBody>) -> Self::Future {
+    fn call(&mut self, req: Request<SdkBody>) -> Self::Future {
+        if self.request_matching != RequestMatching::InOrder {
+            // the request must be fully read before it can be matched
+            let connection = self.clone();
+            return Box::pin(async move {
+                let req = read_body(req).await;
+                let (event_id, events) = connection.take_matching(&req).ok_or_else(|| {
+                    ConnectorError::other(
+                        format!("no recorded connection matches request: {:?}", req).into(),
+                        None,
+                    )
+                })?;
+                replay(
+                    event_id,
+                    events,
+                    Waitable::Value(req),
+                    connection.recorded_requests,
+                    connection.replay_delays,
+                )
+                .await
+            });
+        }
+
         let event_id = self.next_id();
-        let mut events = match self.live_events.lock().unwrap().remove(&event_id) {
+        let events = match self.live_events.lock().unwrap().remove(&event_id) {
             Some(traffic) => traffic,
             None => {
                 return Box::pin(std::future::ready(Err(ConnectorError::other(
@@ -227,76 +531,13 @@ impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
                 ))))
             }
         };
-
-        let _initial_request = events.pop_front().unwrap();
-        let (sender, response_body) = hyper::Body::channel();
-        let body = SdkBody::from(response_body);
-        let recording = self.recorded_requests.clone();
-        let recorded_request = tokio::spawn(async move {
-            let mut data_read = vec![];
-            while let Some(data) = req.body_mut().data().await {
-                data_read
-                    .extend_from_slice(data.expect("in memory request should not fail").as_ref())
-            }
-            req.map(|_| Bytes::from(data_read))
-        });
-        let mut recorded_request = Waitable::Loading(recorded_request);
-        let fut = async move {
-            let resp = loop {
-                let event = events
-                    .pop_front()
-                    .expect("no events, needed a response event");
-                match event.action {
-                    // to ensure deterministic behavior if the request EOF happens first in the log,
-                    // wait for the request body to be done before returning a response.
-                    Action::Eof {
-                        direction: Direction::Request,
-                        ..
-                    } => {
-                        recorded_request.wait().await;
-                    }
-                    Action::Request { .. } => panic!("invalid"),
-                    Action::Response {
-                        response: Err(error),
-                    } => break Err(ConnectorError::other(error.0.into(), None)),
-                    Action::Response {
-                        response: Ok(response),
-                    } => {
-                        let mut builder = http::Response::builder()
-                            .status(response.status)
-                            .version(convert_version(&response.version));
-                        for (name, values) in response.headers {
-                            for value in values {
-                                builder = builder.header(&name, &value);
-                            }
-                        }
-                        tokio::spawn(async move {
-                            replay_body(events, sender).await;
-                            // insert the finalized body into
-                        });
-                        break Ok(builder.body(body).expect("valid builder"));
-                    }
-
-                    Action::Data {
-                        direction: Direction::Request,
-                        data: _data,
-                    } => {
-                        tracing::info!("get request data");
-                    }
-                    Action::Eof {
-                        direction: Direction::Response,
-                        ..
-                    } => panic!("got eof before response"),
-
-                    Action::Data {
-                        data: _,
-                        direction: Direction::Response,
-                    } => panic!("got response data before response"),
-                }
-            };
-            recording.lock().unwrap().insert(event_id, recorded_request);
-            resp
-        };
-        Box::pin(fut)
+        let recorded_request = Waitable::Loading(tokio::spawn(read_body(req)));
+        Box::pin(replay(
+            event_id,
+            events,
+            recorded_request,
+            self.recorded_requests.clone(),
+            self.replay_delays,
+        ))
     }
 }
@@ -58,6 +58,9 @@ where
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 }
@@ -98,6 +101,9 @@ where
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            circuit_breaker: self.circuit_breaker,
+            interceptors: self.interceptors,
+            metrics_sink: self.metrics_sink,
         }
     }
 
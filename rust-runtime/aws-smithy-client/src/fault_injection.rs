@@ -0,0 +1,547 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Connection wrapper injecting faults into requests, to test retry and timeout behavior
+//!
+//! A [`FaultInjectingConnection`] wraps a connection and injects [`Fault`]s into the requests it
+//! sends. Each fault comes with a [`Trigger`] choosing the requests it is injected into: faults
+//! can be injected at random, on a schedule, or into the requests matching a predicate. When
+//! several triggers fire for a request, the fault added first is injected.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use aws_smithy_client::fault_injection::{Fault, FaultInjectingConnection, Trigger};
+//! # fn wrap<C>(connection: C) -> FaultInjectingConnection<C> {
+//! // throttle the first two requests, then hang one request in ten
+//! FaultInjectingConnection::new(connection)
+//!     .with_fault(
+//!         Fault::Response { status: 429, retry_after: Some(Duration::from_secs(1)) },
+//!         Trigger::FirstRequests(2),
+//!     )
+//!     .with_fault(Fault::Hang, Trigger::Probability(0.1))
+//! # }
+//! ```
+//!
+//! Delays are implemented with Tokio timers, so tests can use `tokio::time::pause` to run
+//! without waiting for them.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::HeaderMap;
+use http_body::combinators::BoxBody;
+use http_body::{Body, SizeHint};
+use tokio::time::Sleep;
+use tower::BoxError;
+
+use aws_smithy_async::future::never::Never;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+
+use crate::erase::boxclone::BoxFuture;
+
+/// A fault injected into a request
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// Fail to connect, with an IO error of the given kind
+    ///
+    /// The request isn't sent to the inner connection.
+    ConnectError(io::ErrorKind),
+
+    /// Return the response headers at least `delay` after the request was sent
+    DelayHeaders(Duration),
+
+    /// Return the response body in chunks of at most `chunk_size` bytes, waiting `delay`
+    /// before each chunk
+    SlowBody {
+        /// Maximum size of the returned chunks
+        chunk_size: usize,
+        /// Delay before each chunk
+        delay: Duration,
+    },
+
+    /// Return the first `len` bytes of the response body, then fail with a connection reset
+    ///
+    /// Bodies of at most `len` bytes are returned unchanged.
+    TruncatedBody {
+        /// Number of bytes returned before the body fails
+        len: usize,
+    },
+
+    /// Reply with an empty response with the given status, e.g. `500`, `503` or `429`
+    ///
+    /// The request isn't sent to the inner connection. When `retry_after` is set, the response
+    /// has a `Retry-After` header with its number of seconds.
+    Response {
+        /// Status of the response
+        status: u16,
+        /// Value of the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+
+    /// Never reply
+    ///
+    /// The request isn't sent to the inner connection.
+    Hang,
+}
+
+/// Predicate used by [`Trigger::Matching`]
+pub type RequestPredicate = Arc<dyn Fn(&http::Request<SdkBody>) -> bool + Send + Sync>;
+
+/// Chooses the requests a [`Fault`] is injected into
+///
+/// Requests are numbered from `0`, in the order they are sent through the connection and all of
+/// its clones.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Trigger {
+    /// Inject the fault into every request
+    Always,
+    /// Inject the fault into each request with the given probability, between `0.0` and `1.0`
+    Probability(f64),
+    /// Inject the fault into the first `n` requests
+    FirstRequests(usize),
+    /// Inject the fault into the requests with the given numbers
+    Requests(Vec<usize>),
+    /// Inject the fault into the requests matching a predicate
+    Matching(RequestPredicate),
+}
+
+impl Trigger {
+    /// Inject the fault into the requests for which `predicate` returns true
+    pub fn matching(
+        predicate: impl Fn(&http::Request<SdkBody>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Trigger::Matching(Arc::new(predicate))
+    }
+
+    fn fires(
+        &self,
+        request_number: usize,
+        request: &http::Request<SdkBody>,
+        rng: &Mutex<fastrand::Rng>,
+    ) -> bool {
+        match self {
+            Trigger::Always => true,
+            Trigger::Probability(probability) => rng.lock().unwrap().f64() < *probability,
+            Trigger::FirstRequests(n) => request_number < *n,
+            Trigger::Requests(numbers) => numbers.contains(&request_number),
+            Trigger::Matching(predicate) => predicate(request),
+        }
+    }
+}
+
+impl fmt::Debug for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Always => write!(f, "Always"),
+            Trigger::Probability(probability) => {
+                f.debug_tuple("Probability").field(probability).finish()
+            }
+            Trigger::FirstRequests(n) => f.debug_tuple("FirstRequests").field(n).finish(),
+            Trigger::Requests(numbers) => f.debug_tuple("Requests").field(numbers).finish(),
+            Trigger::Matching(_) => write!(f, "Matching(<predicate>)"),
+        }
+    }
+}
+
+/// Connection wrapper injecting [`Fault`]s into requests
+///
+/// Clones share their request count and the record of the injected faults.
+#[derive(Clone, Debug)]
+pub struct FaultInjectingConnection<C> {
+    inner: C,
+    faults: Arc<Vec<(Fault, Trigger)>>,
+    requests: Arc<AtomicUsize>,
+    injected: Arc<Mutex<Vec<(usize, Fault)>>>,
+    rng: Arc<Mutex<fastrand::Rng>>,
+}
+
+impl<C> FaultInjectingConnection<C> {
+    /// Wrap `inner`, without injecting any fault yet
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            faults: Default::default(),
+            requests: Default::default(),
+            injected: Default::default(),
+            rng: Arc::new(Mutex::new(fastrand::Rng::new())),
+        }
+    }
+
+    /// Inject `fault` into the requests chosen by `trigger`
+    pub fn with_fault(mut self, fault: Fault, trigger: Trigger) -> Self {
+        Arc::make_mut(&mut self.faults).push((fault, trigger));
+        self
+    }
+
+    /// Seed the random number generator of [`Trigger::Probability`], to inject the same faults
+    /// on every run
+    pub fn with_seed(self, seed: u64) -> Self {
+        *self.rng.lock().unwrap() = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// The number of requests sent through this connection
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// The faults injected so far, with the number of the request they were injected into
+    pub fn injected_faults(&self) -> Vec<(usize, Fault)> {
+        self.injected.lock().unwrap().clone()
+    }
+
+    fn fault_for(&self, request: &http::Request<SdkBody>) -> Option<Fault> {
+        let request_number = self.requests.fetch_add(1, Ordering::SeqCst);
+        let fault = self
+            .faults
+            .iter()
+            .find(|(_, trigger)| trigger.fires(request_number, request, &self.rng))
+            .map(|(fault, _)| fault.clone())?;
+        tracing::debug!(request_number, fault = ?fault, "injecting fault");
+        self.injected
+            .lock()
+            .unwrap()
+            .push((request_number, fault.clone()));
+        Some(fault)
+    }
+}
+
+impl<C> tower::Service<http::Request<SdkBody>> for FaultInjectingConnection<C>
+where
+    C: tower::Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    C::Error: Into<ConnectorError>,
+    C::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(|err| err.into())
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let fault = match self.fault_for(&req) {
+            Some(fault) => fault,
+            None => {
+                let fut = self.inner.call(req);
+                return Box::pin(async move { fut.await.map_err(|err| err.into()) });
+            }
+        };
+        match fault {
+            Fault::ConnectError(kind) => {
+                let err = io::Error::new(kind, "injected connect error");
+                Box::pin(std::future::ready(Err(ConnectorError::io(err.into()))))
+            }
+            Fault::Response {
+                status,
+                retry_after,
+            } => {
+                let mut response = http::Response::builder().status(status);
+                if let Some(retry_after) = retry_after {
+                    response = response.header("retry-after", retry_after.as_secs());
+                }
+                let response = response
+                    .body(SdkBody::empty())
+                    .map_err(|err| ConnectorError::other(err.into(), None));
+                Box::pin(std::future::ready(response))
+            }
+            Fault::Hang => Box::pin(async {
+                Never::new().await;
+                unreachable!()
+            }),
+            Fault::DelayHeaders(delay) => {
+                let sleep = tokio::time::sleep(delay);
+                let fut = self.inner.call(req);
+                Box::pin(async move {
+                    let response = fut.await.map_err(|err| err.into());
+                    sleep.await;
+                    response
+                })
+            }
+            Fault::SlowBody { chunk_size, delay } => {
+                self.call_with_body_fault(req, BodyFault::Slow { chunk_size, delay })
+            }
+            Fault::TruncatedBody { len } => {
+                self.call_with_body_fault(req, BodyFault::Truncated { remaining: len })
+            }
+        }
+    }
+}
+
+impl<C> FaultInjectingConnection<C>
+where
+    C: tower::Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    C::Error: Into<ConnectorError>,
+    C::Future: Send + 'static,
+{
+    fn call_with_body_fault(
+        &mut self,
+        req: http::Request<SdkBody>,
+        fault: BodyFault,
+    ) -> BoxFuture<http::Response<SdkBody>, ConnectorError> {
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let response = fut.await.map_err(|err| err.into())?;
+            Ok(response.map(|body| {
+                SdkBody::from_dyn(BoxBody::new(FaultyBody {
+                    inner: body,
+                    pending: Bytes::new(),
+                    delay: None,
+                    fault,
+                }))
+            }))
+        })
+    }
+}
+
+enum BodyFault {
+    Slow { chunk_size: usize, delay: Duration },
+    Truncated { remaining: usize },
+}
+
+/// Response body with an injected fault
+struct FaultyBody {
+    inner: SdkBody,
+    /// Data read from `inner` and not returned yet
+    pending: Bytes,
+    /// Delay before the next chunk of a slow body
+    delay: Option<Pin<Box<Sleep>>>,
+    fault: BodyFault,
+}
+
+impl Body for FaultyBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        while this.pending.is_empty() {
+            match Pin::new(&mut this.inner).poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => this.pending = data,
+                other => return other,
+            }
+        }
+        let len = match &mut this.fault {
+            BodyFault::Slow { chunk_size, delay } => {
+                let delay = this
+                    .delay
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(*delay)));
+                match delay.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.delay = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+                *chunk_size
+            }
+            BodyFault::Truncated { remaining: 0 } => {
+                let err =
+                    io::Error::new(io::ErrorKind::ConnectionReset, "injected connection reset");
+                return Poll::Ready(Some(Err(err.into())));
+            }
+            BodyFault::Truncated { remaining } => {
+                let len = std::cmp::min(*remaining, this.pending.len());
+                *remaining -= len;
+                len
+            }
+        };
+        let len = std::cmp::min(len, this.pending.len());
+        Poll::Ready(Some(Ok(this.pending.split_to(len))))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let inner = self.inner.size_hint();
+        let pending = self.pending.len() as u64;
+        let mut size_hint = SizeHint::new();
+        size_hint.set_lower(inner.lower() + pending);
+        if let Some(upper) = inner.upper() {
+            size_hint.set_upper(upper + pending);
+        }
+        size_hint
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Ready;
+    use std::io;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use http_body::Body;
+    use tower::util::ServiceFn;
+    use tower::Service;
+
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::result::ConnectorError;
+
+    use super::{Fault, FaultInjectingConnection, Trigger};
+
+    type ResponseFuture = Ready<Result<http::Response<SdkBody>, ConnectorError>>;
+    type HelloConnection =
+        FaultInjectingConnection<ServiceFn<fn(http::Request<SdkBody>) -> ResponseFuture>>;
+
+    /// A connection replying `hello world` to every request
+    fn connection() -> HelloConnection {
+        fn hello(_req: http::Request<SdkBody>) -> ResponseFuture {
+            std::future::ready(Ok(http::Response::new(SdkBody::from("hello world"))))
+        }
+        FaultInjectingConnection::new(tower::service_fn(hello as fn(_) -> _))
+    }
+
+    fn request(path: &str) -> http::Request<SdkBody> {
+        http::Request::get(format!("https://www.example.com{}", path))
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    async fn read_body(mut body: SdkBody) -> Vec<Result<Bytes, io::ErrorKind>> {
+        let mut data = vec![];
+        while let Some(chunk) = body.data().await {
+            let failed = chunk.is_err();
+            data.push(chunk.map_err(|err| err.downcast_ref::<io::Error>().unwrap().kind()));
+            if failed {
+                break;
+            }
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn faults_are_injected_on_schedule() {
+        let mut conn = connection()
+            .with_fault(
+                Fault::Response {
+                    status: 429,
+                    retry_after: Some(Duration::from_secs(2)),
+                },
+                Trigger::FirstRequests(1),
+            )
+            .with_fault(
+                Fault::ConnectError(io::ErrorKind::ConnectionRefused),
+                Trigger::Requests(vec![2]),
+            )
+            .with_fault(
+                Fault::Response {
+                    status: 503,
+                    retry_after: None,
+                },
+                Trigger::matching(|req| req.uri().path() == "/unavailable"),
+            );
+
+        let resp = conn.call(request("/")).await.unwrap();
+        assert_eq!(429, resp.status());
+        assert_eq!("2", resp.headers()["retry-after"]);
+        assert_eq!(200, conn.call(request("/")).await.unwrap().status());
+        let err = conn.call(request("/")).await.expect_err("connect error");
+        assert!(err.is_io(), "{:?}", err);
+        let resp = conn.call(request("/unavailable")).await.unwrap();
+        assert_eq!(503, resp.status());
+
+        assert_eq!(4, conn.requests());
+        let injected = conn.injected_faults();
+        assert_eq!(
+            vec![0, 2, 3],
+            injected.iter().map(|(n, _)| *n).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn probabilities_are_reproducible_with_a_seed() {
+        let injected = |seed| async move {
+            let mut conn = connection()
+                .with_fault(
+                    Fault::ConnectError(io::ErrorKind::ConnectionReset),
+                    Trigger::Probability(0.5),
+                )
+                .with_seed(seed);
+            for _ in 0..20 {
+                let _ = conn.call(request("/")).await;
+            }
+            conn.injected_faults()
+        };
+        let first = injected(1234).await;
+        assert!(!first.is_empty() && first.len() < 20, "{:?}", first);
+        assert_eq!(first, injected(1234).await);
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_fail_with_a_connection_reset() {
+        let mut conn = connection().with_fault(Fault::TruncatedBody { len: 5 }, Trigger::Always);
+        let resp = conn.call(request("/")).await.unwrap();
+        assert_eq!(
+            vec![
+                Ok(Bytes::from_static(b"hello")),
+                Err(io::ErrorKind::ConnectionReset)
+            ],
+            read_body(resp.into_body()).await
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_bodies_are_delayed() {
+        tokio::time::pause();
+        let mut conn = connection()
+            .with_fault(
+                Fault::DelayHeaders(Duration::from_secs(1)),
+                Trigger::FirstRequests(1),
+            )
+            .with_fault(
+                Fault::SlowBody {
+                    chunk_size: 6,
+                    delay: Duration::from_secs(1),
+                },
+                Trigger::Always,
+            );
+        let start = tokio::time::Instant::now();
+        conn.call(request("/")).await.unwrap();
+        aws_smithy_async::assert_elapsed!(start, Duration::from_secs(1));
+
+        let start = tokio::time::Instant::now();
+        let resp = conn.call(request("/")).await.unwrap();
+        assert_eq!(Some(11), resp.body().size_hint().exact());
+        assert!(!resp.body().is_end_stream());
+        assert_eq!(
+            vec![
+                Ok(Bytes::from_static(b"hello ")),
+                Ok(Bytes::from_static(b"world"))
+            ],
+            read_body(resp.into_body()).await
+        );
+        aws_smithy_async::assert_elapsed!(start, Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn hung_requests_never_complete() {
+        tokio::time::pause();
+        let mut conn = connection().with_fault(Fault::Hang, Trigger::Always);
+        let result = tokio::time::timeout(Duration::from_secs(60), conn.call(request("/"))).await;
+        assert!(result.is_err(), "the request should hang");
+    }
+}
@@ -38,9 +38,36 @@
 //! // once you have a connector, use it to construct a Smithy client:
 //! let client = Client::<DynConnector, MyMiddleware>::new(DynConnector::new(connector));
 //! ```
+//!
+//! ### Connect through a proxy
+//! Connections can be tunnelled through an HTTP proxy, configured explicitly or read from the
+//! `HTTPS_PROXY`, `http_proxy` and `NO_PROXY` environment variables:
+//! ```no_run
+//! use aws_smithy_client::hyper_ext::{self, ProxyConfig};
+//!
+//! let connector = hyper_ext::Adapter::builder().build_https(ProxyConfig::from_env());
+//! ```
+//!
+//! ### Tune the connection pool
+//! Idle connections are kept in a pool to be reused by later requests. The pool can be tuned, and
+//! the connections opened, reused and closed by the connector are counted in its [`ConnectionMetrics`]:
+//! ```no_run
+//! use std::time::Duration;
+//! use aws_smithy_client::hyper_ext::{self, ProxyConfig};
+//!
+//! let connector = hyper_ext::Adapter::builder()
+//!     .pool_idle_timeout(Some(Duration::from_secs(20)))
+//!     .pool_max_idle_per_host(8)
+//!     .tcp_keepalive(Some(Duration::from_secs(60)))
+//!     .build_https(ProxyConfig::new());
+//! let metrics = connector.connection_metrics().clone();
+//! // ... later
+//! println!("{} connections opened, {} reused", metrics.connections_opened(), metrics.connections_reused());
+//! ```
 
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Duration;
 
 use http::Uri;
 use hyper::client::connect::{Connected, Connection};
@@ -58,15 +85,33 @@ use crate::erase::DynConnector;
 use crate::never::stream::EmptyStream;
 use crate::Builder as ClientBuilder;
 
+use self::metrics::MetricsConnector;
+use self::proxy::ProxyError;
 use self::timeout_middleware::{ConnectTimeout, HttpReadTimeout, HttpTimeoutError};
 
+pub use self::metrics::ConnectionMetrics;
+pub use self::proxy::{InvalidProxy, Proxy, ProxyConfig, ProxyConnector};
+
+mod metrics;
+mod proxy;
+
 /// Adapter from a [`hyper::Client`](hyper::Client) to a connector usable by a Smithy [`Client`](crate::Client).
 ///
 /// This adapter also enables TCP `CONNECT` and HTTP `READ` timeouts via [`Adapter::builder`]. For examples
 /// see [the module documentation](crate::hyper_ext).
 #[derive(Clone, Debug)]
 #[non_exhaustive]
-pub struct Adapter<C>(HttpReadTimeout<hyper::Client<ConnectTimeout<C>, SdkBody>>);
+pub struct Adapter<C> {
+    client: HttpReadTimeout<hyper::Client<ConnectTimeout<MetricsConnector<C>>, SdkBody>>,
+    metrics: ConnectionMetrics,
+}
+
+impl<C> Adapter<C> {
+    /// The counters of the connections opened, reused and closed by this adapter
+    pub fn connection_metrics(&self) -> &ConnectionMetrics {
+        &self.metrics
+    }
+}
 
 impl<C> Service<http::Request<SdkBody>> for Adapter<C>
 where
@@ -88,12 +133,17 @@ where
         &mut self,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.0.poll_ready(cx).map_err(downcast_error)
+        self.client.poll_ready(cx).map_err(downcast_error)
     }
 
     fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
-        let fut = self.0.call(req);
-        Box::pin(async move { Ok(fut.await.map_err(downcast_error)?.map(SdkBody::from)) })
+        let fut = self.client.call(req);
+        let metrics = self.metrics.clone();
+        Box::pin(async move {
+            let response = fut.await.map_err(downcast_error)?;
+            metrics.record_response(&response);
+            Ok(response.map(SdkBody::from))
+        })
     }
 }
 
@@ -142,6 +192,8 @@ fn to_connector_error(err: hyper::Error) -> ConnectorError {
     // We sometimes receive this from S3: hyper::Error(IncompleteMessage)
     else if err.is_incomplete_message() {
         ConnectorError::other(err.into(), Some(ErrorKind::TransientError))
+    } else if find_source::<ProxyError>(&err).is_some() {
+        ConnectorError::other(err.into(), None)
     } else {
         tracing::warn!(err = ?err, "unrecognized error from Hyper. If this error should be retried, please file an issue.");
         ConnectorError::other(err.into(), None)
@@ -184,6 +236,9 @@ pub struct Builder {
     timeout_config: TimeoutConfig,
     sleep: Option<Arc<dyn AsyncSleep>>,
     client_builder: hyper::client::Builder,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    tcp_keepalive: Option<Duration>,
+    metrics: ConnectionMetrics,
 }
 
 impl Builder {
@@ -198,6 +253,7 @@ impl Builder {
     {
         // if we are using Hyper, Tokio must already be enabled so we can fallback to Tokio.
         let sleep = self.sleep.or_else(default_async_sleep);
+        let connector = MetricsConnector::new(connector, self.metrics.clone());
         let connector = match self.timeout_config.connect_timeout() {
             Some(duration) => ConnectTimeout::new(
                 connector,
@@ -219,7 +275,99 @@ impl Builder {
             ),
             None => HttpReadTimeout::no_timeout(base),
         };
-        Adapter(http_timeout)
+        Adapter {
+            client: http_timeout,
+            metrics: self.metrics,
+        }
+    }
+
+    /// Create a HyperAdapter that connects over HTTPS with Rustls, through the proxies selected by
+    /// `proxy`, if any
+    ///
+    /// Pass [`ProxyConfig::new`] to connect to every destination directly. To use a proxy with a
+    /// custom connector, wrap it in a [`ProxyConnector`] and pass it to [`Builder::build`].
+    #[cfg(feature = "rustls")]
+    pub fn build_https(self, proxy: ProxyConfig) -> Adapter<crate::conns::HttpsWithProxy> {
+        let http = self.proxy_connector(proxy);
+        self.build(crate::conns::https_over(http))
+    }
+
+    /// Create a HyperAdapter that connects over HTTPS with the native TLS library of your
+    /// platform, through the proxies selected by `proxy`, if any
+    ///
+    /// Pass [`ProxyConfig::new`] to connect to every destination directly. To use a proxy with a
+    /// custom connector, wrap it in a [`ProxyConnector`] and pass it to [`Builder::build`].
+    #[cfg(feature = "native-tls")]
+    pub fn build_native_tls(self, proxy: ProxyConfig) -> Adapter<crate::conns::NativeTlsWithProxy> {
+        let http = self.proxy_connector(proxy);
+        self.build(crate::conns::native_tls_over(http))
+    }
+
+    /// The TCP connector used by [`Builder::build_https`] and [`Builder::build_native_tls`]
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    fn proxy_connector(&self, proxy: ProxyConfig) -> ProxyConnector<hyper::client::HttpConnector> {
+        let mut http = crate::conns::http_connector();
+        http.set_keepalive(self.tcp_keepalive);
+        ProxyConnector::new(http, proxy)
+    }
+
+    /// Set how long idle connections are kept in the pool before being closed
+    ///
+    /// Pass `None` to keep idle connections until the server closes them. Defaults to 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.client_builder.pool_idle_timeout(timeout);
+        self
+    }
+
+    /// Set the maximum number of idle connections kept in the pool for each host
+    ///
+    /// Pass `0` to close connections after every request. Defaults to no limit.
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.client_builder.pool_max_idle_per_host(max_idle);
+        self
+    }
+
+    /// Only use HTTP/2, without negotiating the protocol with the server. Defaults to `false`.
+    pub fn http2_only(mut self, http2_only: bool) -> Self {
+        self.client_builder.http2_only(http2_only);
+        self
+    }
+
+    /// Set the interval at which HTTP/2 `PING` frames are sent to keep connections alive
+    ///
+    /// Pass `None` to disable HTTP/2 keep-alive, the default.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn http2_keep_alive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.client_builder.http2_keep_alive_interval(interval);
+        self
+    }
+
+    /// Set how long to wait for the acknowledgement of an HTTP/2 keep-alive `PING` before closing
+    /// the connection. Defaults to 20 seconds.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.client_builder.http2_keep_alive_timeout(timeout);
+        self
+    }
+
+    /// Set the interval of TCP keep-alive probes on idle connections
+    ///
+    /// The keep-alive is set on the connections of [`Builder::build_https`] and
+    /// [`Builder::build_native_tls`]. Pass `None` to disable TCP keep-alive, the default.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn tcp_keepalive(self, interval: Option<Duration>) -> Self {
+        Self {
+            tcp_keepalive: interval,
+            ..self
+        }
+    }
+
+    /// Count the connections of the HyperAdapter in `metrics`
+    ///
+    /// By default, every HyperAdapter has its own [`ConnectionMetrics`], available with
+    /// [`Adapter::connection_metrics`]. Use this to share counters between several adapters.
+    pub fn connection_metrics(self, metrics: ConnectionMetrics) -> Self {
+        Self { metrics, ..self }
     }
 
     /// Set the async sleep implementation used for timeouts
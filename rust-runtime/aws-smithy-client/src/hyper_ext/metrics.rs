@@ -0,0 +1,352 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Connection metrics for the Hyper connector
+//!
+//! Every connection opened by an [`Adapter`](super::Adapter) is counted in its
+//! [`ConnectionMetrics`]. Connections are counted as opened when the connector returns them, as
+//! reused when they serve a request after their first one, and as closed when Hyper drops them,
+//! e.g. after they stayed idle in the pool for too long. The same events are emitted with
+//! `tracing`, under the `aws_smithy_client::hyper_ext::metrics` target.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Counters of the connections opened, reused and closed by an [`Adapter`](super::Adapter)
+///
+/// `ConnectionMetrics` is cheap to clone: clones share the same counters. To aggregate the
+/// connections of several adapters, give them the same `ConnectionMetrics` with
+/// [`Builder::connection_metrics`](super::Builder::connection_metrics).
+#[derive(Clone, Default)]
+pub struct ConnectionMetrics(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+    opened: AtomicU64,
+    reused: AtomicU64,
+    closed: AtomicU64,
+}
+
+impl ConnectionMetrics {
+    /// Create new counters, starting at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of connections opened
+    pub fn connections_opened(&self) -> u64 {
+        self.0.opened.load(Ordering::Relaxed)
+    }
+
+    /// The number of requests sent over a connection that had already been used
+    pub fn connections_reused(&self) -> u64 {
+        self.0.reused.load(Ordering::Relaxed)
+    }
+
+    /// The number of connections closed
+    pub fn connections_closed(&self) -> u64 {
+        self.0.closed.load(Ordering::Relaxed)
+    }
+
+    /// The number of connections currently open, either idle in the pool or in use
+    pub fn connections_open(&self) -> u64 {
+        self.connections_opened()
+            .saturating_sub(self.connections_closed())
+    }
+
+    /// Record the response to a request, counting its connection as reused if it had already
+    /// served a request
+    pub(super) fn record_response<B>(&self, response: &http::Response<B>) {
+        if let Some(requests) = response.extensions().get::<ConnectionRequests>() {
+            let previous = requests.0.fetch_add(1, Ordering::Relaxed);
+            if previous > 0 {
+                self.0.reused.fetch_add(1, Ordering::Relaxed);
+                tracing::trace!(requests = previous + 1, "reused a pooled connection");
+            }
+        }
+    }
+
+    fn record_opened(&self) {
+        let opened = self.0.opened.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::debug!(
+            opened,
+            open = self.connections_open(),
+            "opened a connection"
+        );
+    }
+
+    fn record_closed(&self) {
+        let closed = self.0.closed.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::debug!(
+            closed,
+            open = self.connections_open(),
+            "closed a connection"
+        );
+    }
+}
+
+impl fmt::Debug for ConnectionMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionMetrics")
+            .field("opened", &self.connections_opened())
+            .field("reused", &self.connections_reused())
+            .field("closed", &self.connections_closed())
+            .finish()
+    }
+}
+
+/// The number of requests served by a connection, attached by Hyper to the responses it reads
+/// from the connection
+#[derive(Clone)]
+struct ConnectionRequests(Arc<AtomicUsize>);
+
+/// Connector counting the connections returned by `inner` in a [`ConnectionMetrics`]
+#[derive(Clone, Debug)]
+pub(super) struct MetricsConnector<C> {
+    inner: C,
+    metrics: ConnectionMetrics,
+}
+
+impl<C> MetricsConnector<C> {
+    pub(super) fn new(inner: C, metrics: ConnectionMetrics) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+impl<C> tower::Service<Uri> for MetricsConnector<C>
+where
+    C: tower::Service<Uri>,
+{
+    type Response = MetricsStream<C::Response>;
+    type Error = C::Error;
+    type Future = MetricsFuture<C::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, destination: Uri) -> Self::Future {
+        MetricsFuture {
+            inner: self.inner.call(destination),
+            metrics: Some(self.metrics.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Future returned by [`MetricsConnector`]
+    pub(super) struct MetricsFuture<F> {
+        #[pin]
+        inner: F,
+        metrics: Option<ConnectionMetrics>,
+    }
+}
+
+impl<F, S, E> Future for MetricsFuture<F>
+where
+    F: Future<Output = Result<S, E>>,
+{
+    type Output = Result<MetricsStream<S>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.poll(cx) {
+            Poll::Ready(Ok(stream)) => {
+                let metrics = this
+                    .metrics
+                    .take()
+                    .expect("futures must not be polled after completion");
+                metrics.record_opened();
+                Poll::Ready(Ok(MetricsStream {
+                    inner: stream,
+                    requests: ConnectionRequests(Arc::new(AtomicUsize::new(0))),
+                    _closed: ClosedGuard(metrics),
+                }))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Counts its connection as closed when dropped
+struct ClosedGuard(ConnectionMetrics);
+
+impl Drop for ClosedGuard {
+    fn drop(&mut self) {
+        self.0.record_closed();
+    }
+}
+
+pin_project! {
+    /// A connection counted in a [`ConnectionMetrics`]
+    pub(super) struct MetricsStream<S> {
+        #[pin]
+        inner: S,
+        requests: ConnectionRequests,
+        _closed: ClosedGuard,
+    }
+}
+
+impl<S: Connection> Connection for MetricsStream<S> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().extra(self.requests.clone())
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for MetricsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for MetricsStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+// `HttpConnector` is only available with the TLS features
+#[cfg(all(test, any(feature = "rustls", feature = "native-tls")))]
+mod test {
+    use std::time::Duration;
+
+    use hyper::client::HttpConnector;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tower::Service;
+
+    use aws_smithy_http::body::SdkBody;
+
+    use super::ConnectionMetrics;
+    use crate::hyper_ext::Adapter;
+
+    /// Serve `200 OK` responses over keep-alive connections on a local port
+    async fn server() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    let mut request = Vec::new();
+                    loop {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                        while let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            request.drain(..end + 4);
+                            stream
+                                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                                .await
+                                .unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn send_requests(
+        adapter: &mut Adapter<HttpConnector>,
+        addr: std::net::SocketAddr,
+        count: usize,
+    ) {
+        for _ in 0..count {
+            let response = adapter
+                .call(
+                    http::Request::builder()
+                        .uri(format!("http://{}/", addr))
+                        .body(SdkBody::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(&b"ok"[..], &body[..]);
+        }
+    }
+
+    async fn wait_for_closed(metrics: &ConnectionMetrics, closed: u64) {
+        for _ in 0..100 {
+            if metrics.connections_closed() == closed {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} closed connections: {:?}", closed, metrics);
+    }
+
+    #[tokio::test]
+    async fn pooled_connections_are_counted_as_reused() {
+        let addr = server().await;
+        let mut adapter = Adapter::builder().build(HttpConnector::new());
+        let metrics = adapter.connection_metrics().clone();
+
+        send_requests(&mut adapter, addr, 3).await;
+        assert_eq!(1, metrics.connections_opened());
+        assert_eq!(2, metrics.connections_reused());
+        assert_eq!(1, metrics.connections_open());
+
+        drop(adapter);
+        wait_for_closed(&metrics, 1).await;
+        assert_eq!(0, metrics.connections_open());
+    }
+
+    #[tokio::test]
+    async fn connections_are_not_pooled_without_idle_connections() {
+        let addr = server().await;
+        let metrics = ConnectionMetrics::new();
+        let mut adapter = Adapter::builder()
+            .pool_max_idle_per_host(0)
+            .connection_metrics(metrics.clone())
+            .build(HttpConnector::new());
+
+        send_requests(&mut adapter, addr, 3).await;
+        assert_eq!(3, metrics.connections_opened());
+        assert_eq!(0, metrics.connections_reused());
+        wait_for_closed(&metrics, 3).await;
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Interceptors observing and modifying the requests and responses of a client
+//!
+//! An [`Interceptor`] is registered on a [`Client`](crate::Client) at runtime, with
+//! [`Client::with_interceptor`](crate::Client::with_interceptor) or
+//! [`Builder::interceptor`](crate::Builder::interceptor), and is called at the following points
+//! of every operation:
+//!
+//! 1. [`read_before_execution`](Interceptor::read_before_execution), once, when the operation is
+//!    sent.
+//! 2. For every attempt:
+//!     1. [`modify_before_signing`](Interceptor::modify_before_signing), before the middleware runs.
+//!     2. [`read_before_transmit`](Interceptor::read_before_transmit), after the middleware ran,
+//!        right before the request is dispatched.
+//!     3. [`read_after_deserialization`](Interceptor::read_after_deserialization), once the
+//!        response was parsed.
+//! 3. [`modify_before_completion`](Interceptor::modify_before_completion), once, with the response
+//!    of the last attempt.
+//!
+//! Interceptors have access to the [`PropertyBag`](aws_smithy_http::property_bag::PropertyBag) of
+//! the operation through the request and the response. When a hook returns an error, the
+//! operation fails: with a [`SdkError::ConstructionFailure`] for the hooks called with the
+//! request, and with a [`SdkError::ResponseError`] for the hooks called with a successful
+//! response. The errors of the hooks called with the response of a failed attempt are logged, and
+//! the attempt keeps its original error.
+//!
+//! ```no_run
+//! use aws_smithy_client::interceptor::Interceptor;
+//! use aws_smithy_http::operation;
+//! use tower::BoxError;
+//!
+//! #[derive(Debug)]
+//! struct AddTraceHeader;
+//!
+//! impl Interceptor for AddTraceHeader {
+//!     fn modify_before_signing(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+//!         request
+//!             .http_mut()
+//!             .headers_mut()
+//!             .insert("x-trace-id", "1234".parse()?);
+//!         Ok(())
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use aws_smithy_http::operation::{self, Operation};
+use aws_smithy_http::result::{SdkError, SdkSuccess};
+use aws_smithy_http_tower::dispatch::BeforeDispatch;
+use pin_project_lite::pin_project;
+use tower::{BoxError, Layer, Service};
+
+/// Hooks into the requests and responses of a [`Client`](crate::Client)
+///
+/// Every hook does nothing by default. See [the module documentation](crate::interceptor) for
+/// when they are called.
+pub trait Interceptor: fmt::Debug + Send + Sync {
+    /// Called once per operation, before its first attempt, with the request of the operation
+    /// before any middleware ran
+    fn read_before_execution(&self, request: &operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt, before the middleware, which signs the request, runs
+    fn modify_before_signing(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt, with the request as it is about to be dispatched
+    fn read_before_transmit(&self, request: &operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt that received a response, once the response was parsed
+    fn read_after_deserialization(&self, response: &operation::Response) -> Result<(), BoxError> {
+        let _ = response;
+        Ok(())
+    }
+
+    /// Called once per operation, with the response of its last attempt, if any
+    fn modify_before_completion(&self, response: &mut operation::Response) -> Result<(), BoxError> {
+        let _ = response;
+        Ok(())
+    }
+}
+
+/// The interceptors registered on a client, called in the order they were registered
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Interceptors(Vec<Arc<dyn Interceptor>>);
+
+impl Interceptors {
+    pub(crate) fn push(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.0.push(interceptor);
+    }
+
+    pub(crate) fn read_before_execution(
+        &self,
+        request: &operation::Request,
+    ) -> Result<(), BoxError> {
+        self.0
+            .iter()
+            .try_for_each(|interceptor| interceptor.read_before_execution(request))
+    }
+
+    fn modify_before_signing(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        self.0
+            .iter()
+            .try_for_each(|interceptor| interceptor.modify_before_signing(request))
+    }
+
+    /// The hook calling `read_before_transmit` from the dispatch service, if there is any
+    /// interceptor
+    pub(crate) fn before_dispatch(&self) -> Option<BeforeDispatch> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let interceptors = self.clone();
+        Some(Arc::new(move |request: &operation::Request| {
+            interceptors
+                .0
+                .iter()
+                .try_for_each(|interceptor| interceptor.read_before_transmit(request))
+        }))
+    }
+
+    fn read_after_deserialization(&self, response: &operation::Response) -> Result<(), BoxError> {
+        self.0
+            .iter()
+            .try_for_each(|interceptor| interceptor.read_after_deserialization(response))
+    }
+
+    /// Call `modify_before_completion` with the raw response of `result`, if any
+    // `SdkError` is large, but this takes and returns the result of the client as is
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn modify_before_completion<T, E>(
+        &self,
+        result: Result<SdkSuccess<T>, SdkError<E>>,
+    ) -> Result<SdkSuccess<T>, SdkError<E>> {
+        intercept_response(result, |response| {
+            self.0
+                .iter()
+                .try_for_each(|interceptor| interceptor.modify_before_completion(response))
+        })
+    }
+}
+
+/// Call `hook` with the raw response of `result`, if any
+///
+/// The errors of `hook` turn successful responses into [`SdkError::ResponseError`]s. Failed
+/// responses keep their original error, and the error of `hook` is logged.
+#[allow(clippy::result_large_err)]
+fn intercept_response<T, E>(
+    result: Result<SdkSuccess<T>, SdkError<E>>,
+    hook: impl Fn(&mut operation::Response) -> Result<(), BoxError>,
+) -> Result<SdkSuccess<T>, SdkError<E>> {
+    match result {
+        Ok(mut success) => match hook(&mut success.raw) {
+            Ok(()) => Ok(success),
+            Err(err) => Err(SdkError::ResponseError {
+                err,
+                raw: success.raw,
+            }),
+        },
+        Err(SdkError::ServiceError { err, mut raw }) => {
+            log_hook_error(hook(&mut raw));
+            Err(SdkError::ServiceError { err, raw })
+        }
+        Err(SdkError::ResponseError { err, mut raw }) => {
+            log_hook_error(hook(&mut raw));
+            Err(SdkError::ResponseError { err, raw })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn log_hook_error(result: Result<(), BoxError>) {
+    if let Err(err) = result {
+        tracing::warn!(
+            err = %err,
+            "an interceptor failed on a response that already failed, keeping the original error"
+        );
+    }
+}
+
+/// A layer calling the per-attempt hooks of interceptors around the parsing of responses
+#[derive(Clone, Debug)]
+pub(crate) struct InterceptorLayer {
+    interceptors: Interceptors,
+}
+
+impl InterceptorLayer {
+    pub(crate) fn new(interceptors: Interceptors) -> Self {
+        Self { interceptors }
+    }
+}
+
+impl<S> Layer<S> for InterceptorLayer {
+    type Service = InterceptorService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService {
+            inner,
+            interceptors: self.interceptors.clone(),
+        }
+    }
+}
+
+/// A service calling the `modify_before_signing` and `read_after_deserialization` hooks of
+/// interceptors
+#[derive(Clone, Debug)]
+pub(crate) struct InterceptorService<S> {
+    inner: S,
+    interceptors: Interceptors,
+}
+
+impl<H, R, S, T, E> Service<Operation<H, R>> for InterceptorService<S>
+where
+    S: Service<Operation<H, R>, Response = SdkSuccess<T>, Error = SdkError<E>>,
+{
+    type Response = SdkSuccess<T>;
+    type Error = SdkError<E>;
+    type Future = InterceptorFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, operation: Operation<H, R>) -> Self::Future {
+        if self.interceptors.0.is_empty() {
+            return InterceptorFuture::Unintercepted {
+                future: self.inner.call(operation),
+            };
+        }
+        let (mut request, parts) = operation.into_request_response();
+        if let Err(err) = self.interceptors.modify_before_signing(&mut request) {
+            return InterceptorFuture::Failed { err: Some(err) };
+        }
+        InterceptorFuture::Intercepted {
+            future: self.inner.call(Operation::from_parts(request, parts)),
+            interceptors: self.interceptors.clone(),
+        }
+    }
+}
+
+pin_project! {
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    #[project = InterceptorFutureProj]
+    /// Future returned by an [`InterceptorService`]
+    pub(crate) enum InterceptorFuture<F> {
+        /// A request whose response is passed to the interceptors
+        Intercepted {
+            #[pin]
+            future: F,
+            interceptors: Interceptors,
+        },
+        /// A request rejected by an interceptor
+        Failed {
+            err: Option<BoxError>,
+        },
+        /// A request sent without interceptors
+        Unintercepted {
+            #[pin]
+            future: F,
+        },
+    }
+}
+
+impl<F, T, E> Future for InterceptorFuture<F>
+where
+    F: Future<Output = Result<SdkSuccess<T>, SdkError<E>>>,
+{
+    type Output = Result<SdkSuccess<T>, SdkError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            InterceptorFutureProj::Intercepted {
+                future,
+                interceptors,
+            } => match future.poll(cx) {
+                Poll::Ready(result) => Poll::Ready(intercept_response(result, |response| {
+                    interceptors.read_after_deserialization(response)
+                })),
+                Poll::Pending => Poll::Pending,
+            },
+            InterceptorFutureProj::Failed { err } => {
+                Poll::Ready(Err(SdkError::ConstructionFailure(
+                    err.take()
+                        .expect("futures must not be polled after completion"),
+                )))
+            }
+            InterceptorFutureProj::Unintercepted { future } => future.poll(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_http::response::ParseHttpResponse;
+    use aws_smithy_http::result::SdkError;
+    use tower::layer::util::Identity;
+    use tower::BoxError;
+
+    use crate::interceptor::Interceptor;
+    use crate::metrics::InMemoryMetricsSink;
+    use crate::static_tests::TestOperationError;
+    use crate::{Builder, Client};
+
+    /// Parses responses into their status code, and server errors into a service error
+    #[derive(Clone)]
+    struct StatusParser;
+
+    impl ParseHttpResponse for StatusParser {
+        type Output = Result<u16, TestOperationError>;
+
+        fn parse_unloaded(&self, response: &mut operation::Response) -> Option<Self::Output> {
+            let status = response.http().status();
+            Some(match status.is_server_error() {
+                true => Err(TestOperationError),
+                false => Ok(status.as_u16()),
+            })
+        }
+
+        fn parse_loaded(&self, _response: &http::Response<bytes::Bytes>) -> Self::Output {
+            unreachable!("responses are parsed unloaded")
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        hooks: Mutex<Vec<String>>,
+    }
+
+    /// Records its hooks, adds a header before signing and tags responses in their property bag
+    #[derive(Debug)]
+    struct RecordingInterceptor(Arc<Recorder>);
+
+    #[derive(Debug, PartialEq)]
+    struct Tag(&'static str);
+
+    impl RecordingInterceptor {
+        fn record(&self, hook: &str) {
+            self.0.hooks.lock().unwrap().push(hook.to_string());
+        }
+    }
+
+    impl Interceptor for RecordingInterceptor {
+        fn read_before_execution(&self, request: &operation::Request) -> Result<(), BoxError> {
+            assert!(request.http().headers().get("x-intercepted").is_none());
+            self.record("read_before_execution");
+            Ok(())
+        }
+
+        fn modify_before_signing(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+            request
+                .http_mut()
+                .headers_mut()
+                .insert("x-intercepted", "true".parse().unwrap());
+            request.properties_mut().insert(Tag("request"));
+            self.record("modify_before_signing");
+            Ok(())
+        }
+
+        fn read_before_transmit(&self, request: &operation::Request) -> Result<(), BoxError> {
+            assert_eq!(
+                Some(&Tag("request")),
+                request.properties().get::<Tag>(),
+                "the property bag is shared by the hooks"
+            );
+            self.record("read_before_transmit");
+            Ok(())
+        }
+
+        fn read_after_deserialization(
+            &self,
+            response: &operation::Response,
+        ) -> Result<(), BoxError> {
+            self.record(&format!(
+                "read_after_deserialization {}",
+                response.http().status().as_u16()
+            ));
+            Ok(())
+        }
+
+        fn modify_before_completion(
+            &self,
+            response: &mut operation::Response,
+        ) -> Result<(), BoxError> {
+            response.properties_mut().insert(Tag("response"));
+            self.record("modify_before_completion");
+            Ok(())
+        }
+    }
+
+    /// Fails the hook it is named after
+    #[derive(Debug, PartialEq)]
+    enum RejectingInterceptor {
+        ReadBeforeExecution,
+        ReadBeforeTransmit,
+        ModifyBeforeCompletion,
+    }
+
+    impl RejectingInterceptor {
+        fn reject(&self, hook: RejectingInterceptor) -> Result<(), BoxError> {
+            match *self == hook {
+                true => Err("rejected".into()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl Interceptor for RejectingInterceptor {
+        fn read_before_execution(&self, _request: &operation::Request) -> Result<(), BoxError> {
+            self.reject(RejectingInterceptor::ReadBeforeExecution)
+        }
+
+        fn read_before_transmit(&self, _request: &operation::Request) -> Result<(), BoxError> {
+            self.reject(RejectingInterceptor::ReadBeforeTransmit)
+        }
+
+        fn modify_before_completion(
+            &self,
+            _response: &mut operation::Response,
+        ) -> Result<(), BoxError> {
+            self.reject(RejectingInterceptor::ModifyBeforeCompletion)
+        }
+    }
+
+    fn operation() -> operation::Operation<StatusParser, ()> {
+        let request = http::Request::builder()
+            .uri("https://www.example.com")
+            .body(SdkBody::empty())
+            .unwrap();
+        operation::Operation::new(operation::Request::new(request), StatusParser)
+    }
+
+    /// A client whose connector counts its requests, checks that they were intercepted, and
+    /// responds with `status`
+    fn client(
+        dispatched: Arc<AtomicUsize>,
+        status: u16,
+    ) -> Client<impl crate::bounds::SmithyConnector, Identity> {
+        Builder::new()
+            .middleware(Identity::new())
+            .connector_fn(move |request: http::Request<SdkBody>| {
+                assert_eq!("true", request.headers()["x-intercepted"]);
+                dispatched.fetch_add(1, Ordering::SeqCst);
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(SdkBody::empty())
+                    .unwrap();
+                async { Ok(response) }
+            })
+            .build()
+    }
+
+    #[tokio::test]
+    async fn hooks_are_called_in_order() {
+        let dispatched = Arc::new(AtomicUsize::new(0));
+        let recorder = Arc::new(Recorder::default());
+        let client = client(dispatched.clone(), 200)
+            .with_interceptor(RecordingInterceptor(recorder.clone()));
+
+        let response = client.call_raw(operation()).await.expect("success");
+        assert_eq!(200, response.parsed);
+        assert_eq!(
+            Some(&Tag("response")),
+            response.raw.properties().get::<Tag>()
+        );
+        assert_eq!(
+            vec![
+                "read_before_execution",
+                "modify_before_signing",
+                "read_before_transmit",
+                "read_after_deserialization 200",
+                "modify_before_completion"
+            ],
+            *recorder.hooks.lock().unwrap()
+        );
+        assert_eq!(1, dispatched.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn hook_errors_fail_the_operation() {
+        let dispatched = Arc::new(AtomicUsize::new(0));
+        let recorder = Arc::new(Recorder::default());
+        let client = client(dispatched.clone(), 200)
+            .with_interceptor(RecordingInterceptor(recorder))
+            .with_interceptor(RejectingInterceptor::ReadBeforeTransmit);
+
+        let err = client.call_raw(operation()).await.expect_err("rejected");
+        assert!(
+            matches!(&err, SdkError::ConstructionFailure(err) if err.to_string() == "rejected"),
+            "{:?}",
+            err
+        );
+        assert_eq!(0, dispatched.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn operations_rejected_before_execution_are_completed() {
+        let dispatched = Arc::new(AtomicUsize::new(0));
+        let metrics = Arc::new(InMemoryMetricsSink::new());
+        let client = client(dispatched.clone(), 200)
+            .with_interceptor(RejectingInterceptor::ReadBeforeExecution)
+            .with_metrics_sink(metrics.clone());
+
+        let err = client.call_raw(operation()).await.expect_err("rejected");
+        assert!(
+            matches!(&err, SdkError::ConstructionFailure(err) if err.to_string() == "rejected"),
+            "{:?}",
+            err
+        );
+        assert_eq!(0, dispatched.load(Ordering::SeqCst));
+        let operations = metrics.operations();
+        assert_eq!(1, operations.len());
+        assert_eq!(0, operations[0].attempts);
+        assert!(!operations[0].succeeded);
+    }
+
+    #[tokio::test]
+    async fn hook_errors_keep_the_original_error() {
+        let dispatched = Arc::new(AtomicUsize::new(0));
+        let recorder = Arc::new(Recorder::default());
+        let failing_client = client(dispatched.clone(), 500)
+            .with_interceptor(RecordingInterceptor(recorder.clone()))
+            .with_interceptor(RejectingInterceptor::ModifyBeforeCompletion);
+        let err = failing_client
+            .call_raw(operation())
+            .await
+            .expect_err("server error");
+        assert!(matches!(&err, SdkError::ServiceError { .. }), "{:?}", err);
+
+        // successful responses fail with the error of the hook
+        let client = client(dispatched, 200)
+            .with_interceptor(RecordingInterceptor(recorder))
+            .with_interceptor(RejectingInterceptor::ModifyBeforeCompletion);
+        let err = client.call_raw(operation()).await.expect_err("rejected");
+        assert!(
+            matches!(&err, SdkError::ResponseError { err, .. } if err.to_string() == "rejected"),
+            "{:?}",
+            err
+        );
+    }
+}
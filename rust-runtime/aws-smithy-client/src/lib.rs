@@ -22,7 +22,10 @@
 )]
 
 pub mod bounds;
+pub mod circuit_breaker;
 pub mod erase;
+pub mod interceptor;
+pub mod metrics;
 pub mod retry;
 
 // https://github.com/rust-lang/rust/issues/72081
@@ -33,6 +36,10 @@ pub use builder::Builder;
 #[cfg(feature = "test-util")]
 pub mod dvr;
 #[cfg(feature = "test-util")]
+pub mod fault_injection;
+#[cfg(feature = "test-util")]
+pub mod mock_server;
+#[cfg(feature = "test-util")]
 pub mod test_connection;
 
 pub mod http_connector;
@@ -62,7 +69,7 @@ pub mod conns {
     #[cfg(feature = "rustls")]
     lazy_static::lazy_static! {
         static ref HTTPS_NATIVE_ROOTS: Https = {
-            hyper_rustls::HttpsConnector::with_native_roots()
+            https_native_roots().build()
         };
     }
 
@@ -71,6 +78,18 @@ pub mod conns {
         HTTPS_NATIVE_ROOTS.clone()
     }
 
+    /// A builder for HTTPS connectors trusting the native root certificates, and accepting both
+    /// `http` and `https` URIs
+    #[cfg(feature = "rustls")]
+    fn https_native_roots(
+    ) -> hyper_rustls::HttpsConnectorBuilder<hyper_rustls::builderstates::WantsProtocols3> {
+        hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .enable_http2()
+    }
+
     #[cfg(feature = "native-tls")]
     pub fn native_tls() -> NativeTls {
         hyper_tls::HttpsConnector::new()
@@ -79,6 +98,55 @@ pub mod conns {
     #[cfg(feature = "native-tls")]
     pub type NativeTls = hyper_tls::HttpsConnector<hyper::client::HttpConnector>;
 
+    #[cfg(feature = "rustls")]
+    pub type HttpsWithProxy = hyper_rustls::HttpsConnector<
+        crate::hyper_ext::ProxyConnector<hyper::client::HttpConnector>,
+    >;
+
+    /// Like [`https`], but connections go through the proxy selected by `proxy`, if any.
+    #[cfg(feature = "rustls")]
+    pub fn https_with_proxy(proxy: crate::hyper_ext::ProxyConfig) -> HttpsWithProxy {
+        https_over(crate::hyper_ext::ProxyConnector::new(
+            http_connector(),
+            proxy,
+        ))
+    }
+
+    #[cfg(feature = "rustls")]
+    pub(crate) fn https_over(
+        http: crate::hyper_ext::ProxyConnector<hyper::client::HttpConnector>,
+    ) -> HttpsWithProxy {
+        https_native_roots().wrap_connector(http)
+    }
+
+    /// A TCP connector accepting both `http` and `https` URIs, to be wrapped in a TLS connector
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) fn http_connector() -> hyper::client::HttpConnector {
+        let mut http = hyper::client::HttpConnector::new();
+        http.enforce_http(false);
+        http
+    }
+
+    #[cfg(feature = "native-tls")]
+    pub type NativeTlsWithProxy =
+        hyper_tls::HttpsConnector<crate::hyper_ext::ProxyConnector<hyper::client::HttpConnector>>;
+
+    /// Like [`native_tls`], but connections go through the proxy selected by `proxy`, if any.
+    #[cfg(feature = "native-tls")]
+    pub fn native_tls_with_proxy(proxy: crate::hyper_ext::ProxyConfig) -> NativeTlsWithProxy {
+        native_tls_over(crate::hyper_ext::ProxyConnector::new(
+            http_connector(),
+            proxy,
+        ))
+    }
+
+    #[cfg(feature = "native-tls")]
+    pub(crate) fn native_tls_over(
+        http: crate::hyper_ext::ProxyConnector<hyper::client::HttpConnector>,
+    ) -> NativeTlsWithProxy {
+        hyper_tls::HttpsConnector::new_with_connector(http)
+    }
+
     #[cfg(feature = "rustls")]
     pub type Rustls =
         crate::hyper_ext::Adapter<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
@@ -86,16 +154,21 @@ pub mod conns {
 
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Instant;
 use tower::{Layer, Service, ServiceBuilder, ServiceExt};
 
+use crate::circuit_breaker::{check_circuit, CircuitBreaker, CircuitBreakerLayer};
+use crate::interceptor::{Interceptor, InterceptorLayer, Interceptors};
+use crate::metrics::{MetricsLayer, MetricsSink, OperationRecorder};
+use crate::retry::{ClientRateLimitLayer, DispatchedUri, HedgeLayer};
 use crate::timeout::generate_timeout_service_params_from_timeout_config;
 use aws_smithy_async::rt::sleep::AsyncSleep;
 use aws_smithy_http::body::SdkBody;
-use aws_smithy_http::operation::Operation;
+use aws_smithy_http::operation::{self, Operation};
 use aws_smithy_http::response::ParseHttpResponse;
 pub use aws_smithy_http::result::{SdkError, SdkSuccess};
 use aws_smithy_http::retry::ClassifyResponse;
-use aws_smithy_http_tower::dispatch::DispatchLayer;
+use aws_smithy_http_tower::dispatch::{BeforeDispatch, DispatchLayer};
 use aws_smithy_http_tower::parse_response::ParseResponseLayer;
 use aws_smithy_types::retry::ProvideErrorKind;
 use aws_smithy_types::timeout::TimeoutConfig;
@@ -130,6 +203,9 @@ pub struct Client<
     retry_policy: RetryPolicy,
     timeout_config: TimeoutConfig,
     sleep_impl: TriState<Arc<dyn AsyncSleep>>,
+    circuit_breaker: Option<CircuitBreaker>,
+    interceptors: Interceptors,
+    metrics_sink: Arc<dyn MetricsSink>,
 }
 
 // Quick-create for people who just want "the default".
@@ -186,12 +262,48 @@ impl<C, M, R> Client<C, M, R> {
         self.set_sleep_impl(Some(sleep_impl));
         self
     }
+
+    /// Add an [`Interceptor`], called after the interceptors already added.
+    pub fn add_interceptor(&mut self, interceptor: impl Interceptor + 'static) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    /// Add an [`Interceptor`], called after the interceptors already added.
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.add_interceptor(interceptor);
+        self
+    }
+
+    /// Set the [`MetricsSink`] receiving the metrics of every operation.
+    pub fn set_metrics_sink(&mut self, metrics_sink: Arc<dyn MetricsSink>) {
+        self.metrics_sink = metrics_sink;
+    }
+
+    /// Set the [`MetricsSink`] receiving the metrics of every operation.
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.set_metrics_sink(metrics_sink);
+        self
+    }
 }
 
 fn check_send_sync<T: Send + Sync>(t: T) -> T {
     t
 }
 
+/// The hook called with every attempt once the middleware has resolved its endpoint, right before
+/// it is dispatched
+fn before_dispatch(interceptors: &Interceptors, dispatched_uri: DispatchedUri) -> BeforeDispatch {
+    let interceptors = interceptors.before_dispatch();
+    Arc::new(move |request: &operation::Request| {
+        dispatched_uri.record(request.http().uri());
+        check_circuit(request)?;
+        match &interceptors {
+            Some(interceptors) => interceptors(request),
+            None => Ok(()),
+        }
+    })
+}
+
 impl<C, M, R> Client<C, M, R>
 where
     C: bounds::SmithyConnector,
@@ -205,7 +317,7 @@ where
     pub async fn call<O, T, E, Retry>(&self, input: Operation<O, Retry>) -> Result<T, SdkError<E>>
     where
         O: Send + Sync,
-        Retry: Send + Sync,
+        Retry: Send + Sync + Clone + ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
         R::Policy: bounds::SmithyRetryPolicy<O, T, E, Retry>,
         bounds::Parsed<<M as bounds::SmithyMiddleware<C>>::Service, O, Retry>:
             Service<Operation<O, Retry>, Response = SdkSuccess<T>, Error = SdkError<E>> + Clone,
@@ -219,11 +331,11 @@ where
     /// implementing unsupported features.
     pub async fn call_raw<O, T, E, Retry>(
         &self,
-        input: Operation<O, Retry>,
+        mut input: Operation<O, Retry>,
     ) -> Result<SdkSuccess<T>, SdkError<E>>
     where
         O: Send + Sync,
-        Retry: Send + Sync,
+        Retry: Send + Sync + Clone + ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
         R::Policy: bounds::SmithyRetryPolicy<O, T, E, Retry>,
         // This bound is not _technically_ inferred by all the previous bounds, but in practice it
         // is because _we_ know that there is only implementation of Service for Parsed
@@ -244,26 +356,65 @@ where
         }
         let connector = self.connector.clone();
 
+        let start = Instant::now();
+        let metadata = input.metadata().cloned();
+        let recorder = OperationRecorder::default();
+        input.properties_mut().insert(recorder.clone());
+        let dispatched_uri = DispatchedUri::default();
+        input.properties_mut().insert(dispatched_uri.clone());
+
         let timeout_service_params = generate_timeout_service_params_from_timeout_config(
             &self.timeout_config,
             self.sleep_impl.clone().into(),
         );
 
+        let retry_policy = self
+            .retry_policy
+            .new_request_policy(self.sleep_impl.clone().into());
         let svc = ServiceBuilder::new()
+            // The API call timeout covers every attempt and the backoff between them, while the
+            // attempt timeout covers a single dispatch, up to the response headers of streaming
+            // responses. Attempts that time out are retried.
             .layer(TimeoutLayer::new(timeout_service_params.api_call))
-            .retry(
-                self.retry_policy
-                    .new_request_policy(self.sleep_impl.clone().into()),
-            )
+            .retry(retry_policy.clone())
+            .layer(HedgeLayer::new(
+                self.retry_policy.hedging(),
+                self.sleep_impl.clone().into(),
+                retry_policy,
+            ))
+            .layer(ClientRateLimitLayer::new(
+                self.retry_policy.client_rate_limiter(),
+                self.sleep_impl.clone().into(),
+            ))
+            .layer(CircuitBreakerLayer::new(self.circuit_breaker.clone()))
+            .layer(MetricsLayer::new(self.metrics_sink.clone()))
             .layer(TimeoutLayer::new(timeout_service_params.api_call_attempt))
+            .layer(InterceptorLayer::new(self.interceptors.clone()))
             .layer(ParseResponseLayer::<O, Retry>::new())
             // These layers can be considered as occurring in order. That is, first invoke the
             // customer-provided middleware, then dispatch dispatch over the wire.
             .layer(&self.middleware)
-            .layer(DispatchLayer::new())
+            .layer(
+                DispatchLayer::new().with_before_dispatch(Some(before_dispatch(
+                    &self.interceptors,
+                    dispatched_uri,
+                ))),
+            )
             .service(connector);
 
-        check_send_sync(svc).ready().await?.call(input).await
+        // Operations rejected before their first attempt go through the same completion hooks and
+        // metrics as the others
+        let result = match self.interceptors.read_before_execution(input.request()) {
+            Ok(()) => match check_send_sync(svc).ready().await {
+                Ok(svc) => svc.call(input).await,
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(SdkError::ConstructionFailure(err)),
+        };
+        let result = self.interceptors.modify_before_completion(result);
+        self.metrics_sink
+            .record_operation(&recorder.finish(metadata, start, result.as_ref()));
+        result
     }
 
     /// Statically check the validity of a `Client` without a request to send.
@@ -0,0 +1,429 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Operational metrics of the operations sent by a client
+//!
+//! A [`MetricsSink`] registered on a [`Client`](crate::Client), with
+//! [`Client::with_metrics_sink`](crate::Client::with_metrics_sink) or
+//! [`Builder::metrics_sink`](crate::Builder::metrics_sink), receives:
+//! - [`AttemptMetrics`] after every attempt of an operation, including attempts that timed out.
+//! - [`OperationMetrics`] once the operation completed, after all its retries.
+//!
+//! By default, metrics are discarded by a [`NoOpMetricsSink`]. An [`InMemoryMetricsSink`] keeps
+//! them in memory, which is mostly useful in tests. To export metrics, e.g. to Prometheus or
+//! StatsD, implement [`MetricsSink`]:
+//!
+//! ```no_run
+//! use aws_smithy_client::metrics::{AttemptMetrics, MetricsSink, OperationMetrics};
+//!
+//! #[derive(Debug)]
+//! struct LogMetrics;
+//!
+//! impl MetricsSink for LogMetrics {
+//!     fn record_attempt(&self, metrics: &AttemptMetrics) {
+//!         println!("attempt {} took {:?}", metrics.attempt, metrics.latency);
+//!     }
+//!
+//!     fn record_operation(&self, metrics: &OperationMetrics) {
+//!         println!("{} attempts, {:?} of backoff", metrics.attempts, metrics.backoff);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_smithy_http::operation::{self, Metadata, Operation};
+use aws_smithy_http::result::{SdkError, SdkSuccess};
+use aws_smithy_http::retry::ClassifyResponse;
+use aws_smithy_types::retry::RetryKind;
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+/// Receives the metrics of the operations sent by a client
+pub trait MetricsSink: fmt::Debug + Send + Sync {
+    /// Record the metrics of a single attempt
+    fn record_attempt(&self, metrics: &AttemptMetrics);
+
+    /// Record the metrics of a whole operation, once it completed
+    fn record_operation(&self, metrics: &OperationMetrics);
+}
+
+/// The metrics of a single attempt of an operation
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct AttemptMetrics {
+    /// The operation and service names, if the operation has [`Metadata`]
+    pub operation: Option<Metadata>,
+    /// The number of the attempt, starting at 1
+    pub attempt: u32,
+    /// How long the attempt took, from sending the request to parsing the response
+    pub latency: Duration,
+    /// The size of the request body, if it is known before the request is sent
+    ///
+    /// Bodies are not counted while they are streamed, so this is `None` for streaming request
+    /// bodies of unknown size.
+    pub request_bytes: Option<u64>,
+    /// The size of the response body, if a response was received and its size is known when the
+    /// attempt completes
+    ///
+    /// This is `None` for streaming and chunked responses that do not declare their size, since the
+    /// attempt completes before their body is read.
+    pub response_bytes: Option<u64>,
+    /// The status code of the response, if a response was received
+    pub status: Option<u16>,
+    /// Whether the attempt succeeded, i.e. the retry policy of the operation classified its
+    /// result as not needing a retry
+    pub succeeded: bool,
+}
+
+/// The metrics of an operation, including all its attempts
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct OperationMetrics {
+    /// The operation and service names, if the operation has [`Metadata`]
+    pub operation: Option<Metadata>,
+    /// The number of attempts made
+    pub attempts: u32,
+    /// How long the operation took, including all its attempts and the backoff between them
+    pub latency: Duration,
+    /// The total backoff between attempts
+    pub backoff: Duration,
+    /// Whether the operation succeeded
+    pub succeeded: bool,
+}
+
+/// A [`MetricsSink`] discarding all metrics, used by default
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct NoOpMetricsSink;
+
+impl NoOpMetricsSink {
+    /// Create a new `NoOpMetricsSink`
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MetricsSink for NoOpMetricsSink {
+    fn record_attempt(&self, _metrics: &AttemptMetrics) {}
+
+    fn record_operation(&self, _metrics: &OperationMetrics) {}
+}
+
+/// A [`MetricsSink`] keeping all metrics in memory
+#[derive(Debug, Default)]
+pub struct InMemoryMetricsSink {
+    attempts: Mutex<Vec<AttemptMetrics>>,
+    operations: Mutex<Vec<OperationMetrics>>,
+}
+
+impl InMemoryMetricsSink {
+    /// Create a new, empty, `InMemoryMetricsSink`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The metrics of all the attempts recorded so far, in the order they completed
+    pub fn attempts(&self) -> Vec<AttemptMetrics> {
+        self.attempts.lock().unwrap().clone()
+    }
+
+    /// The metrics of all the operations recorded so far, in the order they completed
+    pub fn operations(&self) -> Vec<OperationMetrics> {
+        self.operations.lock().unwrap().clone()
+    }
+}
+
+impl MetricsSink for InMemoryMetricsSink {
+    fn record_attempt(&self, metrics: &AttemptMetrics) {
+        self.attempts.lock().unwrap().push(metrics.clone());
+    }
+
+    fn record_operation(&self, metrics: &OperationMetrics) {
+        self.operations.lock().unwrap().push(metrics.clone());
+    }
+}
+
+/// Counts the attempts of an operation and the backoff between them
+///
+/// The recorder is stored in the property bag of the operation, which is shared by its attempts.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OperationRecorder(Arc<Mutex<RecordedOperation>>);
+
+#[derive(Debug, Default)]
+struct RecordedOperation {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl OperationRecorder {
+    /// Count a new attempt, returning its number
+    fn start_attempt(&self) -> u32 {
+        let mut operation = self.0.lock().unwrap();
+        operation.attempts += 1;
+        operation.attempts
+    }
+
+    pub(crate) fn record_backoff(&self, backoff: Duration) {
+        self.0.lock().unwrap().backoff += backoff;
+    }
+
+    /// The metrics of the operation, once it completed
+    pub(crate) fn finish<T, E>(
+        &self,
+        operation: Option<Metadata>,
+        start: Instant,
+        result: Result<&SdkSuccess<T>, &SdkError<E>>,
+    ) -> OperationMetrics {
+        let recorded = self.0.lock().unwrap();
+        OperationMetrics {
+            operation,
+            attempts: recorded.attempts,
+            latency: start.elapsed(),
+            backoff: recorded.backoff,
+            succeeded: result.is_ok(),
+        }
+    }
+}
+
+/// A layer recording the [`AttemptMetrics`] of every attempt
+#[derive(Clone, Debug)]
+pub(crate) struct MetricsLayer {
+    sink: Arc<dyn MetricsSink>,
+}
+
+impl MetricsLayer {
+    pub(crate) fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Self { sink }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MetricsService<S> {
+    inner: S,
+    sink: Arc<dyn MetricsSink>,
+}
+
+impl<H, R, S, T, E> Service<Operation<H, R>> for MetricsService<S>
+where
+    S: Service<Operation<H, R>, Response = SdkSuccess<T>, Error = SdkError<E>>,
+    R: ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
+{
+    type Response = SdkSuccess<T>;
+    type Error = SdkError<E>;
+    type Future = MetricsFuture<S::Future, R>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, operation: Operation<H, R>) -> Self::Future {
+        let attempt = operation
+            .properties()
+            .get::<OperationRecorder>()
+            .map(OperationRecorder::start_attempt)
+            .unwrap_or(1);
+        let metrics = AttemptMetrics {
+            operation: operation.metadata().cloned(),
+            attempt,
+            latency: Duration::ZERO,
+            request_bytes: operation.request().http().body().content_length(),
+            response_bytes: None,
+            status: None,
+            succeeded: false,
+        };
+        let classifier = operation.retry_policy().clone();
+        MetricsFuture {
+            inner: self.inner.call(operation),
+            classifier,
+            sink: self.sink.clone(),
+            metrics: Some(metrics),
+            start: Instant::now(),
+        }
+    }
+}
+
+pin_project! {
+    /// Future returned by a [`MetricsService`]
+    pub(crate) struct MetricsFuture<F, R> {
+        #[pin]
+        inner: F,
+        classifier: R,
+        sink: Arc<dyn MetricsSink>,
+        metrics: Option<AttemptMetrics>,
+        start: Instant,
+    }
+}
+
+impl<F, R, T, E> Future for MetricsFuture<F, R>
+where
+    F: Future<Output = Result<SdkSuccess<T>, SdkError<E>>>,
+    R: ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
+{
+    type Output = Result<SdkSuccess<T>, SdkError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        let mut metrics = this
+            .metrics
+            .take()
+            .expect("futures must not be polled after completion");
+        metrics.latency = this.start.elapsed();
+        // A response that parsed successfully may still be retried, e.g. a `503` classified as a
+        // server error, so the attempt only succeeded if no retry is needed.
+        metrics.succeeded =
+            result.is_ok() && this.classifier.classify(result.as_ref()) == RetryKind::Unnecessary;
+        if let Some(raw) = raw_response(&result) {
+            metrics.status = Some(raw.http().status().as_u16());
+            metrics.response_bytes = raw.http().body().content_length();
+        }
+        this.sink.record_attempt(&metrics);
+        Poll::Ready(result)
+    }
+}
+
+fn raw_response<T, E>(result: &Result<SdkSuccess<T>, SdkError<E>>) -> Option<&operation::Response> {
+    match result {
+        Ok(success) => Some(&success.raw),
+        Err(SdkError::ServiceError { raw, .. }) | Err(SdkError::ResponseError { raw, .. }) => {
+            Some(raw)
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use aws_smithy_async::rt::sleep::TokioSleep;
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_http::response::ParseHttpResponse;
+    use aws_smithy_http::result::{SdkError, SdkSuccess};
+    use aws_smithy_http::retry::ClassifyResponse;
+    use aws_smithy_types::retry::{ErrorKind, RetryKind};
+    use tower::layer::util::Identity;
+
+    use crate::metrics::InMemoryMetricsSink;
+    use crate::static_tests::TestOperationError;
+    use crate::{retry, Builder};
+
+    /// Parses responses into their status code
+    #[derive(Clone)]
+    struct StatusParser;
+
+    impl ParseHttpResponse for StatusParser {
+        type Output = Result<u16, TestOperationError>;
+
+        fn parse_unloaded(&self, _response: &mut operation::Response) -> Option<Self::Output> {
+            None
+        }
+
+        fn parse_loaded(&self, response: &http::Response<bytes::Bytes>) -> Self::Output {
+            Ok(response.status().as_u16())
+        }
+    }
+
+    /// Retries `503` responses
+    #[derive(Clone)]
+    struct RetryUnavailable;
+
+    impl ClassifyResponse<SdkSuccess<u16>, SdkError<TestOperationError>> for RetryUnavailable {
+        fn classify(
+            &self,
+            response: Result<&SdkSuccess<u16>, &SdkError<TestOperationError>>,
+        ) -> RetryKind {
+            match response {
+                Ok(success) if success.parsed == 503 => RetryKind::Error(ErrorKind::ServerError),
+                _ => RetryKind::Unnecessary,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn attempts_and_operations_are_recorded() {
+        let sink = Arc::new(InMemoryMetricsSink::new());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut builder = Builder::new()
+            .middleware(Identity::new())
+            .connector_fn(move |_request: http::Request<SdkBody>| {
+                let status = match requests.fetch_add(1, Ordering::SeqCst) {
+                    0 => 503,
+                    _ => 200,
+                };
+                async move {
+                    Ok(http::Response::builder()
+                        .status(status)
+                        .body(SdkBody::from("response"))
+                        .unwrap())
+                }
+            })
+            .sleep_impl(Some(Arc::new(TokioSleep::new())))
+            .metrics_sink(Some(sink.clone()));
+        builder.set_retry_config(retry::Config::default().with_base(|| 1_f64));
+        let client = builder.build();
+        tokio::time::pause();
+
+        let request = http::Request::builder()
+            .uri("https://www.example.com")
+            .body(SdkBody::from("hello"))
+            .unwrap();
+        let operation = operation::Operation::new(operation::Request::new(request), StatusParser)
+            .with_metadata(operation::Metadata::new("GetThing", "ThingService"))
+            .with_retry_policy(RetryUnavailable);
+        assert_eq!(200, client.call(operation).await.unwrap());
+
+        let attempts = sink.attempts();
+        assert_eq!(2, attempts.len());
+        for (attempt, (status, succeeded)) in attempts.iter().zip([(503, false), (200, true)]) {
+            assert_eq!(
+                "GetThing",
+                attempt.operation.as_ref().map(|op| op.name()).unwrap()
+            );
+            assert_eq!(Some(5), attempt.request_bytes);
+            assert_eq!(Some(8), attempt.response_bytes);
+            assert_eq!(Some(status), attempt.status);
+            assert_eq!(succeeded, attempt.succeeded);
+        }
+        assert_eq!(
+            vec![1, 2],
+            attempts.iter().map(|a| a.attempt).collect::<Vec<_>>()
+        );
+
+        let operations = sink.operations();
+        assert_eq!(1, operations.len());
+        let operation = &operations[0];
+        assert_eq!(
+            "ThingService",
+            operation.operation.as_ref().map(|op| op.service()).unwrap()
+        );
+        assert_eq!(2, operation.attempts);
+        assert_eq!(Duration::from_secs(1), operation.backoff);
+        assert!(operation.succeeded);
+    }
+}
@@ -0,0 +1,534 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! In-process HTTP server for end-to-end tests
+//!
+//! Unlike the connections in [`test_connection`](crate::test_connection), which replace the
+//! connector entirely, a [`MockServer`] listens on a local port so that requests go through the
+//! real connector, serialization and hyper code paths. Requests are answered according to a list
+//! of [`Rule`]s, and the expectations set on those rules are verified when the server is dropped.
+//!
+//! ```rust,no_run
+//! use aws_smithy_client::mock_server::{MockServer, Rule};
+//!
+//! # async fn example() {
+//! let server = MockServer::builder()
+//!     .rule(
+//!         Rule::new()
+//!             .method(http::Method::GET)
+//!             .path("/greeting")
+//!             .header("x-api-key", "1234")
+//!             .respond_with(http::Response::new("hello"))
+//!             .times(1),
+//!     )
+//!     .start();
+//! // plug `server.endpoint()` into the client config, then send the requests...
+//! let endpoint = server.endpoint();
+//! # }
+//! ```
+
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{Method, StatusCode, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::Body;
+use tokio::sync::oneshot;
+
+use aws_smithy_http::endpoint::Endpoint;
+
+type Responder = Arc<dyn Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync>;
+
+/// Request matcher and the response to send to matching requests
+///
+/// A rule with no matchers matches every request. When no response is configured, matching
+/// requests receive an empty `200 OK` response.
+#[derive(Clone)]
+pub struct Rule {
+    method: Option<Method>,
+    path: Option<String>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    responder: Responder,
+    expected_calls: Option<usize>,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rule {
+    /// Create a rule that matches every request
+    pub fn new() -> Self {
+        Rule {
+            method: None,
+            path: None,
+            headers: vec![],
+            responder: Arc::new(|_| http::Response::new(Bytes::new())),
+            expected_calls: None,
+        }
+    }
+
+    /// Only match requests with the given method
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Only match requests with the given path
+    ///
+    /// The query string isn't part of the path and is ignored.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Only match requests with a header set to the given value
+    ///
+    /// # Panics
+    /// Panics if `name` or `value` isn't a valid header name or value.
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        let name = HeaderName::from_bytes(name.as_ref().as_bytes()).expect("valid header name");
+        let value = HeaderValue::from_str(value.as_ref()).expect("valid header value");
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Send `response` to every matching request
+    pub fn respond_with(self, response: http::Response<impl Into<Bytes>>) -> Self {
+        let (parts, body) = response.into_parts();
+        let (status, headers, body) = (parts.status, parts.headers, body.into());
+        self.respond_with_fn(move |_| {
+            let mut response = http::Response::new(body.clone());
+            *response.status_mut() = status;
+            *response.headers_mut() = headers.clone();
+            response
+        })
+    }
+
+    /// Compute the response to each matching request with `responder`
+    pub fn respond_with_fn(
+        mut self,
+        responder: impl Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync + 'static,
+    ) -> Self {
+        self.responder = Arc::new(responder);
+        self
+    }
+
+    /// Expect the rule to match exactly `times` requests
+    ///
+    /// The expectation is verified by [`MockServer::verify`] and when the server is dropped.
+    pub fn times(mut self, times: usize) -> Self {
+        self.expected_calls = Some(times);
+        self
+    }
+
+    fn matches(&self, request: &http::Request<Bytes>) -> bool {
+        self.method.iter().all(|method| request.method() == method)
+            && self.path.iter().all(|path| request.uri().path() == path)
+            && self
+                .headers
+                .iter()
+                .all(|(name, value)| request.headers().get_all(name).iter().any(|v| v == value))
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rule")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("expected_calls", &self.expected_calls)
+            .finish()
+    }
+}
+
+/// Builder for [`MockServer`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    rules: Vec<Rule>,
+}
+
+impl Builder {
+    /// Add a rule
+    ///
+    /// Each request is answered by the first rule that matches it. Requests that don't match any
+    /// rule receive a `404 Not Found` response and fail verification.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Start the server on an ephemeral port of the loopback interface
+    ///
+    /// # Panics
+    /// Panics if no port can be bound, or when not called from within a Tokio runtime.
+    pub fn start(self) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+        let addr = listener
+            .local_addr()
+            .expect("bound listeners have an address");
+        let state = Arc::new(State {
+            rules: self
+                .rules
+                .into_iter()
+                .map(|rule| (rule, AtomicUsize::new(0)))
+                .collect(),
+            requests: Mutex::new(vec![]),
+            unmatched: Mutex::new(vec![]),
+        });
+        let make_service = {
+            let state = state.clone();
+            make_service_fn(move |_conn| {
+                let state = state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        let state = state.clone();
+                        async move { state.respond(request).await }
+                    }))
+                }
+            })
+        };
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = hyper::Server::from_tcp(listener)
+            .expect("failed to listen on a local port")
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::warn!(err = %err, "mock server failed");
+            }
+        });
+        MockServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+}
+
+struct State {
+    rules: Vec<(Rule, AtomicUsize)>,
+    requests: Mutex<Vec<http::Request<Bytes>>>,
+    unmatched: Mutex<Vec<String>>,
+}
+
+impl State {
+    async fn respond(
+        &self,
+        request: http::Request<Body>,
+    ) -> Result<http::Response<Body>, hyper::Error> {
+        let (parts, body) = request.into_parts();
+        let request = http::Request::from_parts(parts, hyper::body::to_bytes(body).await?);
+        let response = match self.rules.iter().find(|(rule, _)| rule.matches(&request)) {
+            Some((rule, calls)) => {
+                calls.fetch_add(1, Ordering::SeqCst);
+                (rule.responder)(&request)
+            }
+            None => {
+                let description = format!("{} {}", request.method(), request.uri());
+                self.unmatched.lock().unwrap().push(description.clone());
+                let mut response =
+                    http::Response::new(Bytes::from(format!("no rule matches {}", description)));
+                *response.status_mut() = StatusCode::NOT_FOUND;
+                response
+            }
+        };
+        self.requests.lock().unwrap().push(request);
+        Ok(response.map(Body::from))
+    }
+}
+
+/// HTTP server answering requests on a local port according to a list of [`Rule`]s
+///
+/// The server shuts down when dropped. Unless the thread is already panicking, dropping the
+/// server also [verifies](MockServer::verify) its expectations.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Create a builder for a `MockServer`
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Base URI of the server, e.g. `http://127.0.0.1:41234`
+    pub fn uri(&self) -> Uri {
+        Uri::builder()
+            .scheme("http")
+            .authority(self.addr.to_string().as_str())
+            .path_and_query("/")
+            .build()
+            .expect("socket addresses are valid authorities")
+    }
+
+    /// Endpoint directing a client to the server
+    ///
+    /// The endpoint is [immutable](Endpoint::immutable) so that services which prefix the host,
+    /// e.g. with an S3 bucket name, still send their requests to the server.
+    pub fn endpoint(&self) -> Endpoint {
+        Endpoint::immutable(self.uri())
+    }
+
+    /// Requests received so far, with their bodies
+    pub fn received_requests(&self) -> Vec<http::Request<Bytes>> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let mut copy = http::Request::new(request.body().clone());
+                *copy.method_mut() = request.method().clone();
+                *copy.uri_mut() = request.uri().clone();
+                *copy.version_mut() = request.version();
+                *copy.headers_mut() = request.headers().clone();
+                copy
+            })
+            .collect()
+    }
+
+    /// Verify that every request matched a rule and that every rule matched as many requests as
+    /// it expects
+    ///
+    /// # Panics
+    /// Panics with a description of every unmet expectation.
+    pub fn verify(&self) {
+        let mut failures: Vec<String> = self
+            .state
+            .unmatched
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| format!("no rule matched request `{}`", request))
+            .collect();
+        for (rule, calls) in &self.state.rules {
+            let calls = calls.load(Ordering::SeqCst);
+            match rule.expected_calls {
+                Some(expected) if expected != calls => failures.push(format!(
+                    "expected {} request(s) to match {:?} but got {}",
+                    expected, rule, calls
+                )),
+                _ => {}
+            }
+        }
+        if !failures.is_empty() {
+            panic!(
+                "mock server expectations were not met:\n{}",
+                failures.join("\n")
+            );
+        }
+    }
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockServer")
+            .field("addr", &self.addr)
+            .field(
+                "rules",
+                &self
+                    .state
+                    .rules
+                    .iter()
+                    .map(|(rule, _)| rule)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use http::{Method, StatusCode};
+    use hyper::client::HttpConnector;
+    use tower::Service;
+
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_http::response::ParseHttpResponse;
+
+    use super::{MockServer, Rule};
+    use crate::hyper_ext::Adapter;
+    use crate::static_tests::TestOperationError;
+    use crate::Builder;
+
+    async fn send(
+        server: &MockServer,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &'static str,
+    ) -> (StatusCode, Bytes) {
+        let mut uri: http::Uri = path.parse().unwrap();
+        server.endpoint().set_endpoint(&mut uri, None);
+        let mut request = http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = Adapter::builder()
+            .build(HttpConnector::new())
+            .call(request.body(SdkBody::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            hyper::body::to_bytes(response.into_body()).await.unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn requests_are_answered_by_the_first_matching_rule() {
+        let server = MockServer::builder()
+            .rule(
+                Rule::new()
+                    .method(Method::GET)
+                    .path("/greeting")
+                    .header("x-api-key", "1234")
+                    .respond_with(http::Response::new("hello"))
+                    .times(1),
+            )
+            .rule(
+                Rule::new()
+                    .method(Method::POST)
+                    .path("/echo")
+                    .respond_with_fn(|request| http::Response::new(request.body().clone()))
+                    .times(2),
+            )
+            .rule(
+                Rule::new().respond_with(
+                    http::Response::builder()
+                        .status(503)
+                        .body("unavailable")
+                        .unwrap(),
+                ),
+            )
+            .start();
+
+        assert_eq!(
+            (StatusCode::OK, Bytes::from("hello")),
+            send(
+                &server,
+                Method::GET,
+                "/greeting",
+                &[("x-api-key", "1234")],
+                ""
+            )
+            .await
+        );
+        assert_eq!(
+            (StatusCode::OK, Bytes::from("ping")),
+            send(&server, Method::POST, "/echo?a=b", &[], "ping").await
+        );
+        assert_eq!(
+            (StatusCode::OK, Bytes::from("pong")),
+            send(&server, Method::POST, "/echo", &[], "pong").await
+        );
+        // a missing header falls through to the catch-all rule
+        assert_eq!(
+            (StatusCode::SERVICE_UNAVAILABLE, Bytes::from("unavailable")),
+            send(&server, Method::GET, "/greeting", &[], "").await
+        );
+
+        let requests = server.received_requests();
+        assert_eq!(4, requests.len());
+        assert_eq!("/echo?a=b", requests[1].uri());
+        assert_eq!(&b"ping"[..], &requests[1].body()[..]);
+        server.verify();
+    }
+
+    /// Parses responses into their body
+    #[derive(Clone)]
+    struct BodyParser;
+
+    impl ParseHttpResponse for BodyParser {
+        type Output = Result<String, TestOperationError>;
+
+        fn parse_unloaded(&self, _response: &mut operation::Response) -> Option<Self::Output> {
+            None
+        }
+
+        fn parse_loaded(&self, response: &http::Response<Bytes>) -> Self::Output {
+            Ok(String::from_utf8(response.body().to_vec()).unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn clients_are_directed_to_the_server_by_its_endpoint() {
+        let server = MockServer::builder()
+            .rule(
+                Rule::new()
+                    .method(Method::POST)
+                    .path("/greeting")
+                    .respond_with_fn(|request| http::Response::new(request.body().clone()))
+                    .times(1),
+            )
+            .start();
+        let endpoint = server.endpoint();
+        let client = Builder::new()
+            .connector(Adapter::builder().build(HttpConnector::new()))
+            .middleware_fn(move |mut request: operation::Request| {
+                endpoint.set_endpoint(request.http_mut().uri_mut(), None);
+                request
+            })
+            .build();
+
+        let request = http::Request::post("https://greeting.us-east-1.amazonaws.com/greeting")
+            .body(SdkBody::from("hello"))
+            .unwrap();
+        let operation = operation::Operation::new(operation::Request::new(request), BodyParser);
+        assert_eq!("hello", client.call(operation).await.unwrap());
+        assert_eq!("/greeting", server.received_requests()[0].uri());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no rule matched request `DELETE /greeting`")]
+    async fn unmatched_requests_fail_verification_on_drop() {
+        let server = MockServer::builder()
+            .rule(Rule::new().method(Method::GET))
+            .start();
+        let (status, _) = send(&server, Method::DELETE, "/greeting", &[], "").await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected 2 request(s) to match")]
+    async fn unmet_call_counts_fail_verification_on_drop() {
+        let server = MockServer::builder()
+            .rule(Rule::new().path("/greeting").times(2))
+            .start();
+        send(&server, Method::GET, "/greeting", &[], "").await;
+    }
+}
@@ -15,20 +15,39 @@
 //! - [`RetryHandler`]: A request-scoped retry policy, backed by request-local state and shared
 //!   state contained within [`Standard`].
 //! - [`Config`]: Static configuration (max attempts, max backoff etc.)
-
+//! - [`ClientRateLimiter`]: In the `adaptive` retry mode, a rate limiter shared by all the requests
+//!   of a client, that slows down the sending of requests when the service throttles them.
+//! - [`Adaptive`]: A [`Standard`] policy that always uses the `adaptive` retry mode.
+//! - [`RetryPartition`]: A scope for retry quota. Requests are assigned a partition according to
+//!   the configured [`RetryPartitioning`], so that a failing endpoint only drains its own quota.
+//! - [`Hedging`]: Opt-in hedging of slow idempotent requests, configured with a [`HedgeConfig`].
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::metrics::OperationRecorder;
+use crate::timeout::is_attempt_timeout;
 use crate::{SdkError, SdkSuccess};
 use aws_smithy_async::rt::sleep::AsyncSleep;
 use aws_smithy_http::operation;
 use aws_smithy_http::operation::Operation;
 use aws_smithy_http::retry::ClassifyResponse;
-use aws_smithy_types::retry::{ErrorKind, RetryKind};
+use aws_smithy_types::retry::{BackoffStrategy, ErrorKind, RetryKind, RetryMode};
 use tracing::Instrument;
 
+pub use hedge::{HedgeConfig, HedgeDelay, HedgeFuture, HedgeLayer, HedgeService, Hedging};
+pub use rate_limit::{
+    ClientRateLimitFuture, ClientRateLimitLayer, ClientRateLimitService, ClientRateLimiter,
+};
+
+mod hedge;
+mod rate_limit;
+
 /// A policy instantiator.
 ///
 /// Implementors are essentially "policy factories" that can produce a new instance of a retry
@@ -43,13 +62,24 @@ where
 
     /// Create a new policy mechanism instance.
     fn new_request_policy(&self, sleep_impl: Option<Arc<dyn AsyncSleep>>) -> Self::Policy;
+
+    /// The rate limiter every request attempt must be allowed to be sent by, if any.
+    fn client_rate_limiter(&self) -> Option<ClientRateLimiter> {
+        None
+    }
+
+    /// The policy slow requests are hedged with, if any.
+    fn hedging(&self) -> Option<Hedging> {
+        None
+    }
 }
 
 /// Retry Policy Configuration
 ///
-/// Without specific use cases, users should generally rely on the default values set by `[Config::default]`(Config::default).`
+/// Without specific use cases, users should generally rely on the default values set by [`Config::default`](Config::default).
 ///
-/// Currently these fields are private and no setters provided. As needed, this configuration will become user-modifiable in the future..
+/// The retry quota, backoff and retry mode can be tuned with the `with_` setters, or from a
+/// [`RetryConfig`](aws_smithy_types::retry::RetryConfig).
 #[derive(Clone, Debug)]
 pub struct Config {
     initial_retry_tokens: usize,
@@ -58,7 +88,11 @@ pub struct Config {
     timeout_retry_cost: usize,
     max_attempts: u32,
     max_backoff: Duration,
+    backoff_strategy: BackoffStrategy,
     base: fn() -> f64,
+    mode: RetryMode,
+    partitioning: RetryPartitioning,
+    hedging: Option<HedgeConfig>,
 }
 
 impl Config {
@@ -82,6 +116,76 @@ impl Config {
         self.max_attempts = max_attempts;
         self
     }
+
+    /// Override the retry quota each retry partition starts with
+    ///
+    /// Every retry consumes some of the quota of its partition, and requests are no longer retried
+    /// once it is exhausted. Defaults to `500`.
+    pub fn with_initial_retry_tokens(mut self, initial_retry_tokens: usize) -> Self {
+        self.initial_retry_tokens = initial_retry_tokens;
+        self
+    }
+
+    /// Override the retry quota consumed by retrying a request. Defaults to `5`.
+    pub fn with_retry_cost(mut self, retry_cost: usize) -> Self {
+        self.retry_cost = retry_cost;
+        self
+    }
+
+    /// Override the retry quota consumed by retrying a request that failed with a
+    /// [transient error](ErrorKind::TransientError). Defaults to `10`.
+    pub fn with_timeout_retry_cost(mut self, timeout_retry_cost: usize) -> Self {
+        self.timeout_retry_cost = timeout_retry_cost;
+        self
+    }
+
+    /// Override the retry quota given back by a request that succeeded on its first attempt.
+    /// Defaults to `1`.
+    ///
+    /// Requests that succeeded after being retried give back the quota their last retry consumed.
+    pub fn with_no_retry_increment(mut self, no_retry_increment: usize) -> Self {
+        self.no_retry_increment = no_retry_increment;
+        self
+    }
+
+    /// Override the maximum time to wait before retrying a request. Defaults to 20 seconds.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Override how long to wait before retrying a request. Defaults to
+    /// [`BackoffStrategy::FullJitter`].
+    pub fn with_backoff_strategy(mut self, backoff_strategy: BackoffStrategy) -> Self {
+        self.backoff_strategy = backoff_strategy;
+        self
+    }
+
+    /// Override the retry mode
+    ///
+    /// In [`RetryMode::Adaptive`], requests are delayed before being sent, including the initial
+    /// attempt, when the service has been throttling them.
+    pub fn with_retry_mode(mut self, mode: RetryMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Override how requests are assigned a [`RetryPartition`]
+    ///
+    /// By default, all the requests of a client share the same retry quota.
+    pub fn with_retry_partitioning(mut self, partitioning: RetryPartitioning) -> Self {
+        self.partitioning = partitioning;
+        self
+    }
+
+    /// Enable hedging of slow idempotent requests
+    ///
+    /// Hedges consume retry quota like retries do, so requests are no longer hedged once the quota
+    /// of their partition is exhausted. Hedging is disabled by default.
+    pub fn with_hedging(mut self, hedging: HedgeConfig) -> Self {
+        self.hedging = Some(hedging);
+        self
+    }
 }
 
 impl Default for Config {
@@ -93,15 +197,40 @@ impl Default for Config {
             timeout_retry_cost: 10,
             max_attempts: MAX_ATTEMPTS,
             max_backoff: Duration::from_secs(20),
+            backoff_strategy: BackoffStrategy::FullJitter,
             // by default, use a random base for exponential backoff
             base: fastrand::f64,
+            mode: RetryMode::Standard,
+            partitioning: RetryPartitioning::Client,
+            hedging: None,
         }
     }
 }
 
 impl From<aws_smithy_types::retry::RetryConfig> for Config {
     fn from(conf: aws_smithy_types::retry::RetryConfig) -> Self {
-        Self::default().with_max_attempts(conf.max_attempts())
+        let mut config = Self::default()
+            .with_max_attempts(conf.max_attempts())
+            .with_retry_mode(conf.mode());
+        if let Some(initial_retry_tokens) = conf.initial_retry_tokens() {
+            config = config.with_initial_retry_tokens(initial_retry_tokens);
+        }
+        if let Some(retry_cost) = conf.retry_cost() {
+            config = config.with_retry_cost(retry_cost);
+        }
+        if let Some(timeout_retry_cost) = conf.timeout_retry_cost() {
+            config = config.with_timeout_retry_cost(timeout_retry_cost);
+        }
+        if let Some(no_retry_increment) = conf.no_retry_increment() {
+            config = config.with_no_retry_increment(no_retry_increment);
+        }
+        if let Some(max_backoff) = conf.max_backoff() {
+            config = config.with_max_backoff(max_backoff);
+        }
+        if let Some(backoff_strategy) = conf.backoff_strategy() {
+            config = config.with_backoff_strategy(backoff_strategy);
+        }
+        config
     }
 }
 
@@ -114,8 +243,8 @@ const RETRY_COST: usize = 5;
 /// An implementation of the `standard` AWS retry strategy as specified in the SEP. A `Strategy` is scoped to a client.
 /// For an individual request, call [`Standard::new_request_policy()`](Standard::new_request_policy)
 ///
-/// In the future, adding support for the adaptive retry strategy will be added by adding a `TokenBucket` to
-/// `CrossRequestRetryState`
+/// When the configured [`RetryMode`] is [`RetryMode::Adaptive`], a [`ClientRateLimiter`] is added to
+/// the state shared between requests.
 /// Its main functionality is via `new_request_policy` which creates a `RetryHandler` to manage the retry for
 /// an individual request.
 #[derive(Debug, Clone)]
@@ -128,13 +257,19 @@ impl Standard {
     /// Construct a new standard retry policy from the given policy configuration.
     pub fn new(config: Config) -> Self {
         Self {
-            shared_state: CrossRequestRetryState::new(config.initial_retry_tokens),
+            shared_state: CrossRequestRetryState::new(config.mode),
             config,
         }
     }
 
+    /// The retry quota currently available to the requests of `partition`.
+    pub fn retry_quota(&self, partition: &RetryPartition) -> usize {
+        self.shared_state.quota_available(partition, &self.config)
+    }
+
     /// Set the configuration for this retry policy.
     pub fn with_config(&mut self, config: Config) -> &mut Self {
+        self.shared_state.set_retry_mode(config.mode);
         self.config = config;
         self
     }
@@ -151,6 +286,19 @@ impl NewRequestPolicy for Standard {
             sleep_impl,
         }
     }
+
+    fn client_rate_limiter(&self) -> Option<ClientRateLimiter> {
+        self.shared_state.rate_limiter.clone()
+    }
+
+    fn hedging(&self) -> Option<Hedging> {
+        let hedge_config = self.config.hedging.clone()?;
+        Some(Hedging::new(
+            hedge_config,
+            self.shared_state.clone(),
+            self.config.clone(),
+        ))
+    }
 }
 
 impl Default for Standard {
@@ -159,10 +307,65 @@ impl Default for Standard {
     }
 }
 
+/// Manage retries for a service using the `adaptive` retry mode
+///
+/// This is a [`Standard`] retry policy whose configuration always uses [`RetryMode::Adaptive`]: on
+/// top of the retry quota, requests are delayed before being sent whenever the service has been
+/// throttling them, following the rate of a [`ClientRateLimiter`].
+#[derive(Debug, Clone)]
+pub struct Adaptive {
+    standard: Standard,
+}
+
+impl Adaptive {
+    /// Construct a new adaptive retry policy from the given policy configuration.
+    pub fn new(config: Config) -> Self {
+        Self {
+            standard: Standard::new(config.with_retry_mode(RetryMode::Adaptive)),
+        }
+    }
+
+    /// The retry quota currently available to the requests of `partition`.
+    pub fn retry_quota(&self, partition: &RetryPartition) -> usize {
+        self.standard.retry_quota(partition)
+    }
+
+    /// Set the configuration for this retry policy.
+    pub fn with_config(&mut self, config: Config) -> &mut Self {
+        self.standard
+            .with_config(config.with_retry_mode(RetryMode::Adaptive));
+        self
+    }
+}
+
+impl NewRequestPolicy for Adaptive {
+    type Policy = RetryHandler;
+
+    fn new_request_policy(&self, sleep_impl: Option<Arc<dyn AsyncSleep>>) -> Self::Policy {
+        self.standard.new_request_policy(sleep_impl)
+    }
+
+    fn client_rate_limiter(&self) -> Option<ClientRateLimiter> {
+        self.standard.client_rate_limiter()
+    }
+
+    fn hedging(&self) -> Option<Hedging> {
+        self.standard.hedging()
+    }
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
+}
+
 #[derive(Clone, Debug)]
 struct RequestLocalRetryState {
     attempts: u32,
     last_quota_usage: Option<usize>,
+    last_backoff: Option<Duration>,
+    partition: RetryPartition,
 }
 
 impl Default for RequestLocalRetryState {
@@ -171,6 +374,8 @@ impl Default for RequestLocalRetryState {
             // Starts at one to account for the initial request that failed and warranted a retry
             attempts: 1,
             last_quota_usage: None,
+            last_backoff: None,
+            partition: RetryPartition::default(),
         }
     }
 }
@@ -181,48 +386,190 @@ impl RequestLocalRetryState {
     }
 }
 
-/* TODO(retries)
 /// RetryPartition represents a scope for cross request retry state
 ///
-/// For example, a retry partition could be the id of a service. This would give each service a separate retry budget.
-struct RetryPartition(Cow<'static, str>); */
+/// For example, a retry partition could be the host of an endpoint. This would give each endpoint a
+/// separate retry budget. Requests that aren't assigned a partition share the default partition.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RetryPartition(Cow<'static, str>);
+
+impl RetryPartition {
+    /// Create a new retry partition with the given name
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    /// The name of this partition
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RetryPartition {
+    fn default() -> Self {
+        Self::new("default")
+    }
+}
+
+impl fmt::Display for RetryPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// How requests are assigned a [`RetryPartition`]
+#[non_exhaustive]
+#[derive(Clone)]
+pub enum RetryPartitioning {
+    /// All the requests of the client share the [default partition](RetryPartition::default).
+    Client,
+    /// Requests are partitioned by the host of their URI, once it has been resolved by the
+    /// middleware.
+    ///
+    /// Requests whose URI has no host are assigned the default partition.
+    EndpointHost,
+    /// Requests are partitioned by the given function, which can for example read the region of
+    /// the request from its property bag. Requests for which it returns `None` are assigned the
+    /// default partition.
+    Custom(fn(&operation::Request) -> Option<RetryPartition>),
+}
+
+impl RetryPartitioning {
+    pub(crate) fn partition(&self, request: &operation::Request) -> RetryPartition {
+        let partition = match self {
+            RetryPartitioning::Client => None,
+            RetryPartitioning::EndpointHost => {
+                let dispatched_host = request
+                    .properties()
+                    .get::<DispatchedUri>()
+                    .and_then(DispatchedUri::host);
+                dispatched_host
+                    .or_else(|| request.http().uri().host().map(str::to_owned))
+                    .map(RetryPartition::new)
+            }
+            RetryPartitioning::Custom(partition) => partition(request),
+        };
+        partition.unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for RetryPartitioning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryPartitioning::Client => f.write_str("Client"),
+            RetryPartitioning::EndpointHost => f.write_str("EndpointHost"),
+            RetryPartitioning::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// The URI the attempts of an operation were last dispatched to
+///
+/// Endpoints are usually resolved by the middleware, so the URI of an operation only has a host
+/// once it went through the middleware. The client records the URI of every attempt right before
+/// it is dispatched, so that requests can be partitioned by the host they are actually sent to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DispatchedUri(Arc<Mutex<Option<http::Uri>>>);
+
+impl DispatchedUri {
+    pub(crate) fn record(&self, uri: &http::Uri) {
+        *self.0.lock().unwrap() = Some(uri.clone());
+    }
+
+    fn host(&self) -> Option<String> {
+        let uri = self.0.lock().unwrap();
+        uri.as_ref().and_then(|uri| uri.host()).map(str::to_owned)
+    }
+}
 
 /// Shared state between multiple requests to the same client.
 #[derive(Clone, Debug)]
 struct CrossRequestRetryState {
-    quota_available: Arc<Mutex<usize>>,
+    quota_available: Arc<Mutex<HashMap<RetryPartition, usize>>>,
+    rate_limiter: Option<ClientRateLimiter>,
+    latencies: hedge::Latencies,
 }
 
-// clippy is upset that we didn't use AtomicUsize here, but doing so makes the code
-// significantly more complicated for negligible benefit.
-#[allow(clippy::mutex_atomic)]
 impl CrossRequestRetryState {
-    pub fn new(initial_quota: usize) -> Self {
-        Self {
-            quota_available: Arc::new(Mutex::new(initial_quota)),
+    pub fn new(mode: RetryMode) -> Self {
+        let mut state = Self {
+            quota_available: Default::default(),
+            rate_limiter: None,
+            latencies: Default::default(),
+        };
+        state.set_retry_mode(mode);
+        state
+    }
+
+    /// Add or remove the rate limiter, keeping the current one if it is still needed.
+    fn set_retry_mode(&mut self, mode: RetryMode) {
+        match mode {
+            RetryMode::Adaptive => {
+                self.rate_limiter.get_or_insert_with(ClientRateLimiter::new);
+            }
+            _ => self.rate_limiter = None,
         }
     }
 
-    fn quota_release(&self, value: Option<usize>, config: &Config) {
-        let mut quota = self.quota_available.lock().unwrap();
+    fn update_rate_limiter(&self, retry_kind: &RetryKind) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.update(retry_kind == &RetryKind::Error(ErrorKind::ThrottlingError));
+        }
+    }
+
+    /// The quota available to `partition`. Partitions start with the configured initial quota.
+    fn quota_available(&self, partition: &RetryPartition, config: &Config) -> usize {
+        let quotas = self.quota_available.lock().unwrap();
+        quotas
+            .get(partition)
+            .copied()
+            .unwrap_or(config.initial_retry_tokens)
+    }
+
+    fn quota_release(&self, partition: &RetryPartition, value: Option<usize>, config: &Config) {
+        let mut quotas = self.quota_available.lock().unwrap();
+        let quota = quotas
+            .entry(partition.clone())
+            .or_insert(config.initial_retry_tokens);
         *quota += value.unwrap_or(config.no_retry_increment);
+        tracing::trace!(partition = %partition, quota_available = *quota, "retry quota released");
     }
 
-    /// Attempt to acquire retry quota for `ErrorKind`
+    /// Attempt to acquire retry quota for `ErrorKind` from `partition`
     ///
     /// If quota is available, the amount of quota consumed is returned
     /// If no quota is available, `None` is returned.
-    fn quota_acquire(&self, err: &ErrorKind, config: &Config) -> Option<usize> {
-        let mut quota = self.quota_available.lock().unwrap();
+    fn quota_acquire(
+        &self,
+        partition: &RetryPartition,
+        err: &ErrorKind,
+        config: &Config,
+    ) -> Option<usize> {
         let retry_cost = if err == &ErrorKind::TransientError {
             config.timeout_retry_cost
         } else {
             config.retry_cost
         };
+        self.quota_acquire_cost(partition, retry_cost, config)
+    }
+
+    /// Attempt to acquire `retry_cost` quota from `partition`
+    fn quota_acquire_cost(
+        &self,
+        partition: &RetryPartition,
+        retry_cost: usize,
+        config: &Config,
+    ) -> Option<usize> {
+        let mut quotas = self.quota_available.lock().unwrap();
+        let quota = quotas
+            .entry(partition.clone())
+            .or_insert(config.initial_retry_tokens);
         if retry_cost > *quota {
+            tracing::debug!(partition = %partition, quota_available = *quota, "retry quota exhausted");
             None
         } else {
             *quota -= retry_cost;
+            tracing::trace!(partition = %partition, quota_available = *quota, "retry quota acquired");
             Some(retry_cost)
         }
     }
@@ -246,7 +593,8 @@ pub struct RetryHandler {
 #[cfg(test)]
 impl RetryHandler {
     fn retry_quota(&self) -> usize {
-        *self.shared.quota_available.lock().unwrap()
+        self.shared
+            .quota_available(&self.local.partition, &self.config)
     }
 }
 
@@ -260,24 +608,16 @@ impl RetryHandler {
             if self.local.attempts == self.config.max_attempts {
                 return None;
             }
-            self.shared.quota_acquire(error_kind, &self.config)?
+            self.shared
+                .quota_acquire(&self.local.partition, error_kind, &self.config)?
         };
-        /*
-        From the retry spec:
-            b = random number within the range of: 0 <= b <= 1
-            r = 2
-            t_i = min(br^i, MAX_BACKOFF);
-         */
-        let r: i32 = 2;
-        let b = (self.config.base)();
-        // `self.local.attempts` tracks number of requests made including the initial request
-        // The initial attempt shouldn't count towards backoff calculations so we subtract it
-        let backoff = b * (r.pow(self.local.attempts - 1) as f64);
-        let backoff = Duration::from_secs_f64(backoff).min(self.config.max_backoff);
+        let backoff = self.backoff();
         let next = RetryHandler {
             local: RequestLocalRetryState {
                 attempts: self.local.attempts + 1,
                 last_quota_usage: Some(quota_used),
+                last_backoff: Some(backoff),
+                partition: self.local.partition.clone(),
             },
             shared: self.shared.clone(),
             config: self.config.clone(),
@@ -287,13 +627,51 @@ impl RetryHandler {
         Some((next, backoff))
     }
 
+    /// Compute how long to wait before the next attempt, according to the backoff strategy
+    fn backoff(&self) -> Duration {
+        /*
+        From the retry spec:
+            b = random number within the range of: 0 <= b <= 1
+            r = 2
+            t_i = min(br^i, MAX_BACKOFF);
+         */
+        if let BackoffStrategy::Fixed(backoff) = self.config.backoff_strategy {
+            return backoff;
+        }
+        let r: f64 = 2.0;
+        let b = (self.config.base)();
+        let max_backoff = self.config.max_backoff.as_secs_f64();
+        // `self.local.attempts` tracks number of requests made including the initial request
+        // The initial attempt shouldn't count towards backoff calculations so we subtract it
+        let exponential = r.powi(self.local.attempts as i32 - 1);
+        let backoff = match self.config.backoff_strategy {
+            BackoffStrategy::EqualJitter => {
+                let t = exponential.min(max_backoff);
+                t / 2.0 + b * t / 2.0
+            }
+            BackoffStrategy::DecorrelatedJitter => {
+                let previous = self
+                    .local
+                    .last_backoff
+                    .map(|backoff| backoff.as_secs_f64())
+                    .unwrap_or(1.0);
+                1.0 + b * (previous * 3.0 - 1.0).max(0.0)
+            }
+            _ => b * exponential,
+        };
+        Duration::from_secs_f64(backoff.min(max_backoff))
+    }
+
     fn should_retry(&self, retry_kind: &RetryKind) -> Option<(Self, Duration)> {
         match retry_kind {
             RetryKind::Explicit(dur) => Some((self.clone(), *dur)),
             RetryKind::UnretryableFailure => None,
             RetryKind::Unnecessary => {
-                self.shared
-                    .quota_release(self.local.last_quota_usage, &self.config);
+                self.shared.quota_release(
+                    &self.local.partition,
+                    self.local.last_quota_usage,
+                    &self.config,
+                );
                 None
             }
             RetryKind::Error(err) => self.should_retry_error(err),
@@ -301,7 +679,9 @@ impl RetryHandler {
         }
     }
 
-    fn retry_for(&self, retry_kind: RetryKind) -> Option<BoxFuture<Self>> {
+    /// Returns the future of the next attempt, and how long it backs off for
+    fn retry_for(&self, retry_kind: RetryKind) -> Option<(BoxFuture<Self>, Duration)> {
+        self.shared.update_rate_limiter(&retry_kind);
         let (next, dur) = self.should_retry(&retry_kind)?;
 
         let sleep = match &self.sleep_impl {
@@ -319,7 +699,7 @@ impl RetryHandler {
             next
         }
         .instrument(tracing::info_span!("retry", kind = &debug(retry_kind)));
-        Some(check_send(Box::pin(fut)))
+        Some((check_send(Box::pin(fut)), dur))
     }
 }
 
@@ -337,9 +717,18 @@ where
         req: &Operation<Handler, R>,
         result: Result<&SdkSuccess<T>, &SdkError<E>>,
     ) -> Option<Self::Future> {
-        let policy = req.retry_policy();
-        let retry_kind = policy.classify(result);
-        self.retry_for(retry_kind)
+        let retry_kind = match result {
+            // Attempts that timed out never reached the retry classifier of the operation
+            Err(err) if is_attempt_timeout(err) => RetryKind::Error(ErrorKind::TransientError),
+            _ => req.retry_policy().classify(result),
+        };
+        let mut handler = self.clone();
+        handler.local.partition = self.config.partitioning.partition(req.request());
+        let (next, backoff) = handler.retry_for(retry_kind)?;
+        if let Some(recorder) = req.properties().get::<OperationRecorder>() {
+            recorder.record_backoff(backoff);
+        }
+        Some(next)
     }
 
     fn clone_request(&self, req: &Operation<Handler, R>) -> Option<Operation<Handler, R>> {
@@ -354,9 +743,14 @@ fn check_send<T: Send>(t: T) -> T {
 #[cfg(test)]
 mod test {
 
-    use crate::retry::{Config, NewRequestPolicy, RetryHandler, Standard};
+    use crate::retry::{
+        Adaptive, Config, DispatchedUri, NewRequestPolicy, RetryHandler, RetryPartition,
+        RetryPartitioning, Standard, INITIAL_RETRY_TOKENS,
+    };
 
-    use aws_smithy_types::retry::{ErrorKind, RetryKind};
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_types::retry::{BackoffStrategy, ErrorKind, RetryConfig, RetryKind, RetryMode};
 
     use std::time::Duration;
 
@@ -453,8 +847,11 @@ mod test {
             policy.should_retry(&RetryKind::Unnecessary).is_none(),
             "it should not retry success"
         );
-        let available = policy.shared.quota_available.lock().unwrap();
-        assert_eq!(100, *available, "successful request should replenish quota");
+        assert_eq!(
+            100,
+            policy.retry_quota(),
+            "successful request should replenish quota"
+        );
     }
 
     #[test]
@@ -525,4 +922,194 @@ mod test {
         assert!(no_retry.is_none());
         assert_eq!(policy.retry_quota(), 480);
     }
+
+    #[test]
+    fn adaptive_mode_from_retry_config() {
+        let conf = Config::from(RetryConfig::new().with_retry_mode(RetryMode::Adaptive));
+        let mut policy = Standard::new(conf.clone());
+        let rate_limiter = policy.client_rate_limiter().expect("adaptive mode");
+
+        policy.with_config(conf.with_max_attempts(5));
+        assert!(
+            policy.client_rate_limiter().is_some(),
+            "the rate limiter is kept across configuration changes"
+        );
+        // Both handles refer to the same limiter: throttling through one enables the other.
+        rate_limiter.update(true);
+        assert!(policy
+            .client_rate_limiter()
+            .unwrap()
+            .acquire_permission_delay()
+            .is_some());
+
+        policy.with_config(test_config());
+        assert!(policy.client_rate_limiter().is_none());
+        assert!(Standard::default().client_rate_limiter().is_none());
+    }
+
+    #[tokio::test]
+    async fn throttling_enables_the_rate_limiter() {
+        let policy = Adaptive::new(test_config());
+        let rate_limiter = policy.client_rate_limiter().expect("adaptive mode");
+        let handler = policy.new_request_policy(None);
+
+        let _ = handler.retry_for(RetryKind::Error(ErrorKind::ServerError));
+        assert_eq!(None, rate_limiter.acquire_permission_delay());
+
+        let _ = handler.retry_for(RetryKind::Error(ErrorKind::ThrottlingError));
+        assert!(rate_limiter.acquire_permission_delay().is_some());
+    }
+
+    #[test]
+    fn partitions_have_separate_quotas() {
+        let mut conf = test_config();
+        conf.initial_retry_tokens = 10;
+        let policy = Standard::new(conf);
+        let east = RetryPartition::new("us-east-1");
+        let west = RetryPartition::new("us-west-2");
+        let handler_in = |partition: &RetryPartition| {
+            let mut handler = policy.new_request_policy(None);
+            handler.local.partition = partition.clone();
+            handler
+        };
+
+        let east_handler = handler_in(&east);
+        let (east_handler, _) = east_handler
+            .should_retry(&RetryKind::Error(ErrorKind::TransientError))
+            .expect("should retry");
+        assert!(east_handler
+            .should_retry(&RetryKind::Error(ErrorKind::ServerError))
+            .is_none());
+        assert_eq!(0, policy.retry_quota(&east));
+
+        // The brownout in one partition doesn't prevent retries in the others.
+        let (west_handler, _) = handler_in(&west)
+            .should_retry(&RetryKind::Error(ErrorKind::ServerError))
+            .expect("should retry");
+        assert_eq!(5, policy.retry_quota(&west));
+        assert_eq!(10, policy.retry_quota(&RetryPartition::default()));
+
+        // Successes replenish the quota of their own partition.
+        assert!(west_handler.should_retry(&RetryKind::Unnecessary).is_none());
+        assert_eq!(10, policy.retry_quota(&west));
+        assert_eq!(0, policy.retry_quota(&east));
+    }
+
+    #[test]
+    fn requests_are_assigned_partitions() {
+        struct Region(&'static str);
+
+        let mut request = operation::Request::new(
+            http::Request::builder()
+                .uri("https://dynamodb.eu-west-1.amazonaws.com/")
+                .body(SdkBody::empty())
+                .unwrap(),
+        );
+        let mut unresolved = operation::Request::new(http::Request::new(SdkBody::empty()));
+
+        assert_eq!(
+            RetryPartition::default(),
+            RetryPartitioning::Client.partition(&request)
+        );
+        assert_eq!(
+            RetryPartition::new("dynamodb.eu-west-1.amazonaws.com"),
+            RetryPartitioning::EndpointHost.partition(&request)
+        );
+        assert_eq!(
+            RetryPartition::default(),
+            RetryPartitioning::EndpointHost.partition(&unresolved)
+        );
+        // Once an attempt is dispatched, the operation is partitioned by its resolved endpoint.
+        let dispatched_uri = DispatchedUri::default();
+        unresolved.properties_mut().insert(dispatched_uri.clone());
+        dispatched_uri.record(&"https://dynamodb.eu-west-1.amazonaws.com/".parse().unwrap());
+        assert_eq!(
+            RetryPartition::new("dynamodb.eu-west-1.amazonaws.com"),
+            RetryPartitioning::EndpointHost.partition(&unresolved)
+        );
+
+        let by_region = RetryPartitioning::Custom(|request| {
+            let region = request.properties().get::<Region>()?.0;
+            Some(RetryPartition::new(region))
+        });
+        assert_eq!(RetryPartition::default(), by_region.partition(&request));
+        request.properties_mut().insert(Region("eu-west-1"));
+        assert_eq!(
+            RetryPartition::new("eu-west-1"),
+            by_region.partition(&request)
+        );
+    }
+
+    /// The backoffs before each retry of a request that keeps on failing
+    fn backoffs(conf: Config) -> Vec<Duration> {
+        let mut policy = Standard::new(conf).new_request_policy(None);
+        let mut backoffs = Vec::new();
+        while let Some((next, dur)) = policy.should_retry(&RetryKind::Error(ErrorKind::ServerError))
+        {
+            backoffs.push(dur);
+            policy = next;
+        }
+        backoffs
+    }
+
+    #[test]
+    fn backoff_strategies() {
+        let conf = test_config()
+            .with_max_attempts(5)
+            .with_max_backoff(Duration::from_secs(10));
+        let secs = |secs: &[f64]| -> Vec<Duration> {
+            secs.iter().map(|s| Duration::from_secs_f64(*s)).collect()
+        };
+
+        assert_eq!(backoffs(conf.clone()), secs(&[1.0, 2.0, 4.0, 8.0]));
+        assert_eq!(
+            backoffs(
+                conf.clone()
+                    .with_base(|| 0_f64)
+                    .with_backoff_strategy(BackoffStrategy::EqualJitter)
+            ),
+            secs(&[0.5, 1.0, 2.0, 4.0])
+        );
+        assert_eq!(
+            backoffs(
+                conf.clone()
+                    .with_backoff_strategy(BackoffStrategy::DecorrelatedJitter)
+            ),
+            secs(&[3.0, 9.0, 10.0, 10.0])
+        );
+        assert_eq!(
+            backoffs(conf.with_backoff_strategy(BackoffStrategy::Fixed(Duration::from_secs(15)))),
+            secs(&[15.0, 15.0, 15.0, 15.0])
+        );
+    }
+
+    #[test]
+    fn config_from_retry_config() {
+        let conf = Config::from(
+            RetryConfig::new()
+                .with_max_attempts(4)
+                .with_initial_retry_tokens(20)
+                .with_retry_cost(3)
+                .with_timeout_retry_cost(7)
+                .with_no_retry_increment(2)
+                .with_max_backoff(Duration::from_secs(1))
+                .with_backoff_strategy(BackoffStrategy::Fixed(Duration::from_secs(2))),
+        );
+        assert_eq!(4, conf.max_attempts);
+        assert_eq!(20, conf.initial_retry_tokens);
+        assert_eq!(3, conf.retry_cost);
+        assert_eq!(7, conf.timeout_retry_cost);
+        assert_eq!(2, conf.no_retry_increment);
+        assert_eq!(Duration::from_secs(1), conf.max_backoff);
+        assert_eq!(
+            BackoffStrategy::Fixed(Duration::from_secs(2)),
+            conf.backoff_strategy
+        );
+
+        // Unset settings keep their defaults.
+        let conf = Config::from(RetryConfig::new());
+        assert_eq!(INITIAL_RETRY_TOKENS, conf.initial_retry_tokens);
+        assert_eq!(Duration::from_secs(20), conf.max_backoff);
+        assert_eq!(BackoffStrategy::FullJitter, conf.backoff_strategy);
+    }
 }
@@ -0,0 +1,568 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Request hedging
+//!
+//! Hedging sends a second copy of a slow request without waiting for the first one to fail: if
+//! a request has not completed after a [`HedgeDelay`], the [`HedgeService`] sends a copy of it and
+//! returns whichever response arrives first. The other request is cancelled by being dropped.
+//!
+//! Only idempotent requests that the retry policy can clone are hedged. A hedge only wins if it
+//! succeeds: if it fails first, the original request is still awaited. Every hedge consumes retry
+//! quota from the partition of its request, just like a retry, so that hedging cannot amplify an
+//! outage: once the quota is exhausted, requests are no longer hedged.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+use aws_smithy_http::operation;
+use aws_smithy_http::operation::Operation;
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+use super::{Config, CrossRequestRetryState};
+
+/// The number of recent request latencies kept to compute percentiles.
+const MAX_LATENCY_SAMPLES: usize = 1000;
+/// The number of latencies that must have been recorded before hedging at a percentile.
+const MIN_LATENCY_SAMPLES: usize = 20;
+
+/// When to send a hedged copy of a request that has not completed yet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HedgeDelay {
+    /// Hedge requests after a fixed delay.
+    Fixed(Duration),
+    /// Hedge requests that have taken longer than the given percentile of the latencies of recent
+    /// hedgeable requests, e.g. `95.0` for the 95th percentile.
+    ///
+    /// Requests are not hedged until enough latencies have been recorded.
+    Percentile(f64),
+}
+
+/// Hedging configuration, enabled with [`Config::with_hedging`]
+#[derive(Clone)]
+pub struct HedgeConfig {
+    delay: HedgeDelay,
+    is_idempotent: fn(&operation::Request) -> bool,
+}
+
+impl HedgeConfig {
+    /// Hedge requests that have not completed after `delay`.
+    pub fn after(delay: Duration) -> Self {
+        Self {
+            delay: HedgeDelay::Fixed(delay),
+            is_idempotent: is_safe_method,
+        }
+    }
+
+    /// Hedge requests that have taken longer than `percentile` of the latencies of recent
+    /// requests, e.g. `95.0` for the 95th percentile.
+    ///
+    /// # Panics
+    /// Panics if `percentile` is not greater than `0` and less than or equal to `100`.
+    pub fn at_percentile(percentile: f64) -> Self {
+        assert!(
+            percentile > 0.0 && percentile <= 100.0,
+            "the hedging percentile must be in (0, 100], got {}",
+            percentile
+        );
+        Self {
+            delay: HedgeDelay::Percentile(percentile),
+            is_idempotent: is_safe_method,
+        }
+    }
+
+    /// Override how idempotent requests, which are safe to send twice, are recognized
+    ///
+    /// By default, only `GET` and `HEAD` requests are hedged.
+    pub fn with_idempotency_check(
+        mut self,
+        is_idempotent: fn(&operation::Request) -> bool,
+    ) -> Self {
+        self.is_idempotent = is_idempotent;
+        self
+    }
+
+    /// When requests are hedged.
+    pub fn delay(&self) -> HedgeDelay {
+        self.delay
+    }
+}
+
+impl fmt::Debug for HedgeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HedgeConfig")
+            .field("delay", &self.delay)
+            .finish()
+    }
+}
+
+fn is_safe_method(request: &operation::Request) -> bool {
+    let method = request.http().method();
+    method == http::Method::GET || method == http::Method::HEAD
+}
+
+/// The latencies of recent hedgeable requests, shared by all the requests of a client.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Latencies(Arc<Mutex<VecDeque<Duration>>>);
+
+impl Latencies {
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.0.lock().unwrap();
+        if latencies.len() == MAX_LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// The `percentile` of the recorded latencies, computed with the nearest-rank method, or `None`
+    /// if too few latencies have been recorded.
+    fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut latencies: Vec<Duration> = {
+            let latencies = self.0.lock().unwrap();
+            if latencies.len() < MIN_LATENCY_SAMPLES {
+                return None;
+            }
+            latencies.iter().copied().collect()
+        };
+        latencies.sort_unstable();
+        let rank = (percentile / 100.0 * latencies.len() as f64).ceil() as usize;
+        Some(latencies[rank.clamp(1, latencies.len()) - 1])
+    }
+}
+
+/// The hedging policy of a client, created by a
+/// [`NewRequestPolicy`](super::NewRequestPolicy) configured with [`Config::with_hedging`]
+#[derive(Clone, Debug)]
+pub struct Hedging {
+    hedge_config: HedgeConfig,
+    shared: CrossRequestRetryState,
+    config: Config,
+}
+
+impl Hedging {
+    pub(super) fn new(
+        hedge_config: HedgeConfig,
+        shared: CrossRequestRetryState,
+        config: Config,
+    ) -> Self {
+        Self {
+            hedge_config,
+            shared,
+            config,
+        }
+    }
+
+    fn is_hedgeable(&self, request: &operation::Request) -> bool {
+        (self.hedge_config.is_idempotent)(request)
+    }
+
+    /// How long to wait before hedging a request, if it should be hedged at all.
+    fn delay(&self) -> Option<Duration> {
+        match self.hedge_config.delay {
+            HedgeDelay::Fixed(delay) => Some(delay),
+            HedgeDelay::Percentile(percentile) => self.shared.latencies.percentile(percentile),
+        }
+    }
+
+    /// Consume the retry quota a hedge of `request` costs. Returns `false` if there is not enough.
+    fn acquire_quota(&self, request: &operation::Request) -> bool {
+        let partition = self.config.partitioning.partition(request);
+        self.shared
+            .quota_acquire_cost(&partition, self.config.retry_cost, &self.config)
+            .is_some()
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        self.shared.latencies.record(latency);
+    }
+}
+
+/// A layer that wraps services in a [`HedgeService`]
+#[derive(Clone, Debug)]
+pub struct HedgeLayer<P> {
+    hedging: Option<Hedging>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+    retry_policy: P,
+}
+
+impl<P> HedgeLayer<P> {
+    /// Create a new `HedgeLayer`
+    ///
+    /// Hedges are copies of requests made by the
+    /// [`clone_request`](tower::retry::Policy::clone_request) of `retry_policy`. Requests are not
+    /// hedged if either `hedging` or `sleep_impl` is `None`.
+    pub fn new(
+        hedging: Option<Hedging>,
+        sleep_impl: Option<Arc<dyn AsyncSleep>>,
+        retry_policy: P,
+    ) -> Self {
+        Self {
+            hedging,
+            sleep_impl,
+            retry_policy,
+        }
+    }
+}
+
+impl<S, P: Clone> Layer<S> for HedgeLayer<P> {
+    type Service = HedgeService<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HedgeService {
+            inner,
+            hedging: self.hedging.clone(),
+            sleep_impl: self.sleep_impl.clone(),
+            retry_policy: self.retry_policy.clone(),
+        }
+    }
+}
+
+/// A service that sends a copy of slow idempotent requests, and returns the first successful
+/// response
+#[derive(Clone, Debug)]
+pub struct HedgeService<S, P> {
+    inner: S,
+    hedging: Option<Hedging>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+    retry_policy: P,
+}
+
+impl<S, P, H, R> Service<Operation<H, R>> for HedgeService<S, P>
+where
+    S: Service<Operation<H, R>> + Clone,
+    P: tower::retry::Policy<Operation<H, R>, S::Response, S::Error>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = HedgeFuture<S, Operation<H, R>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        let hedging = match &self.hedging {
+            Some(hedging) if hedging.is_hedgeable(req.request()) => hedging.clone(),
+            _ => {
+                return HedgeFuture {
+                    primary: self.inner.call(req),
+                    hedge: None,
+                    pending: None,
+                    hedging: None,
+                    start: Instant::now(),
+                }
+            }
+        };
+        let pending = match (&self.sleep_impl, hedging.delay()) {
+            (Some(sleep_impl), Some(delay)) => {
+                self.retry_policy
+                    .clone_request(&req)
+                    .map(|request| PendingHedge {
+                        sleep: Some(sleep_impl.sleep(delay)),
+                        service: self.inner.clone(),
+                        request: Some(request),
+                    })
+            }
+            _ => None,
+        };
+        HedgeFuture {
+            primary: self.inner.call(req),
+            hedge: None,
+            pending,
+            hedging: Some(hedging),
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A hedge that has not been sent yet.
+struct PendingHedge<S, Req> {
+    sleep: Option<Sleep>,
+    service: S,
+    request: Option<Req>,
+}
+
+pin_project! {
+    /// A future generated by a [`HedgeService`], that resolves to the response of the request, or
+    /// to the response of its hedge if it succeeds first.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct HedgeFuture<S, Req>
+    where
+        S: Service<Req>,
+    {
+        #[pin]
+        primary: S::Future,
+        #[pin]
+        hedge: Option<S::Future>,
+        pending: Option<PendingHedge<S, Req>>,
+        hedging: Option<Hedging>,
+        start: Instant,
+    }
+}
+
+impl<S, H, R> Future for HedgeFuture<S, Operation<H, R>>
+where
+    S: Service<Operation<H, R>>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Poll::Ready(result) = this.primary.poll(cx) {
+            if let Some(hedging) = this.hedging.as_ref() {
+                hedging.record_latency(this.start.elapsed());
+            }
+            return Poll::Ready(result);
+        }
+        if this.hedge.is_none() {
+            let pending = match this.pending.as_mut() {
+                Some(pending) => pending,
+                None => return Poll::Pending,
+            };
+            if let Some(sleep) = pending.sleep.as_mut() {
+                if Pin::new(sleep).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                pending.sleep = None;
+                let hedging = this.hedging.as_ref().expect("pending hedges have a policy");
+                let request = pending.request.as_ref().expect("the hedge was not sent");
+                if !hedging.acquire_quota(request.request()) {
+                    tracing::debug!("not enough retry quota to hedge the request");
+                    *this.pending = None;
+                    return Poll::Pending;
+                }
+            }
+            match pending.service.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(_)) => {
+                    // The request is still in flight, so its own result is returned instead.
+                    *this.pending = None;
+                    return Poll::Pending;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+            let request = pending.request.take().expect("the hedge is only sent once");
+            tracing::debug!("request is slow, sending a hedged copy");
+            this.hedge.set(Some(pending.service.call(request)));
+            *this.pending = None;
+        }
+        let hedge = this
+            .hedge
+            .as_mut()
+            .as_pin_mut()
+            .expect("the hedge was sent");
+        match hedge.poll(cx) {
+            Poll::Ready(Ok(response)) => {
+                tracing::debug!("hedged request succeeded first");
+                if let Some(hedging) = this.hedging.as_ref() {
+                    hedging.record_latency(this.start.elapsed());
+                }
+                Poll::Ready(Ok(response))
+            }
+            Poll::Ready(Err(_)) => {
+                // The hedge may have failed for the same reason the request is slow, so the
+                // request is given a chance to succeed.
+                tracing::debug!("hedged request failed, waiting for the original request");
+                this.hedge.set(None);
+                Poll::Pending
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::retry::{NewRequestPolicy, RetryPartition, Standard};
+    use aws_smithy_async::assert_elapsed;
+    use aws_smithy_async::rt::sleep::TokioSleep;
+    use aws_smithy_http::body::SdkBody;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    /// A service whose first call responds after 10 seconds and whose later calls respond after
+    /// 1 second. Responds with the index of the call, or fails the later calls if `hedges_fail`.
+    fn slow_first_call(
+        calls: Arc<AtomicUsize>,
+        hedges_fail: bool,
+    ) -> impl Service<Operation<(), ()>, Response = usize, Error = ()> + Clone {
+        tower::service_fn(move |_req: Operation<(), ()>| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                let delay = if call == 0 { 10 } else { 1 };
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                if call > 0 && hedges_fail {
+                    return Err(());
+                }
+                Ok(call)
+            }
+        })
+    }
+
+    /// A retry policy that never retries, and clones requests for hedging.
+    #[derive(Clone)]
+    struct CloneRequests;
+
+    impl tower::retry::Policy<Operation<(), ()>, usize, ()> for CloneRequests {
+        type Future = std::future::Ready<Self>;
+
+        fn retry(
+            &self,
+            _req: &Operation<(), ()>,
+            _result: Result<&usize, &()>,
+        ) -> Option<Self::Future> {
+            None
+        }
+
+        fn clone_request(&self, req: &Operation<(), ()>) -> Option<Operation<(), ()>> {
+            req.try_clone()
+        }
+    }
+
+    fn operation(method: http::Method) -> Operation<(), ()> {
+        let request = http::Request::builder()
+            .method(method)
+            .uri("https://example.com")
+            .body(SdkBody::from("hello"))
+            .unwrap();
+        Operation::new(operation::Request::new(request), ())
+    }
+
+    async fn send(
+        policy: &Standard,
+        req: Operation<(), ()>,
+    ) -> (Result<usize, ()>, usize, tokio::time::Instant) {
+        send_with(policy, req, false).await
+    }
+
+    async fn send_with(
+        policy: &Standard,
+        req: Operation<(), ()>,
+        hedges_fail: bool,
+    ) -> (Result<usize, ()>, usize, tokio::time::Instant) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let layer = HedgeLayer::new(
+            policy.hedging(),
+            Some(Arc::new(TokioSleep::new())),
+            CloneRequests,
+        );
+        let svc = layer.layer(slow_first_call(calls.clone(), hedges_fail));
+        let start = tokio::time::Instant::now();
+        let result = svc.oneshot(req).await;
+        (result, calls.load(Ordering::SeqCst), start)
+    }
+
+    #[tokio::test]
+    async fn slow_requests_are_hedged() {
+        tokio::time::pause();
+        let policy = Standard::new(
+            Config::default().with_hedging(HedgeConfig::after(Duration::from_secs(2))),
+        );
+        let (result, calls, start) = send(&policy, operation(http::Method::GET)).await;
+
+        assert_eq!(Ok(1), result);
+        assert_eq!(2, calls);
+        assert_elapsed!(start, Duration::from_secs(3));
+        assert_eq!(495, policy.retry_quota(&RetryPartition::default()));
+    }
+
+    #[tokio::test]
+    async fn failed_hedges_wait_for_the_request() {
+        tokio::time::pause();
+        let policy = Standard::new(
+            Config::default().with_hedging(HedgeConfig::after(Duration::from_secs(2))),
+        );
+        let (result, calls, start) = send_with(&policy, operation(http::Method::GET), true).await;
+
+        assert_eq!(Ok(0), result);
+        assert_eq!(2, calls);
+        assert_elapsed!(start, Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn latencies_are_recorded_whichever_request_wins() {
+        tokio::time::pause();
+        let policy = Standard::new(
+            Config::default().with_hedging(HedgeConfig::after(Duration::from_secs(2))),
+        );
+        let recorded = || policy.shared_state.latencies.0.lock().unwrap().len();
+
+        let (result, _, _) = send(&policy, operation(http::Method::GET)).await;
+        assert_eq!(Ok(1), result);
+        assert_eq!(1, recorded());
+        let (result, _, _) = send_with(&policy, operation(http::Method::GET), true).await;
+        assert_eq!(Ok(0), result);
+        assert_eq!(2, recorded());
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_requests_are_not_hedged() {
+        tokio::time::pause();
+        let policy = Standard::new(
+            Config::default().with_hedging(HedgeConfig::after(Duration::from_secs(2))),
+        );
+        let (result, calls, start) = send(&policy, operation(http::Method::POST)).await;
+
+        assert_eq!(Ok(0), result);
+        assert_eq!(1, calls);
+        assert_elapsed!(start, Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn hedging_requires_retry_quota() {
+        tokio::time::pause();
+        let policy = Standard::new(
+            Config::default()
+                .with_initial_retry_tokens(4)
+                .with_hedging(HedgeConfig::after(Duration::from_secs(2))),
+        );
+        let (result, calls, start) = send(&policy, operation(http::Method::GET)).await;
+
+        assert_eq!(Ok(0), result);
+        assert_eq!(1, calls);
+        assert_elapsed!(start, Duration::from_secs(10));
+        assert_eq!(4, policy.retry_quota(&RetryPartition::default()));
+    }
+
+    #[tokio::test]
+    async fn hedging_is_disabled_by_default() {
+        tokio::time::pause();
+        let (result, calls, _) = send(&Standard::default(), operation(http::Method::GET)).await;
+
+        assert_eq!(Ok(0), result);
+        assert_eq!(1, calls);
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let latencies = Latencies::default();
+        for millis in 1..MIN_LATENCY_SAMPLES as u64 {
+            latencies.record(Duration::from_millis(millis * 10));
+        }
+        assert_eq!(None, latencies.percentile(50.0));
+
+        latencies.record(Duration::from_millis(200));
+        assert_eq!(Some(Duration::from_millis(100)), latencies.percentile(50.0));
+        assert_eq!(Some(Duration::from_millis(190)), latencies.percentile(95.0));
+        assert_eq!(
+            Some(Duration::from_millis(200)),
+            latencies.percentile(100.0)
+        );
+        assert_eq!(Some(Duration::from_millis(10)), latencies.percentile(0.1));
+
+        for _ in 0..MAX_LATENCY_SAMPLES {
+            latencies.record(Duration::from_secs(1));
+        }
+        assert_eq!(Some(Duration::from_secs(1)), latencies.percentile(50.0));
+    }
+}
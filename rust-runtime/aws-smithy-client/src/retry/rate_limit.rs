@@ -0,0 +1,441 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Client-side rate limiting for the `adaptive` retry mode
+//!
+//! A [`ClientRateLimiter`] is a token bucket whose fill rate follows the CUBIC congestion control
+//! algorithm: it measures the rate at which requests are sent, cuts the allowed rate by
+//! [`BETA`] when a request is throttled, then grows it back along a cubic curve as requests succeed.
+//! The bucket is only enabled once a request has been throttled, so clients that are never
+//! throttled are never slowed down.
+//!
+//! The [`ClientRateLimitService`] delays every attempt, including the initial one, until the
+//! bucket allows it to be sent.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+/// The minimum rate, in requests per second, the bucket is refilled at.
+const MIN_FILL_RATE: f64 = 0.5;
+/// The minimum number of tokens the bucket can hold.
+const MIN_CAPACITY: f64 = 1.0;
+/// The weight of the latest measurement in the measured send rate.
+const SMOOTH: f64 = 0.8;
+/// The factor the sending rate is multiplied by when a request is throttled.
+const BETA: f64 = 0.7;
+/// The scale of the cubic curve the sending rate grows back along.
+const SCALE_CONSTANT: f64 = 0.4;
+
+/// Rate limiter shared by all the requests of a client in `adaptive` retry mode.
+///
+/// Cloning a `ClientRateLimiter` yields a handle to the same limiter.
+#[derive(Clone, Debug)]
+pub struct ClientRateLimiter {
+    start: Instant,
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl ClientRateLimiter {
+    /// Create a new rate limiter. It does not limit anything until a request is throttled.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            state: Arc::new(Mutex::new(RateLimiterState::default())),
+        }
+    }
+
+    /// Update the sending rate after a response was received
+    ///
+    /// `throttled` indicates whether the service asked the client to back off.
+    pub fn update(&self, throttled: bool) {
+        let now = self.now();
+        self.state.lock().unwrap().update(throttled, now);
+    }
+
+    /// Acquire the permission to send a request, returning how long to wait before sending it.
+    pub(crate) fn acquire_permission_delay(&self) -> Option<Duration> {
+        let now = self.now();
+        self.state.lock().unwrap().acquire(now)
+    }
+
+    /// Seconds elapsed since this limiter was created
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+impl Default for ClientRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Token bucket and CUBIC state. All timestamps are in seconds.
+#[derive(Debug, Default)]
+struct RateLimiterState {
+    enabled: bool,
+    fill_rate: f64,
+    max_capacity: f64,
+    /// Tokens available. This goes negative when requests are scheduled ahead of time: each
+    /// request waits until the debt it took on has been refilled.
+    current_capacity: f64,
+    last_timestamp: Option<f64>,
+    measured_tx_rate: f64,
+    last_tx_rate_bucket: f64,
+    request_count: u64,
+    last_max_rate: f64,
+    last_throttle_time: f64,
+    time_window: f64,
+}
+
+impl RateLimiterState {
+    fn acquire(&mut self, now: f64) -> Option<Duration> {
+        if !self.enabled {
+            return None;
+        }
+        self.refill(now);
+        let delay = if self.current_capacity < 1.0 {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.current_capacity) / self.fill_rate,
+            ))
+        } else {
+            None
+        };
+        self.current_capacity -= 1.0;
+        delay
+    }
+
+    fn update(&mut self, throttled: bool, now: f64) {
+        self.update_measured_rate(now);
+        let calculated_rate = if throttled {
+            let rate_to_use = if self.enabled {
+                self.measured_tx_rate.min(self.fill_rate)
+            } else {
+                self.measured_tx_rate
+            };
+            self.last_max_rate = rate_to_use;
+            self.calculate_time_window();
+            self.last_throttle_time = now;
+            self.enabled = true;
+            cubic_throttle(rate_to_use)
+        } else {
+            self.calculate_time_window();
+            self.cubic_success(now)
+        };
+        let new_rate = calculated_rate.min(2.0 * self.measured_tx_rate);
+        self.update_bucket_rate(new_rate, now);
+    }
+
+    fn refill(&mut self, now: f64) {
+        if let Some(last_timestamp) = self.last_timestamp {
+            let fill_amount = (now - last_timestamp) * self.fill_rate;
+            self.current_capacity = self.max_capacity.min(self.current_capacity + fill_amount);
+        }
+        self.last_timestamp = Some(now);
+    }
+
+    fn update_bucket_rate(&mut self, new_rate: f64, now: f64) {
+        self.refill(now);
+        self.fill_rate = new_rate.max(MIN_FILL_RATE);
+        self.max_capacity = new_rate.max(MIN_CAPACITY);
+        self.current_capacity = self.current_capacity.min(self.max_capacity);
+    }
+
+    /// Measure the sending rate over half-second buckets.
+    fn update_measured_rate(&mut self, now: f64) {
+        let time_bucket = (now * 2.0).floor() / 2.0;
+        self.request_count += 1;
+        if time_bucket > self.last_tx_rate_bucket {
+            let current_rate = self.request_count as f64 / (time_bucket - self.last_tx_rate_bucket);
+            self.measured_tx_rate = current_rate * SMOOTH + self.measured_tx_rate * (1.0 - SMOOTH);
+            self.request_count = 0;
+            self.last_tx_rate_bucket = time_bucket;
+        }
+    }
+
+    /// Compute how long it takes the cubic curve to grow back to the rate of the last throttle.
+    fn calculate_time_window(&mut self) {
+        self.time_window = (self.last_max_rate * (1.0 - BETA) / SCALE_CONSTANT).cbrt();
+    }
+
+    fn cubic_success(&self, now: f64) -> f64 {
+        let dt = now - self.last_throttle_time - self.time_window;
+        SCALE_CONSTANT * dt.powi(3) + self.last_max_rate
+    }
+}
+
+fn cubic_throttle(rate: f64) -> f64 {
+    rate * BETA
+}
+
+/// A layer that wraps services in a [`ClientRateLimitService`]
+#[derive(Clone, Debug)]
+pub struct ClientRateLimitLayer {
+    rate_limiter: Option<ClientRateLimiter>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+impl ClientRateLimitLayer {
+    /// Create a new `ClientRateLimitLayer`
+    ///
+    /// Requests are not delayed if either `rate_limiter` or `sleep_impl` is `None`.
+    pub fn new(
+        rate_limiter: Option<ClientRateLimiter>,
+        sleep_impl: Option<Arc<dyn AsyncSleep>>,
+    ) -> Self {
+        Self {
+            rate_limiter,
+            sleep_impl,
+        }
+    }
+}
+
+impl<S> Layer<S> for ClientRateLimitLayer {
+    type Service = ClientRateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientRateLimitService {
+            inner,
+            rate_limiter: self.rate_limiter.clone(),
+            sleep_impl: self.sleep_impl.clone(),
+        }
+    }
+}
+
+/// A service that delays requests until a [`ClientRateLimiter`] allows them to be sent
+#[derive(Clone, Debug)]
+pub struct ClientRateLimitService<S> {
+    inner: S,
+    rate_limiter: Option<ClientRateLimiter>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+impl<S, Req> Service<Req> for ClientRateLimitService<S>
+where
+    S: Service<Req> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ClientRateLimitFuture<S, Req>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let delay = match (&self.rate_limiter, &self.sleep_impl) {
+            (Some(rate_limiter), Some(sleep_impl)) => rate_limiter
+                .acquire_permission_delay()
+                .map(|delay| (delay, sleep_impl)),
+            _ => None,
+        };
+        match delay {
+            Some((delay, sleep_impl)) => {
+                tracing::debug!(delay = ?delay, "client rate limit reached, delaying request");
+                // The ready service is the one that must be called, so it is moved into the future.
+                let clone = self.inner.clone();
+                let inner = std::mem::replace(&mut self.inner, clone);
+                ClientRateLimitFuture {
+                    sleep: Some(sleep_impl.sleep(delay)),
+                    service: Some(inner),
+                    request: Some(req),
+                    future: None,
+                }
+            }
+            None => ClientRateLimitFuture {
+                sleep: None,
+                service: None,
+                request: None,
+                future: Some(self.inner.call(req)),
+            },
+        }
+    }
+}
+
+pin_project! {
+    /// A future generated by a [`ClientRateLimitService`], that sends the request once it is
+    /// allowed to.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct ClientRateLimitFuture<S, Req>
+    where
+        S: Service<Req>,
+    {
+        sleep: Option<Sleep>,
+        service: Option<S>,
+        request: Option<Req>,
+        #[pin]
+        future: Option<S::Future>,
+    }
+}
+
+impl<S, Req> Future for ClientRateLimitFuture<S, Req>
+where
+    S: Service<Req>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(sleep) = this.sleep.as_mut() {
+            if Pin::new(sleep).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            *this.sleep = None;
+        }
+        if this.future.is_none() {
+            let service = this
+                .service
+                .as_mut()
+                .expect("a delayed request keeps its service");
+            match service.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            let request = this
+                .request
+                .take()
+                .expect("a delayed request is only sent once");
+            this.future.set(Some(service.call(request)));
+        }
+        this.future
+            .as_pin_mut()
+            .expect("the request was sent")
+            .poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_smithy_async::rt::sleep::TokioSleep;
+    use tower::ServiceExt;
+
+    /// Simulate sending `rate` requests per second for `seconds`, starting at `start`. Returns the
+    /// time the last request was sent at.
+    fn send_successfully(state: &mut RateLimiterState, start: f64, rate: f64, seconds: f64) -> f64 {
+        let mut now = start;
+        while now < start + seconds {
+            assert_eq!(None, state.acquire(now));
+            state.update(false, now);
+            now += 1.0 / rate;
+        }
+        now
+    }
+
+    #[test]
+    fn disabled_until_throttled() {
+        let mut state = RateLimiterState::default();
+        send_successfully(&mut state, 0.0, 100.0, 5.0);
+        assert!(!state.enabled);
+    }
+
+    #[test]
+    fn throttling_reduces_the_sending_rate() {
+        let mut state = RateLimiterState::default();
+        let now = send_successfully(&mut state, 0.0, 10.0, 5.0);
+        state.update(true, now);
+        assert!(state.enabled);
+        let measured = state.measured_tx_rate;
+        assert!((measured - 10.0).abs() < 2.0, "measured {}", measured);
+        assert_eq!(measured, state.last_max_rate);
+        assert!((state.fill_rate - measured * BETA).abs() < 1e-9);
+
+        // Once the tokens left in the bucket are used up, sending requests faster than the new
+        // rate builds up debt, so each request must wait longer than the previous one.
+        let delays: Vec<Duration> = std::iter::repeat_with(|| state.acquire(now))
+            .flatten()
+            .take(3)
+            .collect();
+        assert!(delays[0] < delays[1] && delays[1] < delays[2]);
+        let spacing = (delays[1] - delays[0]).as_secs_f64();
+        assert!((spacing - 1.0 / state.fill_rate).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sending_rate_recovers_along_a_cubic_curve() {
+        let mut state = RateLimiterState {
+            last_max_rate: 10.0,
+            last_throttle_time: 5.0,
+            ..Default::default()
+        };
+        state.calculate_time_window();
+        assert!((state.time_window - 7.5_f64.cbrt()).abs() < 1e-9);
+
+        // The rate is back to its value at the last throttle after `time_window`...
+        let recovered = state.cubic_success(5.0 + state.time_window);
+        assert!((recovered - 10.0).abs() < 1e-9);
+        // ...starts from `BETA` times that value...
+        assert!((state.cubic_success(5.0) - 10.0 * BETA).abs() < 1e-9);
+        // ...and keeps on growing after that.
+        assert!(state.cubic_success(5.0 + state.time_window + 1.0) > 10.0);
+    }
+
+    #[test]
+    fn new_rate_is_bounded_by_twice_the_measured_rate() {
+        let mut state = RateLimiterState::default();
+        let now = send_successfully(&mut state, 0.0, 2.0, 5.0);
+        state.update(true, now);
+        // Long after the throttle, the cubic curve allows a huge rate, but it is capped.
+        state.update(false, now + 100.0);
+        assert!(state.fill_rate <= 2.0 * state.measured_tx_rate);
+    }
+
+    #[test]
+    fn bucket_never_refills_past_its_capacity() {
+        let mut state = RateLimiterState::default();
+        state.update(true, 0.0);
+        assert_eq!(MIN_FILL_RATE, state.fill_rate);
+        assert_eq!(MIN_CAPACITY, state.max_capacity);
+
+        state.refill(1000.0);
+        assert_eq!(MIN_CAPACITY, state.current_capacity);
+        assert_eq!(None, state.acquire(1000.0));
+        assert_eq!(Some(Duration::from_secs(2)), state.acquire(1000.0));
+    }
+
+    #[tokio::test]
+    async fn service_delays_requests() {
+        tokio::time::pause();
+        let rate_limiter = ClientRateLimiter::new();
+        rate_limiter.update(true);
+        let layer = ClientRateLimitLayer::new(
+            Some(rate_limiter.clone()),
+            Some(Arc::new(TokioSleep::new())),
+        );
+        let svc = layer.layer(tower::service_fn(|req: u32| async move {
+            Ok::<_, std::convert::Infallible>(req)
+        }));
+
+        // The rate was cut to the minimum: one request every two seconds, the first of which is
+        // sent once the bucket holds a token.
+        let now = tokio::time::Instant::now();
+        let (first, second) = tokio::join!(svc.clone().oneshot(1), svc.oneshot(2));
+        assert_eq!((1, 2), (first.unwrap(), second.unwrap()));
+        let elapsed = now.elapsed();
+        assert!(
+            elapsed > Duration::from_millis(3900) && elapsed <= Duration::from_secs(4),
+            "elapsed {:?}",
+            elapsed
+        );
+    }
+
+    #[tokio::test]
+    async fn service_without_rate_limiter_does_not_delay() {
+        let layer = ClientRateLimitLayer::new(None, Some(Arc::new(TokioSleep::new())));
+        let svc = layer.layer(tower::service_fn(|req: u32| async move {
+            Ok::<_, std::convert::Infallible>(req)
+        }));
+        let future = svc.oneshot(1);
+        assert_eq!(1, future.await.unwrap());
+    }
+}
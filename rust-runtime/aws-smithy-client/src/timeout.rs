@@ -65,16 +65,55 @@ impl Settings {
     }
 }
 
-#[derive(Debug)]
-struct RequestTimeoutError {
-    kind: &'static str,
+/// The kind of a [`Client`](crate::Client) timeout
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// The timeout of a whole API call, including all its attempts and the backoff between them
+    ApiCall,
+    /// The timeout of a single attempt of an API call, up to the reception of the response
+    /// headers for streaming responses, or of the whole response otherwise
+    ApiCallAttempt,
+}
+
+impl TimeoutKind {
+    fn description(&self) -> &'static str {
+        match self {
+            TimeoutKind::ApiCall => "API call (all attempts including retries)",
+            TimeoutKind::ApiCallAttempt => "API call (single attempt)",
+        }
+    }
+}
+
+/// The error of an [`SdkError::TimeoutError`] returned when a [`TimeoutService`] times out
+pub struct RequestTimeoutError {
+    kind: TimeoutKind,
     duration: Duration,
 }
 
 impl RequestTimeoutError {
-    pub fn new_boxed(kind: &'static str, duration: Duration) -> Box<Self> {
+    pub(crate) fn new_boxed(kind: TimeoutKind, duration: Duration) -> Box<Self> {
         Box::new(Self { kind, duration })
     }
+
+    /// Which timeout occurred
+    pub fn kind(&self) -> TimeoutKind {
+        self.kind
+    }
+
+    /// The duration of the timeout that occurred
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl std::fmt::Debug for RequestTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTimeoutError")
+            .field("kind", &self.kind.description())
+            .field("duration", &self.duration)
+            .finish()
+    }
 }
 
 impl std::fmt::Display for RequestTimeoutError {
@@ -82,20 +121,35 @@ impl std::fmt::Display for RequestTimeoutError {
         write!(
             f,
             "{} timeout occurred after {:?}",
-            self.kind, self.duration
+            self.kind.description(),
+            self.duration
         )
     }
 }
 
 impl std::error::Error for RequestTimeoutError {}
 
+/// Whether `err` is the timeout of a single attempt, which can be retried
+pub(crate) fn is_attempt_timeout<E>(err: &SdkError<E>) -> bool {
+    match err {
+        SdkError::TimeoutError(err) => matches!(
+            err.downcast_ref::<RequestTimeoutError>(),
+            Some(RequestTimeoutError {
+                kind: TimeoutKind::ApiCallAttempt,
+                ..
+            })
+        ),
+        _ => false,
+    }
+}
+
 #[derive(Clone, Debug)]
 /// A struct containing everything needed to create a new [`TimeoutService`]
 pub struct TimeoutServiceParams {
     /// The duration of timeouts created from these params
     duration: Duration,
     /// The kind of timeouts created from these params
-    kind: &'static str,
+    kind: TimeoutKind,
     /// The AsyncSleep impl that will be used to create time-limited futures
     async_sleep: Arc<dyn AsyncSleep>,
 }
@@ -121,13 +175,13 @@ pub fn generate_timeout_service_params_from_timeout_config(
                 .api_call_timeout()
                 .map(|duration| TimeoutServiceParams {
                     duration,
-                    kind: "API call (all attempts including retries)",
+                    kind: TimeoutKind::ApiCall,
                     async_sleep: async_sleep.clone(),
                 }),
             api_call_attempt: timeout_config.api_call_attempt_timeout().map(|duration| {
                 TimeoutServiceParams {
                     duration,
-                    kind: "API call (single attempt)",
+                    kind: TimeoutKind::ApiCallAttempt,
                     async_sleep: async_sleep.clone(),
                 }
             }),
@@ -200,7 +254,7 @@ pin_project! {
         Timeout {
             #[pin]
             future: Timeout<F, Sleep>,
-            kind: &'static str,
+            kind: TimeoutKind,
             duration: Duration,
         },
         /// A thin wrapper around an inner future that will never time out
@@ -247,7 +301,7 @@ where
         match future.poll(cx) {
             Poll::Ready(Ok(response)) => Poll::Ready(response),
             Poll::Ready(Err(_timeout)) => Poll::Ready(Err(SdkError::TimeoutError(
-                RequestTimeoutError::new_boxed(kind, *duration),
+                RequestTimeoutError::new_boxed(*kind, *duration),
             ))),
             Poll::Pending => Poll::Pending,
         }
@@ -6,13 +6,24 @@
 use crate::test_operation::TestPolicy;
 use aws_smithy_async::rt::sleep::TokioSleep;
 
+use aws_smithy_client::circuit_breaker::{
+    CircuitBreaker, CircuitBreakerConfig, CircuitOpenError, CircuitState,
+};
+use aws_smithy_client::fault_injection::{Fault, FaultInjectingConnection, Trigger};
+use aws_smithy_client::retry::{RetryPartition, RetryPartitioning};
 use aws_smithy_client::test_connection::TestConnection;
+use aws_smithy_client::timeout::{RequestTimeoutError, TimeoutKind};
 use aws_smithy_client::Client;
 use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::operation;
 use aws_smithy_http::operation::Operation;
-use aws_smithy_http::result::SdkError;
+use aws_smithy_http::result::{ConnectorError, SdkError};
+use aws_smithy_types::timeout::TimeoutConfig;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::time::Instant;
 use tower::layer::util::Identity;
@@ -78,6 +89,7 @@ mod test_operation {
         fn classify(&self, err: Result<&T, &SdkError<E>>) -> RetryKind {
             let kind = match err {
                 Err(SdkError::ServiceError { err, .. }) => err.retryable_error_kind(),
+                Err(SdkError::ConstructionFailure(_)) => return RetryKind::UnretryableFailure,
                 Ok(_) => return RetryKind::Unnecessary,
                 _ => panic!("test handler only handles modeled errors got: {:?}", err),
             };
@@ -181,3 +193,245 @@ fn assert_time_passed(initial: Instant, passed: Duration) {
         assert_eq!(delta, passed)
     }
 }
+
+/// A connection that hangs for `delays[i]` before answering the i-th request with `status`
+#[derive(Clone)]
+struct SlowConnection {
+    delays: Arc<Vec<Duration>>,
+    status: u16,
+    requests: Arc<AtomicUsize>,
+}
+
+impl SlowConnection {
+    fn new(delays: Vec<Duration>, status: u16) -> Self {
+        Self {
+            delays: Arc::new(delays),
+            status,
+            requests: Default::default(),
+        }
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl tower::Service<http::Request<SdkBody>> for SlowConnection {
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: http::Request<SdkBody>) -> Self::Future {
+        let attempt = self.requests.fetch_add(1, Ordering::SeqCst);
+        let delay = self.delays.get(attempt).cloned().unwrap_or_default();
+        let status = self.status;
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            Ok(http::Response::builder()
+                .status(status)
+                .body(SdkBody::empty())
+                .unwrap())
+        })
+    }
+}
+
+fn timeout_client(
+    conn: SlowConnection,
+    timeout_config: TimeoutConfig,
+) -> Client<SlowConnection, Identity> {
+    let retry_config = aws_smithy_client::retry::Config::default()
+        .with_max_attempts(3)
+        .with_base(|| 1_f64);
+    Client::<SlowConnection, Identity>::new(conn)
+        .with_retry_config(retry_config)
+        .with_timeout_config(timeout_config)
+        .with_sleep_impl(Arc::new(TokioSleep::new()))
+}
+
+fn timeout_kind<E>(err: &SdkError<E>) -> TimeoutKind {
+    match err {
+        SdkError::TimeoutError(err) => err
+            .downcast_ref::<RequestTimeoutError>()
+            .expect("a client timeout")
+            .kind(),
+        _ => panic!("expected a timeout"),
+    }
+}
+
+#[tokio::test]
+async fn attempts_that_time_out_are_retried() {
+    let conn = SlowConnection::new(vec![Duration::from_secs(10)], 200);
+    let client = timeout_client(
+        conn.clone(),
+        TimeoutConfig::new().with_api_call_attempt_timeout(Some(Duration::from_secs(1))),
+    );
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let resp = client
+        .call(test_operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(resp, "Hello!");
+    // 1s for the attempt that timed out, then 1s of backoff
+    assert_time_passed(initial, Duration::from_secs(2));
+    assert_eq!(conn.requests(), 2);
+}
+
+#[tokio::test]
+async fn attempt_timeout_is_returned_when_all_attempts_time_out() {
+    let conn = SlowConnection::new(vec![Duration::from_secs(10); 3], 200);
+    let client = timeout_client(
+        conn.clone(),
+        TimeoutConfig::new().with_api_call_attempt_timeout(Some(Duration::from_secs(1))),
+    );
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let err = client
+        .call(test_operation())
+        .await
+        .expect_err("all attempts time out");
+    assert_eq!(timeout_kind(&err), TimeoutKind::ApiCallAttempt);
+    assert_eq!(
+        format!("{}", err),
+        "request has timed out: API call (single attempt) timeout occurred after 1s"
+    );
+    // 3 attempts of 1s, with 1s then 2s of backoff
+    assert_time_passed(initial, Duration::from_secs(6));
+    assert_eq!(conn.requests(), 3);
+}
+
+#[tokio::test]
+async fn api_call_timeout_includes_retries_and_backoff() {
+    let conn = SlowConnection::new(vec![], 500);
+    let client = timeout_client(
+        conn.clone(),
+        TimeoutConfig::new()
+            .with_api_call_timeout(Some(Duration::from_millis(2500)))
+            .with_api_call_attempt_timeout(Some(Duration::from_secs(1))),
+    );
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let err = client
+        .call(test_operation())
+        .await
+        .expect_err("the API call times out");
+    assert_eq!(timeout_kind(&err), TimeoutKind::ApiCall);
+    // the timeout fires during the 2s backoff after the second attempt
+    assert_time_passed(initial, Duration::from_millis(2500));
+    assert_eq!(conn.requests(), 2);
+}
+
+#[tokio::test]
+async fn injected_throttling_errors_are_retried() {
+    let conn = FaultInjectingConnection::new(SlowConnection::new(vec![], 200)).with_fault(
+        Fault::Response {
+            status: 429,
+            retry_after: None,
+        },
+        Trigger::FirstRequests(2),
+    );
+    let retry_config = aws_smithy_client::retry::Config::default().with_base(|| 1_f64);
+    let client = Client::<FaultInjectingConnection<SlowConnection>, Identity>::new(conn.clone())
+        .with_retry_config(retry_config)
+        .with_sleep_impl(Arc::new(TokioSleep::new()));
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let resp = client
+        .call(test_operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(resp, "Hello!");
+    // 1s then 2s of backoff
+    assert_time_passed(initial, Duration::from_secs(3));
+    assert_eq!(conn.requests(), 3);
+    assert_eq!(conn.injected_faults().len(), 2);
+}
+
+#[tokio::test]
+async fn injected_hangs_time_out_and_are_retried() {
+    let conn = FaultInjectingConnection::new(SlowConnection::new(vec![], 200))
+        .with_fault(Fault::Hang, Trigger::Requests(vec![0]));
+    let retry_config = aws_smithy_client::retry::Config::default().with_base(|| 1_f64);
+    let client = Client::<FaultInjectingConnection<SlowConnection>, Identity>::new(conn.clone())
+        .with_retry_config(retry_config)
+        .with_timeout_config(
+            TimeoutConfig::new().with_api_call_attempt_timeout(Some(Duration::from_secs(1))),
+        )
+        .with_sleep_impl(Arc::new(TokioSleep::new()));
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let resp = client
+        .call(test_operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(resp, "Hello!");
+    // 1s for the attempt that timed out, then 1s of backoff
+    assert_time_passed(initial, Duration::from_secs(2));
+    assert_eq!(conn.requests(), 2);
+}
+
+#[tokio::test]
+async fn requests_are_partitioned_by_the_endpoint_resolved_by_the_middleware() {
+    let conn = SlowConnection::new(vec![], 500);
+    let retry_policy = aws_smithy_client::retry::Standard::new(
+        aws_smithy_client::retry::Config::default()
+            .with_max_attempts(2)
+            .with_base(|| 0_f64)
+            .with_retry_partitioning(RetryPartitioning::EndpointHost),
+    );
+    let circuit_breaker =
+        CircuitBreaker::new(CircuitBreakerConfig::default().with_failure_threshold(2));
+    let client = aws_smithy_client::Builder::new()
+        .connector(conn.clone())
+        .middleware_fn(|mut req: operation::Request| {
+            *req.http_mut().uri_mut() = http::Uri::from_static("https://resolved.example.com/");
+            req
+        })
+        .retry_policy(retry_policy.clone())
+        .circuit_breaker(Some(circuit_breaker.clone()))
+        .sleep_impl(Some(Arc::new(TokioSleep::new())))
+        .build();
+    let unresolved_operation = || {
+        let req = operation::Request::new(
+            http::Request::builder()
+                .uri("/")
+                .body(SdkBody::from("request body"))
+                .unwrap(),
+        );
+        Operation::new(req, test_operation::TestOperationParser).with_retry_policy(TestPolicy)
+    };
+    let resolved = RetryPartition::new("resolved.example.com");
+
+    let err = client
+        .call(unresolved_operation())
+        .await
+        .expect_err("all responses failed");
+    assert!(matches!(err, SdkError::ServiceError { .. }), "{:?}", err);
+    assert_eq!(conn.requests(), 2);
+    // The retry consumed the quota of the resolved endpoint, and its failures opened its circuit.
+    assert!(
+        retry_policy.retry_quota(&resolved) < retry_policy.retry_quota(&RetryPartition::default())
+    );
+    assert_eq!(circuit_breaker.state(&resolved), CircuitState::Open);
+    assert_eq!(
+        circuit_breaker.state(&RetryPartition::default()),
+        CircuitState::Closed
+    );
+
+    let err = client
+        .call(unresolved_operation())
+        .await
+        .expect_err("the circuit is open");
+    match err {
+        SdkError::ConstructionFailure(err) => {
+            let err = err.downcast_ref::<CircuitOpenError>().unwrap();
+            assert_eq!(err.partition(), &resolved);
+        }
+        err => panic!("expected the request to fail fast, got {:?}", err),
+    }
+    assert_eq!(conn.requests(), 2);
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use aws_smithy_http_server::body::boxed;
+use aws_smithy_http_server::routing::request_spec::{
+    Match, PathAndQuerySpec, PathSegment, PathSpec, QuerySegment, QuerySpec, RequestSpec, UriSpec,
+};
+use aws_smithy_http_server::Router;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures_util::FutureExt;
+use http::{Method, Request, Response};
+use std::convert::Infallible;
+use tower::util::BoxCloneService;
+use tower::Service;
+
+/// Builds the request specs of a service with `resources` resources, each of them with a
+/// create/read/update/delete/list lifecycle, for a total of `5 * resources` operations.
+fn request_specs(resources: usize) -> Vec<RequestSpec> {
+    let spec = |method: Method, path_segments: Vec<PathSegment>, query_segments: Vec<QuerySegment>| {
+        RequestSpec::new(
+            method,
+            UriSpec::new(PathAndQuerySpec::new(
+                PathSpec::from_vector_unchecked(path_segments),
+                QuerySpec::from_vector_unchecked(query_segments),
+            )),
+        )
+    };
+
+    (0..resources)
+        .flat_map(|i| {
+            let collection = PathSegment::Literal(format!("resource{}", i));
+            vec![
+                spec(Method::POST, vec![collection.clone()], Vec::new()),
+                spec(Method::GET, vec![collection.clone(), PathSegment::Label], Vec::new()),
+                spec(Method::PUT, vec![collection.clone(), PathSegment::Label], Vec::new()),
+                spec(Method::DELETE, vec![collection.clone(), PathSegment::Label], Vec::new()),
+                spec(
+                    Method::GET,
+                    vec![collection, PathSegment::Greedy],
+                    vec![QuerySegment::Key(String::from("list"))],
+                ),
+            ]
+        })
+        .collect()
+}
+
+fn router(request_specs: Vec<RequestSpec>) -> Router<()> {
+    Router::from_box_clone_service_iter(request_specs.into_iter().map(|request_spec| {
+        let svc = tower::service_fn(|_req: Request<()>| async {
+            Ok::<_, Infallible>(Response::new(boxed(http_body::Empty::new())))
+        });
+        (BoxCloneService::new(svc), request_spec)
+    }))
+}
+
+/// The strategy the `Router` used before routing through a prefix tree: test each spec in turn,
+/// in order of decreasing rank.
+fn linear_scan(request_specs: &[RequestSpec], req: &Request<()>) -> Option<usize> {
+    request_specs
+        .iter()
+        .position(|request_spec| request_spec.matches(req) == Match::Yes)
+}
+
+fn bench_routing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("routing");
+    for resources in [10, 60, 200] {
+        let operations = resources * 5;
+        // Requests are routed to an operation of the last resource, which is the worst case for a
+        // linear scan.
+        let last = resources - 1;
+        let req = || {
+            Request::builder()
+                .method(Method::DELETE)
+                .uri(format!("/resource{}/some-id", last))
+                .body(())
+                .unwrap()
+        };
+
+        group.bench_with_input(
+            BenchmarkId::new("linear_scan", operations),
+            &request_specs(resources),
+            |b, specs| b.iter(|| assert!(linear_scan(specs, &req()).is_some())),
+        );
+
+        let mut router = router(request_specs(resources));
+        group.bench_function(BenchmarkId::new("router", operations), |b| {
+            b.iter(|| {
+                let res = router.call(req()).now_or_never().unwrap().unwrap();
+                assert!(res.status().is_success());
+                res
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_routing);
+criterion_main!(benches);
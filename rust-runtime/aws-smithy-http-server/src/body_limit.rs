@@ -0,0 +1,306 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Request body size limits.
+//!
+//! Generated operation handlers buffer the whole request body before deserializing it. Applying a
+//! [`RequestBodyLimitLayer`] to a [`Router`](crate::Router) bounds how much of it is buffered:
+//!
+//! ```rust,ignore
+//! use aws_smithy_http_server::body::Body;
+//! use aws_smithy_http_server::body_limit::{Limited, RequestBodyLimitLayer};
+//! use aws_smithy_http_server::Router;
+//!
+//! let router: Router<Limited<Body>> = operation_registry.into();
+//! let app: Router<Body> = router.layer(RequestBodyLimitLayer::new(1024 * 1024));
+//! ```
+//!
+//! Requests whose body is larger than the limit are rejected with a `413 Payload Too Large`
+//! [`PayloadTooLarge`] rejection as soon as they are found to be, that is, before any of the body
+//! is read if they declare a `Content-Length`, and as soon as the limit is crossed otherwise.
+//!
+//! Operations with a `@streaming` payload are exempt: the limit is lifted when the body is
+//! converted into the member's [`ByteStream`].
+
+use crate::body::HttpBody;
+use crate::rejection::{Http, PayloadTooLarge, SmithyRejection};
+use crate::BoxError;
+use aws_smithy_http::byte_stream::ByteStream;
+use bytes::Buf;
+use http::{HeaderMap, Request};
+use std::error::Error as StdError;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// [`Layer`] that applies the [`RequestBodyLimit`] middleware.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestBodyLimitLayer {
+    limit: usize,
+}
+
+impl RequestBodyLimitLayer {
+    /// Creates a new `RequestBodyLimitLayer` that limits request bodies to `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+impl<S> Layer<S> for RequestBodyLimitLayer {
+    type Service = RequestBodyLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestBodyLimit {
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Middleware that wraps request bodies in a [`Limited`] body.
+#[derive(Debug, Clone)]
+pub struct RequestBodyLimit<S> {
+    inner: S,
+    limit: usize,
+}
+
+impl<S, B> Service<Request<B>> for RequestBodyLimit<S>
+where
+    S: Service<Request<Limited<B>>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let content_length = content_length(req.headers());
+        let limit = self.limit;
+        self.inner
+            .call(req.map(|body| Limited::new(body, limit, content_length)))
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(http::header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+pin_project_lite::pin_project! {
+    /// A body that fails with [`LimitedBodyError::LengthLimitExceeded`] if more than a given number
+    /// of bytes are read from it.
+    #[derive(Debug)]
+    pub struct Limited<B> {
+        #[pin]
+        inner: B,
+        limit: usize,
+        remaining: usize,
+        content_length: Option<u64>,
+    }
+}
+
+impl<B> Limited<B> {
+    /// Creates a new `Limited` body, that can yield at most `limit` bytes from `inner`.
+    ///
+    /// If `content_length` is known to be larger than `limit`, the body fails right away.
+    pub fn new(inner: B, limit: usize, content_length: Option<u64>) -> Self {
+        Self {
+            inner,
+            limit,
+            remaining: limit,
+            content_length,
+        }
+    }
+
+    /// Returns the wrapped body, lifting the limit.
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> HttpBody for Limited<B>
+where
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    type Data = B::Data;
+    type Error = LimitedBodyError;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        if matches!(this.content_length, Some(content_length) if *content_length > *this.limit as u64) {
+            return Poll::Ready(Some(Err(LimitedBodyError::LengthLimitExceeded(*this.limit))));
+        }
+
+        match this.inner.poll_data(cx) {
+            Poll::Ready(Some(Ok(data))) => {
+                let len = data.remaining();
+                if len > *this.remaining {
+                    *this.remaining = 0;
+                    Poll::Ready(Some(Err(LimitedBodyError::LengthLimitExceeded(*this.limit))))
+                } else {
+                    *this.remaining -= len;
+                    Poll::Ready(Some(Ok(data)))
+                }
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(LimitedBodyError::Body(err.into())))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project()
+            .inner
+            .poll_trailers(cx)
+            .map_err(|err| LimitedBodyError::Body(err.into()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// `@streaming` payloads are converted into a [`ByteStream`] without a limit, so that they can be
+/// consumed incrementally regardless of their size.
+impl<B> From<Limited<B>> for ByteStream
+where
+    B: Into<ByteStream>,
+{
+    fn from(body: Limited<B>) -> Self {
+        body.into_inner().into()
+    }
+}
+
+/// Errors that can happen when reading a [`Limited`] body.
+#[derive(Debug)]
+pub enum LimitedBodyError {
+    /// The body is larger than the limit, in bytes.
+    LengthLimitExceeded(usize),
+    /// The wrapped body failed.
+    Body(BoxError),
+}
+
+impl fmt::Display for LimitedBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitedBodyError::LengthLimitExceeded(limit) => {
+                write!(f, "request body is larger than the limit of {} bytes", limit)
+            }
+            LimitedBodyError::Body(_) => write!(f, "failed to read request body"),
+        }
+    }
+}
+
+impl StdError for LimitedBodyError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            LimitedBodyError::LengthLimitExceeded(_) => None,
+            LimitedBodyError::Body(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<LimitedBodyError> for SmithyRejection {
+    fn from(err: LimitedBodyError) -> Self {
+        match err {
+            LimitedBodyError::LengthLimitExceeded(_) => SmithyRejection::PayloadTooLarge(PayloadTooLarge),
+            LimitedBodyError::Body(err) => SmithyRejection::Http(Http::from_err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use bytes::Bytes;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    async fn collect(body: Limited<Body>) -> Result<Bytes, LimitedBodyError> {
+        hyper::body::to_bytes(body).await
+    }
+
+    fn chunked(chunks: &'static [&'static str]) -> Body {
+        Body::wrap_stream(futures_util::stream::iter(
+            chunks
+                .iter()
+                .map(|chunk| Ok::<_, Infallible>(Bytes::from_static(chunk.as_bytes()))),
+        ))
+    }
+
+    #[tokio::test]
+    async fn bodies_within_the_limit_are_read() {
+        let body = Limited::new(Body::from("hello"), 5, Some(5));
+        assert_eq!(Bytes::from("hello"), collect(body).await.unwrap());
+
+        let body = Limited::new(chunked(&["hel", "lo"]), 5, None);
+        assert_eq!(Bytes::from("hello"), collect(body).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn declared_content_length_is_checked_before_reading() {
+        // The body itself is within the limit, but it is not read if it declares it is not.
+        let body = Limited::new(Body::from("hi"), 5, Some(6));
+        assert!(matches!(
+            collect(body).await,
+            Err(LimitedBodyError::LengthLimitExceeded(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_are_checked_incrementally() {
+        let body = Limited::new(chunked(&["hel", "lo", " world"]), 5, None);
+        assert!(matches!(
+            collect(body).await,
+            Err(LimitedBodyError::LengthLimitExceeded(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn streaming_payloads_are_exempt() {
+        let body = Limited::new(Body::from("hello world"), 5, Some(11));
+        let stream: ByteStream = body.into();
+        assert_eq!(Bytes::from("hello world"), stream.collect().await.unwrap().into_bytes());
+    }
+
+    #[test]
+    fn length_limit_exceeded_is_a_413() {
+        let rejection = SmithyRejection::from(LimitedBodyError::LengthLimitExceeded(5));
+        assert_eq!(http::StatusCode::PAYLOAD_TOO_LARGE, rejection.status_code());
+    }
+
+    #[tokio::test]
+    async fn layer_passes_content_length() {
+        let svc = tower::service_fn(|req: Request<Limited<Body>>| async move {
+            Ok::<_, Infallible>(collect(req.into_body()).await.is_ok())
+        });
+        let svc = RequestBodyLimitLayer::new(3).layer(svc);
+
+        let req = |content_length: &str| {
+            Request::builder()
+                .header(http::header::CONTENT_LENGTH, content_length)
+                .body(Body::from("hi"))
+                .unwrap()
+        };
+        assert!(svc.clone().oneshot(req("2")).await.unwrap());
+        assert!(!svc.oneshot(req("4")).await.unwrap());
+    }
+
+    #[test]
+    fn layers_a_router() {
+        let _router: crate::Router<Body> =
+            crate::Router::<Limited<Body>>::default().layer(RequestBodyLimitLayer::new(1));
+    }
+}
@@ -0,0 +1,479 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Event streams for operations with a `@streaming` union member.
+//!
+//! Incoming `application/vnd.amazon.eventstream` request bodies are decoded into modeled events
+//! with a [`Receiver`], and outgoing events are encoded into a streamed response body with an
+//! [`EventStreamBody`].
+
+use crate::body::HttpBody;
+use crate::BoxError;
+use aws_smithy_eventstream::error::Error as EventStreamError;
+use aws_smithy_eventstream::frame::{
+    DecodedFrame, Header, HeaderValue, MarshallMessage, Message, MessageFrameDecoder, UnmarshallMessage,
+    UnmarshalledMessage,
+};
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::Stream;
+use http::HeaderMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Header that clients add to the envelope of the messages they sign.
+const CHUNK_SIGNATURE: &str = ":chunk-signature";
+
+/// Errors that can happen when receiving messages out of an event stream.
+#[derive(Debug)]
+pub enum ReceiveError<E> {
+    /// The request body failed.
+    Body(BoxError),
+    /// The request body could not be decoded into message frames.
+    Decode(EventStreamError),
+    /// The request body ended in the middle of a message frame.
+    UnexpectedEndOfStream,
+    /// A message could not be unmarshalled into a modeled event.
+    Unmarshall(EventStreamError),
+    /// The client sent a modeled error.
+    Modeled(E),
+}
+
+impl<E> fmt::Display for ReceiveError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiveError::Body(_) => write!(f, "failed to read event stream body"),
+            ReceiveError::Decode(_) => write!(f, "failed to decode event stream message frame"),
+            ReceiveError::UnexpectedEndOfStream => write!(f, "unexpected end of event stream"),
+            ReceiveError::Unmarshall(_) => write!(f, "failed to unmarshall event stream message"),
+            ReceiveError::Modeled(_) => write!(f, "client sent an error event"),
+        }
+    }
+}
+
+impl<E> StdError for ReceiveError<E>
+where
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ReceiveError::Body(err) => Some(err.as_ref()),
+            ReceiveError::Decode(err) | ReceiveError::Unmarshall(err) => Some(err),
+            ReceiveError::UnexpectedEndOfStream => None,
+            ReceiveError::Modeled(err) => Some(err),
+        }
+    }
+}
+
+/// Receives modeled events out of an event stream request body.
+///
+/// Messages signed by the client are taken out of their signature envelope, but their signature is
+/// not verified. An empty signed message marks the end of the stream.
+pub struct Receiver<T, E, B> {
+    unmarshaller: Box<dyn UnmarshallMessage<Output = T, Error = E> + Send + Sync>,
+    decoder: MessageFrameDecoder,
+    buffer: BytesMut,
+    body: Pin<Box<B>>,
+    body_ended: bool,
+    stream_ended: bool,
+}
+
+impl<T, E, B> fmt::Debug for Receiver<T, E, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("unmarshaller", &self.unmarshaller)
+            .field("decoder", &self.decoder)
+            .field("buffered", &self.buffer.len())
+            .field("body_ended", &self.body_ended)
+            .field("stream_ended", &self.stream_ended)
+            .finish()
+    }
+}
+
+impl<T, E, B> Receiver<T, E, B>
+where
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    /// Creates a new `Receiver` with the given message unmarshaller and request body.
+    pub fn new(unmarshaller: impl UnmarshallMessage<Output = T, Error = E> + Send + Sync + 'static, body: B) -> Self {
+        Receiver {
+            unmarshaller: Box::new(unmarshaller),
+            decoder: MessageFrameDecoder::new(),
+            buffer: BytesMut::new(),
+            body: Box::pin(body),
+            body_ended: false,
+            stream_ended: false,
+        }
+    }
+
+    /// Decodes the next message frame, reading more of the body as needed.
+    async fn next_message(&mut self) -> Result<Option<Message>, ReceiveError<E>> {
+        loop {
+            if let DecodedFrame::Complete(message) = self
+                .decoder
+                .decode_frame(&mut self.buffer)
+                .map_err(ReceiveError::Decode)?
+            {
+                return Ok(Some(message));
+            }
+            if self.body_ended {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(ReceiveError::UnexpectedEndOfStream)
+                };
+            }
+
+            match self.body.as_mut().data().await {
+                Some(Ok(mut chunk)) => {
+                    while chunk.has_remaining() {
+                        let bytes = chunk.chunk();
+                        let len = bytes.len();
+                        self.buffer.extend_from_slice(bytes);
+                        chunk.advance(len);
+                    }
+                }
+                Some(Err(err)) => return Err(ReceiveError::Body(err.into())),
+                None => self.body_ended = true,
+            }
+        }
+    }
+
+    /// Asynchronously receives the next event from the stream. Returns `Ok(None)` once the stream
+    /// has ended.
+    pub async fn recv(&mut self) -> Result<Option<T>, ReceiveError<E>> {
+        if self.stream_ended {
+            return Ok(None);
+        }
+
+        let mut message = match self.next_message().await? {
+            Some(message) => message,
+            None => {
+                self.stream_ended = true;
+                return Ok(None);
+            }
+        };
+        if message
+            .headers()
+            .iter()
+            .any(|header| header.name().as_str() == CHUNK_SIGNATURE)
+        {
+            if message.payload().is_empty() {
+                self.stream_ended = true;
+                return Ok(None);
+            }
+            message = Message::read_from(message.payload().clone()).map_err(ReceiveError::Decode)?;
+        }
+
+        match self.unmarshaller.unmarshall(&message) {
+            Ok(UnmarshalledMessage::Event(event)) => Ok(Some(event)),
+            Ok(UnmarshalledMessage::Error(err)) => Err(ReceiveError::Modeled(err)),
+            Err(err) => Err(ReceiveError::Unmarshall(err)),
+        }
+    }
+}
+
+/// A response body that streams modeled events, encoded as event stream message frames.
+///
+/// Events are pulled from the stream only as the body is polled, that is, as fast as the
+/// connection to the client can send them, so a slow client applies backpressure to the producer
+/// of the events.
+///
+/// When the stream yields a modeled error, it is sent as an `exception` message and the body ends.
+/// If an event or error can't be marshalled, an `error` message with an `InternalFailureException`
+/// error code is sent instead and the body ends.
+pub struct EventStreamBody<T, E> {
+    marshaller: Box<dyn MarshallMessage<Input = T> + Send + Sync>,
+    error_marshaller: Box<dyn MarshallMessage<Input = E> + Send + Sync>,
+    stream: Pin<Box<dyn Stream<Item = Result<T, E>> + Send>>,
+    ended: bool,
+}
+
+impl<T, E> fmt::Debug for EventStreamBody<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStreamBody")
+            .field("marshaller", &self.marshaller)
+            .field("error_marshaller", &self.error_marshaller)
+            .field("ended", &self.ended)
+            .finish()
+    }
+}
+
+impl<T, E> EventStreamBody<T, E> {
+    /// Creates a new `EventStreamBody` that marshalls the events of `stream` with `marshaller`, and
+    /// its modeled errors with `error_marshaller`.
+    pub fn new<S>(
+        marshaller: impl MarshallMessage<Input = T> + Send + Sync + 'static,
+        error_marshaller: impl MarshallMessage<Input = E> + Send + Sync + 'static,
+        stream: S,
+    ) -> Self
+    where
+        S: Stream<Item = Result<T, E>> + Send + 'static,
+    {
+        EventStreamBody {
+            marshaller: Box::new(marshaller),
+            error_marshaller: Box::new(error_marshaller),
+            stream: Box::pin(stream),
+            ended: false,
+        }
+    }
+}
+
+/// Builds the frame sent to the client when a message can't be marshalled.
+fn internal_failure_message(err: &EventStreamError) -> Message {
+    Message::new(Bytes::new())
+        .add_header(Header::new(":message-type", HeaderValue::String("error".into())))
+        .add_header(Header::new(
+            ":error-code",
+            HeaderValue::String("InternalFailureException".into()),
+        ))
+        .add_header(Header::new(
+            ":error-message",
+            HeaderValue::String(err.to_string().into()),
+        ))
+}
+
+fn encode(message: &Message) -> Result<Bytes, BoxError> {
+    let mut buffer = Vec::new();
+    message.write_to(&mut buffer)?;
+    Ok(Bytes::from(buffer))
+}
+
+impl<T, E> HttpBody for EventStreamBody<T, E> {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+
+        let this = &mut *self;
+        let message = match futures_util::ready!(this.stream.as_mut().poll_next(cx)) {
+            Some(Ok(event)) => this.marshaller.marshall(event),
+            Some(Err(err)) => {
+                this.ended = true;
+                this.error_marshaller.marshall(err)
+            }
+            None => {
+                this.ended = true;
+                return Poll::Ready(None);
+            }
+        };
+        let message = message.unwrap_or_else(|err| {
+            tracing::error!(error = %err, "failed to marshall event stream message");
+            this.ended = true;
+            internal_failure_message(&err)
+        });
+        Poll::Ready(Some(encode(&message)))
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.ended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use futures_util::stream;
+    use std::convert::Infallible;
+
+    #[derive(Debug, PartialEq)]
+    struct TestEvent(String);
+
+    #[derive(Debug, PartialEq)]
+    struct TestError(String);
+
+    #[derive(Debug)]
+    struct Unmarshaller;
+
+    impl UnmarshallMessage for Unmarshaller {
+        type Output = TestEvent;
+        type Error = TestError;
+
+        fn unmarshall(
+            &self,
+            message: &Message,
+        ) -> Result<UnmarshalledMessage<Self::Output, Self::Error>, EventStreamError> {
+            let payload = std::str::from_utf8(&message.payload()[..])
+                .map_err(|_| EventStreamError::Unmarshalling("payload is not UTF-8".into()))?
+                .to_owned();
+            match message.headers().first().map(|header| header.name().as_str()) {
+                Some(":exception-type") => Ok(UnmarshalledMessage::Error(TestError(payload))),
+                _ => Ok(UnmarshalledMessage::Event(TestEvent(payload))),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Marshaller;
+
+    impl MarshallMessage for Marshaller {
+        type Input = TestEvent;
+
+        fn marshall(&self, input: Self::Input) -> Result<Message, EventStreamError> {
+            if input.0.is_empty() {
+                return Err(EventStreamError::Marshalling("empty event".into()));
+            }
+            Ok(Message::new(input.0).add_header(Header::new(":message-type", HeaderValue::String("event".into()))))
+        }
+    }
+
+    #[derive(Debug)]
+    struct ErrorMarshaller;
+
+    impl MarshallMessage for ErrorMarshaller {
+        type Input = TestError;
+
+        fn marshall(&self, input: Self::Input) -> Result<Message, EventStreamError> {
+            Ok(Message::new(input.0).add_header(Header::new(":message-type", HeaderValue::String("exception".into()))))
+        }
+    }
+
+    fn encode_event(payload: &'static str) -> Bytes {
+        encode(&Message::new(payload)).unwrap()
+    }
+
+    fn sign(message: Bytes) -> Bytes {
+        encode(&Message::new(message).add_header(Header::new(CHUNK_SIGNATURE, HeaderValue::ByteArray(Bytes::new()))))
+            .unwrap()
+    }
+
+    fn chunked(chunks: Vec<Bytes>) -> Body {
+        Body::wrap_stream(stream::iter(chunks.into_iter().map(Ok::<_, Infallible>)))
+    }
+
+    #[tokio::test]
+    async fn receives_events() {
+        let body = chunked(vec![encode_event("one"), encode_event("two")]);
+        let mut receiver = Receiver::new(Unmarshaller, body);
+
+        assert_eq!(Some(TestEvent("one".into())), receiver.recv().await.unwrap());
+        assert_eq!(Some(TestEvent("two".into())), receiver.recv().await.unwrap());
+        assert_eq!(None, receiver.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn receives_events_split_across_chunks() {
+        let mut both = encode_event("one").to_vec();
+        both.extend_from_slice(&encode_event("two"));
+        let chunks = both.chunks(3).map(Bytes::copy_from_slice).collect();
+        let mut receiver = Receiver::new(Unmarshaller, chunked(chunks));
+
+        assert_eq!(Some(TestEvent("one".into())), receiver.recv().await.unwrap());
+        assert_eq!(Some(TestEvent("two".into())), receiver.recv().await.unwrap());
+        assert_eq!(None, receiver.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn receives_signed_events_until_the_end_signal() {
+        let body = chunked(vec![
+            sign(encode_event("one")),
+            sign(Bytes::new()),
+            encode_event("after the end"),
+        ]);
+        let mut receiver = Receiver::new(Unmarshaller, body);
+
+        assert_eq!(Some(TestEvent("one".into())), receiver.recv().await.unwrap());
+        assert_eq!(None, receiver.recv().await.unwrap());
+        assert_eq!(None, receiver.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn truncated_frames_are_errors() {
+        let event = encode_event("one");
+        let body = chunked(vec![event.slice(..event.len() - 1)]);
+        let mut receiver = Receiver::new(Unmarshaller, body);
+
+        assert!(matches!(
+            receiver.recv().await,
+            Err(ReceiveError::UnexpectedEndOfStream)
+        ));
+    }
+
+    #[tokio::test]
+    async fn modeled_errors_are_received() {
+        let error =
+            Message::new("bad").add_header(Header::new(":exception-type", HeaderValue::String("TestError".into())));
+        let mut receiver = Receiver::new(Unmarshaller, chunked(vec![encode(&error).unwrap()]));
+
+        assert!(matches!(
+            receiver.recv().await,
+            Err(ReceiveError::Modeled(TestError(message))) if message == "bad"
+        ));
+    }
+
+    fn message_type(message: &Message) -> &str {
+        message.headers()[0].value().as_string().unwrap().as_str()
+    }
+
+    #[tokio::test]
+    async fn sends_events() {
+        let events = stream::iter(vec![Ok(TestEvent("one".into())), Ok(TestEvent("two".into()))]);
+        let body = EventStreamBody::new(Marshaller, ErrorMarshaller, events);
+        let bytes = hyper::body::to_bytes(body).await.unwrap();
+
+        let mut receiver = Receiver::new(Unmarshaller, Body::from(bytes));
+        assert_eq!(Some(TestEvent("one".into())), receiver.recv().await.unwrap());
+        assert_eq!(Some(TestEvent("two".into())), receiver.recv().await.unwrap());
+        assert_eq!(None, receiver.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn modeled_errors_end_the_stream() {
+        let events = stream::iter(vec![
+            Ok(TestEvent("one".into())),
+            Err(TestError("bad".into())),
+            Ok(TestEvent("never sent".into())),
+        ]);
+        let mut body = EventStreamBody::new(Marshaller, ErrorMarshaller, events);
+
+        let event = Message::read_from(body.data().await.unwrap().unwrap()).unwrap();
+        assert_eq!("event", message_type(&event));
+        let error = Message::read_from(body.data().await.unwrap().unwrap()).unwrap();
+        assert_eq!("exception", message_type(&error));
+        assert_eq!(&b"bad"[..], &error.payload()[..]);
+        assert!(body.is_end_stream());
+        assert!(body.data().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn marshalling_failures_send_an_error_frame() {
+        let events = stream::iter(vec![Ok(TestEvent("".into())), Ok(TestEvent("never sent".into()))]);
+        let mut body = EventStreamBody::new(Marshaller, ErrorMarshaller, events);
+
+        let error = Message::read_from(body.data().await.unwrap().unwrap()).unwrap();
+        assert_eq!("error", message_type(&error));
+        assert_eq!(
+            "InternalFailureException",
+            error.headers()[1].value().as_string().unwrap().as_str()
+        );
+        assert!(body.data().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn events_are_pulled_on_demand() {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let events = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|event| (event, rx)) });
+        let mut body = EventStreamBody::new(Marshaller, ErrorMarshaller, events);
+
+        tx.send(Ok(TestEvent("one".into()))).await.unwrap();
+        // The channel only has room for one event, which is only freed once the body is polled.
+        assert!(tx.try_send(Ok(TestEvent("two".into()))).is_err());
+        assert!(body.data().await.is_some());
+        tx.try_send(Ok(TestEvent("two".into()))).unwrap();
+        drop(tx);
+        assert!(body.data().await.is_some());
+        assert!(body.data().await.is_none());
+    }
+}
@@ -37,6 +37,7 @@
 use super::rejection::{ExtensionHandlingRejection, ExtensionsAlreadyExtracted, MissingExtension};
 use async_trait::async_trait;
 use axum_core::extract::{FromRequest, RequestParts};
+use std::collections::HashMap;
 use std::ops::Deref;
 
 /// Extension type used to store information in HTTP responses.
@@ -63,6 +64,34 @@ impl ResponseExtensions {
     }
 }
 
+/// Extension type used to store the values bound to the labels of an operation's [endpoint trait]
+/// host prefix, keyed by the name of the input member each label is bound to.
+///
+/// The [`Router`](crate::Router) inserts it into the request extensions when routing a request to
+/// an operation with a host prefix. The generated request deserializers read it to set the
+/// `@hostLabel` members of the operation input, the same way path labels are bound.
+///
+/// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostLabels(HashMap<String, String>);
+
+impl HostLabels {
+    /// Creates a new `HostLabels` from the values bound to each label name.
+    pub fn new(labels: HashMap<String, String>) -> Self {
+        Self(labels)
+    }
+
+    /// Returns the value bound to the label named `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Returns an iterator over the label names and the values bound to them.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
 /// Extension type used to store the type of user defined error returned by an operation.
 /// These are modeled errors, defined in the Smithy model.
 #[derive(Debug, Clone)]
@@ -11,18 +11,26 @@
 pub(crate) mod macros;
 
 pub mod body;
+pub mod body_limit;
 pub mod error;
+pub mod event_stream;
 mod extension;
 pub mod routing;
 
 #[doc(hidden)]
 pub mod protocols;
 pub mod rejection;
+pub mod request_id;
+pub mod runtime_error;
+pub mod serve;
+pub mod validation;
 
 #[doc(inline)]
 pub use self::error::Error;
 #[doc(inline)]
-pub use self::extension::{Extension, ExtensionModeledError, ExtensionRejection, ResponseExtensions};
+pub use self::extension::{Extension, ExtensionModeledError, ExtensionRejection, HostLabels, ResponseExtensions};
+#[doc(inline)]
+pub use self::protocols::Protocol;
 #[doc(inline)]
 pub use self::routing::Router;
 #[doc(inline)]
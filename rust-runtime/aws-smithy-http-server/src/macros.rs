@@ -57,6 +57,13 @@ macro_rules! define_rejection {
             }
         }
 
+        impl $name {
+            /// Returns the status code of the response this rejection is converted into.
+            pub fn status_code(&self) -> http::StatusCode {
+                http::StatusCode::$status
+            }
+        }
+
         impl std::fmt::Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 write!(f, "{}", $body)
@@ -103,6 +110,13 @@ macro_rules! define_rejection {
             }
         }
 
+        impl $name {
+            /// Returns the status code of the response this rejection is converted into.
+            pub fn status_code(&self) -> http::StatusCode {
+                http::StatusCode::$status
+            }
+        }
+
         impl std::fmt::Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 write!(f, "{}", $body)
@@ -161,6 +175,17 @@ macro_rules! composite_rejection {
             }
         )+
 
+        impl $name {
+            /// Returns the status code of the response this rejection is converted into.
+            pub fn status_code(&self) -> http::StatusCode {
+                match self {
+                    $(
+                        Self::$variant(inner) => inner.status_code(),
+                    )+
+                }
+            }
+        }
+
         impl std::fmt::Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -7,6 +7,15 @@
 use crate::rejection::{ContentTypeRejection, MimeParsingFailed, MissingJsonContentType, MissingXmlContentType};
 use axum_core::extract::RequestParts;
 
+/// Supported protocols.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    RestJson1,
+    RestXml,
+    AwsJson10,
+    AwsJson11,
+}
+
 /// Validate that the request had the standard JSON content-type header.
 pub fn check_json_content_type<B>(req: &RequestParts<B>) -> Result<(), ContentTypeRejection> {
     let mime = req
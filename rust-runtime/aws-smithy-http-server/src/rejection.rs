@@ -78,6 +78,14 @@ define_rejection! {
     pub struct MimeParsingFailed;
 }
 
+define_rejection! {
+    #[status = PAYLOAD_TOO_LARGE]
+    #[body = "Request body is too large"]
+    /// Rejection type used if the request body is larger than the limit set with a
+    /// [`RequestBodyLimitLayer`](crate::body_limit::RequestBodyLimitLayer).
+    pub struct PayloadTooLarge;
+}
+
 define_rejection! {
     #[status = INTERNAL_SERVER_ERROR]
     #[body = "Extensions taken by other extractor"]
@@ -130,9 +138,22 @@ composite_rejection! {
         Http,
         HeadersParse,
         ContentTypeRejection,
+        PayloadTooLarge,
         BodyAlreadyExtracted,
         HeadersAlreadyExtracted,
         ExtensionsAlreadyExtracted,
+        MissingExtension,
+    }
+}
+
+impl From<ExtensionHandlingRejection> for SmithyRejection {
+    fn from(rejection: ExtensionHandlingRejection) -> Self {
+        match rejection {
+            ExtensionHandlingRejection::MissingExtension(rejection) => SmithyRejection::MissingExtension(rejection),
+            ExtensionHandlingRejection::ExtensionsAlreadyExtracted(rejection) => {
+                SmithyRejection::ExtensionsAlreadyExtracted(rejection)
+            }
+        }
     }
 }
 
@@ -0,0 +1,273 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Request IDs.
+//!
+//! A [`RequestIdLayer`] gives every request an ID, which is:
+//! - taken from the `x-amzn-requestid` or `x-amz-request-id` header of the request if it has a
+//!   valid one, or freshly minted otherwise;
+//! - stored in the request extensions, so that handlers can extract it with
+//!   [`Extension<RequestId>`](crate::Extension);
+//! - recorded in a `request` [`tracing`] span that covers the processing of the request; and
+//! - echoed back in the response headers.
+//!
+//! To correlate the logs of every layer with the request ID, apply the layer around the whole
+//! [`Router`](crate::Router) rather than with [`Router::layer`](crate::Router::layer):
+//!
+//! ```rust,ignore
+//! use aws_smithy_http_server::request_id::RequestIdLayer;
+//!
+//! let app = tower::ServiceBuilder::new().layer(RequestIdLayer::new()).service(router);
+//! ```
+
+use http::header::HeaderName;
+use http::{HeaderValue, Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+
+const X_AMZN_REQUESTID: HeaderName = HeaderName::from_static("x-amzn-requestid");
+const X_AMZ_REQUEST_ID: HeaderName = HeaderName::from_static("x-amz-request-id");
+
+/// Incoming request IDs longer than this are discarded.
+const MAX_REQUEST_ID_LEN: usize = 256;
+
+/// The ID of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId {
+    id: HeaderValue,
+}
+
+impl RequestId {
+    /// Mints a new random request ID, formatted as a version 4 UUID.
+    pub fn new() -> Self {
+        let bits = (fastrand::u128(..) & !(0xf << 76) & !(0x3 << 62)) | (0x4 << 76) | (0x2 << 62);
+        let hex = format!("{:032x}", bits);
+        let id = format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        );
+        Self {
+            id: HeaderValue::from_str(&id).expect("UUIDs are valid header values"),
+        }
+    }
+
+    /// Returns the request ID carried by a header value, if it is valid.
+    ///
+    /// Valid request IDs are non-empty, at most 256 bytes long and only contain visible ASCII
+    /// characters, so that they can safely be logged.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let bytes = value.as_bytes();
+        let valid =
+            !bytes.is_empty() && bytes.len() <= MAX_REQUEST_ID_LEN && bytes.iter().all(|b| b.is_ascii_graphic());
+        valid.then(|| Self { id: value.clone() })
+    }
+
+    /// Returns the request ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.id
+            .to_str()
+            .expect("request IDs only contain visible ASCII characters")
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// [`Layer`] that applies the [`RequestIdService`] middleware.
+#[derive(Debug, Clone, Default)]
+pub struct RequestIdLayer {
+    _private: (),
+}
+
+impl RequestIdLayer {
+    /// Creates a new `RequestIdLayer`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+/// Middleware that gives every request an ID. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestIdService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RequestIdFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // Echo the request ID back in the header it came in, if any.
+        let incoming = [X_AMZN_REQUESTID, X_AMZ_REQUEST_ID].iter().find_map(|header_name| {
+            let request_id = RequestId::from_header(req.headers().get(header_name)?)?;
+            Some((header_name.clone(), request_id))
+        });
+        let (header_name, request_id) = incoming.unwrap_or_else(|| (X_AMZN_REQUESTID, RequestId::new()));
+
+        let span = tracing::info_span!("request", request_id = %request_id);
+        req.extensions_mut().insert(request_id.clone());
+        let future = {
+            let _entered = span.enter();
+            self.inner.call(req)
+        };
+
+        RequestIdFuture {
+            inner: future.instrument(span),
+            header_name,
+            request_id,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response future for [`RequestIdService`].
+    pub struct RequestIdFuture<F> {
+        #[pin]
+        inner: Instrumented<F>,
+        header_name: HeaderName,
+        request_id: RequestId,
+    }
+}
+
+impl<F, ResBody, E> Future for RequestIdFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = futures_util::ready!(this.inner.poll(cx))?;
+        response
+            .headers_mut()
+            .insert(this.header_name.clone(), this.request_id.id.clone());
+        response.extensions_mut().insert(this.request_id.clone());
+        Poll::Ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Extension;
+    use axum_core::extract::{FromRequest, RequestParts};
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+    use tracing_test::traced_test;
+
+    /// A service that responds with the request ID it extracts from the request extensions.
+    fn echo_request_id() -> impl Service<Request<()>, Response = Response<String>, Error = Infallible> + Clone {
+        tower::service_fn(|req: Request<()>| async move {
+            let mut parts = RequestParts::new(req);
+            let Extension(request_id) = Extension::<RequestId>::from_request(&mut parts).await.unwrap();
+            tracing::info!("handling request");
+            Ok(Response::new(request_id.to_string()))
+        })
+    }
+
+    fn req_with_header(name: &str, value: &str) -> Request<()> {
+        Request::builder().header(name, value).body(()).unwrap()
+    }
+
+    #[test]
+    fn minted_request_ids_are_uuids() {
+        let request_id = RequestId::new();
+        let id = request_id.as_str();
+
+        assert_eq!(36, id.len());
+        assert_eq!(Some('4'), id.chars().nth(14));
+        assert!(matches!(id.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(request_id, RequestId::new());
+    }
+
+    #[tokio::test]
+    async fn mints_a_request_id() {
+        let svc = RequestIdLayer::new().layer(echo_request_id());
+        let res = svc.oneshot(Request::new(())).await.unwrap();
+
+        let header = res.headers()["x-amzn-requestid"].to_str().unwrap().to_owned();
+        assert_eq!(36, header.len());
+        assert_eq!(&header, res.body());
+        assert_eq!(header, res.extensions().get::<RequestId>().unwrap().as_str());
+    }
+
+    #[tokio::test]
+    async fn accepts_incoming_request_ids() {
+        let svc = RequestIdLayer::new().layer(echo_request_id());
+
+        for header in ["x-amzn-requestid", "x-amz-request-id"] {
+            let res = svc
+                .clone()
+                .oneshot(req_with_header(header, "my-request-id"))
+                .await
+                .unwrap();
+            assert_eq!("my-request-id", res.headers()[header]);
+            assert_eq!("my-request-id", res.body());
+        }
+    }
+
+    #[tokio::test]
+    async fn discards_invalid_incoming_request_ids() {
+        let svc = RequestIdLayer::new().layer(echo_request_id());
+
+        let too_long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        for invalid in ["", "two words", too_long.as_str()] {
+            let res = svc
+                .clone()
+                .oneshot(req_with_header("x-amzn-requestid", invalid))
+                .await
+                .unwrap();
+            assert_ne!(invalid, res.body());
+            assert_eq!(36, res.body().len());
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn request_id_is_recorded_in_a_span() {
+        let svc = RequestIdLayer::new().layer(echo_request_id());
+        svc.oneshot(req_with_header("x-amzn-requestid", "traced-request-id"))
+            .await
+            .unwrap();
+
+        assert!(logs_contain("request{request_id=traced-request-id}"));
+        assert!(logs_contain("handling request"));
+    }
+}
@@ -7,12 +7,19 @@
 //!
 //! [Smithy specification]: https://awslabs.github.io/smithy/1.0/spec/core/http-traits.html
 
-use self::{future::RouterFuture, request_spec::RequestSpec};
+use self::{future::RouterFuture, request_spec::RequestSpec, tree::PathTree};
 use crate::body::{boxed, Body, BoxBody, HttpBody};
+use crate::protocols::Protocol;
+use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
 use crate::BoxError;
-use http::{Request, Response, StatusCode};
+use axum_core::response::IntoResponse;
+use http::{Method, Request, Response};
 use std::{
+    collections::{HashMap, HashSet},
     convert::Infallible,
+    error::Error as StdError,
+    fmt,
+    sync::Arc,
     task::{Context, Poll},
 };
 use tower::layer::Layer;
@@ -27,27 +34,68 @@ mod into_make_service;
 pub mod request_spec;
 
 mod route;
+mod tree;
 
 pub use self::{into_make_service::IntoMakeService, route::Route};
 
 /// The router is a [`tower::Service`] that routes incoming requests to other `Service`s
-/// based on the request's URI and HTTP method, adhering to the [Smithy specification].
-/// It currently does not support Smithy's [endpoint trait].
+/// based on the request's URI and HTTP method or on its `X-Amz-Target` header, depending on the
+/// protocol, adhering to the [Smithy specification].
+///
+/// Operations with Smithy's [endpoint trait] are only routed requests sent to a host that starts
+/// with their host prefix. The values bound to the labels of the host prefix are made available to
+/// the operation as a [`HostLabels`](crate::HostLabels) request extension, from which they are bound
+/// to the operation input by name.
 ///
 /// You should not **instantiate** this router directly; it will be created for you from the
 /// code generated from your Smithy model by `smithy-rs`.
 ///
+/// Requests that can't be routed are responded to with `404 Not Found` or `405 Method Not Allowed`
+/// and an empty body, unless the router is configured [with a protocol](Router::with_protocol).
+///
 /// [Smithy specification]: https://awslabs.github.io/smithy/1.0/spec/core/http-traits.html
 /// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
 #[derive(Debug)]
 pub struct Router<B = Body> {
-    routes: Vec<(Route<B>, RequestSpec)>,
+    routes: Routes<B>,
+    /// The protocol in whose wire format routing errors are serialized.
+    protocol: Option<Protocol>,
+}
+
+/// The routes of a [`Router`], stored according to how the service's protocol binds requests to
+/// operations.
+#[derive(Debug)]
+enum Routes<B> {
+    /// Routes for protocols that bind operations to an HTTP method and URI pattern with the `@http`
+    /// trait, such as restJson1 and restXml.
+    Rest {
+        routes: Vec<(Route<B>, RequestSpec)>,
+        /// Index of the URI path patterns of `routes`, used to find the routes matching a request
+        /// without testing all of them.
+        tree: Arc<PathTree>,
+    },
+    /// Routes for awsJson1_0 and awsJson1_1, keyed by the `<ServiceName>.<OperationName>` value that
+    /// requests carry in their `X-Amz-Target` header.
+    AwsJson(HashMap<String, Route<B>>),
+}
+
+impl<B> Clone for Routes<B> {
+    fn clone(&self) -> Self {
+        match self {
+            Routes::Rest { routes, tree } => Routes::Rest {
+                routes: routes.clone(),
+                tree: tree.clone(),
+            },
+            Routes::AwsJson(routes) => Routes::AwsJson(routes.clone()),
+        }
+    }
 }
 
 impl<B> Clone for Router<B> {
     fn clone(&self) -> Self {
         Self {
             routes: self.routes.clone(),
+            protocol: self.protocol,
         }
     }
 }
@@ -58,7 +106,11 @@ where
 {
     fn default() -> Self {
         Self {
-            routes: Default::default(),
+            routes: Routes::Rest {
+                routes: Default::default(),
+                tree: Default::default(),
+            },
+            protocol: None,
         }
     }
 }
@@ -86,11 +138,48 @@ where
             .collect();
 
         // Sort them once by specifity, with the more specific routes sorted before the less
-        // specific ones, so that when routing a request we can simply iterate through the routes
-        // and pick the first one that matches.
+        // specific ones, so that when routing a request we can simply iterate through the matching
+        // routes in index order and pick the first one that matches.
         routes.sort_by_key(|(_route, request_spec)| std::cmp::Reverse(request_spec.rank()));
+        let tree = Arc::new(PathTree::new(routes.iter().map(|(_route, request_spec)| request_spec)));
+
+        Self {
+            routes: Routes::Rest { routes, tree },
+            protocol: None,
+        }
+    }
+
+    /// Create a new `Router` for an awsJson1_0 or awsJson1_1 service from a vector of pairs of
+    /// services and the `<ServiceName>.<OperationName>` target of the operation they serve.
+    ///
+    /// Requests are routed on the value of their `X-Amz-Target` header. If the vector is empty the
+    /// router will respond `404 Not Found` to all requests.
+    #[doc(hidden)]
+    pub fn from_aws_json_box_clone_service_iter<T>(routes: T) -> Self
+    where
+        T: IntoIterator<
+            Item = (
+                tower::util::BoxCloneService<Request<B>, Response<BoxBody>, Infallible>,
+                String,
+            ),
+        >,
+    {
+        let routes = routes
+            .into_iter()
+            .map(|(svc, target)| (target, Route::from_box_clone_service(svc)))
+            .collect();
 
-        Self { routes }
+        Self {
+            routes: Routes::AwsJson(routes),
+            protocol: None,
+        }
+    }
+
+    /// Serialize the responses to requests that can't be routed in the wire format of `protocol`,
+    /// so that clients can parse them as errors. See [`RuntimeError`].
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
     }
 
     /// Convert this router into a [`MakeService`], that is a [`Service`] whose
@@ -124,15 +213,173 @@ where
             .layer_fn(Route::new)
             .layer(MapResponseBodyLayer::new(boxed))
             .layer(layer);
-        let routes = self
-            .routes
+        let routes = match self.routes {
+            Routes::Rest { routes, tree } => Routes::Rest {
+                routes: routes
+                    .into_iter()
+                    .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
+                    .collect(),
+                // Layering does not change the order of the routes, so the tree can be reused.
+                tree,
+            },
+            Routes::AwsJson(routes) => Routes::AwsJson(
+                routes
+                    .into_iter()
+                    .map(|(target, route)| (target, Layer::layer(&layer, route)))
+                    .collect(),
+            ),
+        };
+        Router {
+            routes,
+            protocol: self.protocol,
+        }
+    }
+
+    /// Apply a [`tower::Layer`] to the routes of some operations only.
+    ///
+    /// Only the requests routed to the operations named in `operation_names` will be processed by
+    /// the layer's corresponding middleware. This can be used to, for example, authorize write
+    /// operations but not read-only ones:
+    ///
+    /// ```rust,ignore
+    /// let router = router.layer_operations(["PutItem", "DeleteItem"], AuthLayer::new())?;
+    /// ```
+    ///
+    /// Operations are named by their Smithy shape name, without the namespace. The middleware
+    /// must accept the same request body type as the router, since the requests routed to the other
+    /// operations are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownOperationsError`] if one of `operation_names` does not name an operation
+    /// of the router, so that a typo doesn't silently leave an operation without, say,
+    /// authorization.
+    pub fn layer_operations<I, N, L, NewResBody>(
+        self,
+        operation_names: I,
+        layer: L,
+    ) -> Result<Self, UnknownOperationsError>
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+        L: Layer<Route<B>>,
+        L::Service: Service<Request<B>, Response = Response<NewResBody>, Error = Infallible> + Clone + Send + 'static,
+        <L::Service as Service<Request<B>>>::Future: Send + 'static,
+        NewResBody: HttpBody<Data = bytes::Bytes> + Send + 'static,
+        NewResBody::Error: Into<BoxError>,
+    {
+        let operation_names: HashSet<String> = operation_names
             .into_iter()
-            .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
+            .map(|operation_name| operation_name.as_ref().to_owned())
             .collect();
-        Router { routes }
+        let mut unknown: Vec<String> = {
+            let known: HashSet<&str> = match &self.routes {
+                Routes::Rest { routes, .. } => routes
+                    .iter()
+                    .filter_map(|(_route, request_spec)| request_spec.operation_name())
+                    .collect(),
+                Routes::AwsJson(routes) => routes
+                    .keys()
+                    .filter_map(|target| aws_json_operation_name(target))
+                    .collect(),
+            };
+            operation_names
+                .iter()
+                .filter(|operation_name| !known.contains(operation_name.as_str()))
+                .cloned()
+                .collect()
+        };
+        if !unknown.is_empty() {
+            unknown.sort_unstable();
+            return Err(UnknownOperationsError {
+                operation_names: unknown,
+            });
+        }
+
+        let layer = ServiceBuilder::new()
+            .layer_fn(Route::new)
+            .layer(MapResponseBodyLayer::new(boxed))
+            .layer(layer);
+        let layer_if_named = |operation_name: Option<&str>, route: Route<B>| match operation_name {
+            Some(operation_name) if operation_names.contains(operation_name) => Layer::layer(&layer, route),
+            _ => route,
+        };
+
+        let routes = match self.routes {
+            Routes::Rest { routes, tree } => Routes::Rest {
+                routes: routes
+                    .into_iter()
+                    .map(|(route, request_spec)| (layer_if_named(request_spec.operation_name(), route), request_spec))
+                    .collect(),
+                tree,
+            },
+            Routes::AwsJson(routes) => Routes::AwsJson(
+                routes
+                    .into_iter()
+                    .map(|(target, route)| {
+                        let route = layer_if_named(aws_json_operation_name(&target), route);
+                        (target, route)
+                    })
+                    .collect(),
+            ),
+        };
+
+        Ok(Router {
+            routes,
+            protocol: self.protocol,
+        })
+    }
+
+    /// Finds the route for a request to a service bound with the `@http` trait, along with its
+    /// request spec, or the error to respond with if there is none.
+    fn rest_route<'a>(
+        routes: &'a [(Route<B>, RequestSpec)],
+        tree: &PathTree,
+        req: &Request<B>,
+    ) -> Result<&'a (Route<B>, RequestSpec), RuntimeErrorKind> {
+        let mut method_not_allowed = false;
+
+        for index in tree.matches(req.uri().path()) {
+            let (_route, request_spec) = &routes[index];
+            match request_spec.matches_path_matched(req) {
+                request_spec::Match::Yes => return Ok(&routes[index]),
+                request_spec::Match::MethodNotAllowed => method_not_allowed = true,
+                // Continue looping to see if another route matches.
+                request_spec::Match::No => continue,
+            }
+        }
+
+        if method_not_allowed {
+            Err(RuntimeErrorKind::MethodNotAllowed)
+        } else {
+            Err(RuntimeErrorKind::UnknownOperation)
+        }
+    }
+
+    /// Finds the route for a request to an awsJson1_0 or awsJson1_1 service, or the error to
+    /// respond with if there is none.
+    ///
+    /// These protocols always send `POST` requests to `/`.
+    fn aws_json_route(routes: &HashMap<String, Route<B>>, req: &Request<B>) -> Result<Route<B>, RuntimeErrorKind> {
+        if req.uri().path() != "/" {
+            return Err(RuntimeErrorKind::UnknownOperation);
+        }
+        if req.method() != Method::POST {
+            return Err(RuntimeErrorKind::MethodNotAllowed);
+        }
+
+        req.headers()
+            .get(X_AMZ_TARGET)
+            .and_then(|target| target.to_str().ok())
+            .and_then(|target| routes.get(target))
+            .cloned()
+            .ok_or(RuntimeErrorKind::UnknownOperation)
     }
 }
 
+/// The header awsJson1_0 and awsJson1_1 requests use to identify the operation they target.
+const X_AMZ_TARGET: &str = "x-amz-target";
+
 impl<B> Service<Request<B>> for Router<B>
 where
     B: Send + 'static,
@@ -147,40 +394,68 @@ where
     }
 
     #[inline]
-    fn call(&mut self, req: Request<B>) -> Self::Future {
-        let mut method_not_allowed = false;
-
-        for (route, request_spec) in &self.routes {
-            match request_spec.matches(&req) {
-                request_spec::Match::Yes => {
-                    return RouterFuture::from_oneshot(route.clone().oneshot(req));
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let route = match &self.routes {
+            Routes::Rest { routes, tree } => Self::rest_route(routes, tree, &req).map(|(route, request_spec)| {
+                if let Some(host_labels) = request_spec.host_labels(&req) {
+                    req.extensions_mut().insert(host_labels);
                 }
-                request_spec::Match::MethodNotAllowed => method_not_allowed = true,
-                // Continue looping to see if another route matches.
-                request_spec::Match::No => continue,
-            }
+                route.clone()
+            }),
+            Routes::AwsJson(routes) => Self::aws_json_route(routes, &req),
+        };
+
+        match route {
+            Ok(route) => RouterFuture::from_oneshot(route.oneshot(req)),
+            Err(kind) => RouterFuture::from_response(match self.protocol {
+                Some(protocol) => RuntimeError::new(protocol, kind).into_response(),
+                None => Response::builder()
+                    .status(kind.status_code())
+                    .body(crate::body::empty())
+                    .unwrap(),
+            }),
         }
+    }
+}
 
-        let status_code = if method_not_allowed {
-            StatusCode::METHOD_NOT_ALLOWED
-        } else {
-            StatusCode::NOT_FOUND
-        };
-        RouterFuture::from_response(
-            Response::builder()
-                .status(status_code)
-                .body(crate::body::empty())
-                .unwrap(),
+/// Returns the name of the operation targeted by an `X-Amz-Target` header value, which is
+/// formatted as `<ServiceName>.<OperationName>`.
+fn aws_json_operation_name(target: &str) -> Option<&str> {
+    target.rsplit('.').next()
+}
+
+/// The error returned by [`Router::layer_operations`] when some of the operation names it is given
+/// don't name an operation of the router.
+#[derive(Debug)]
+pub struct UnknownOperationsError {
+    operation_names: Vec<String>,
+}
+
+impl UnknownOperationsError {
+    /// Returns the unknown operation names, sorted.
+    pub fn operation_names(&self) -> &[String] {
+        &self.operation_names
+    }
+}
+
+impl fmt::Display for UnknownOperationsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot apply layer to unknown operations: {}",
+            self.operation_names.join(", ")
         )
     }
 }
 
+impl StdError for UnknownOperationsError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{body::boxed, routing::request_spec::*};
     use futures_util::Future;
-    use http::Method;
+    use http::{Method, StatusCode};
     use std::pin::Pin;
 
     /// Helper function to build a `Request`. Used in other test modules.
@@ -384,4 +659,230 @@ mod tests {
             assert_eq!(format!("{} :: {}", svc_name, uri), actual_body);
         }
     }
+
+    #[tokio::test]
+    async fn aws_json_routing() {
+        let routes = vec!["Service.Operation", "Service.OtherOperation"];
+
+        let mut router = Router::from_aws_json_box_clone_service_iter(routes.into_iter().map(|target| {
+            (
+                tower::util::BoxCloneService::new(NamedEchoUriService(String::from(target))),
+                String::from(target),
+            )
+        }));
+
+        let aws_json_req = |method: &Method, uri: &str, target: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("X-Amz-Target", target)
+                .body(())
+                .unwrap()
+        };
+
+        for target in ["Service.Operation", "Service.OtherOperation"] {
+            let mut res = router.call(aws_json_req(&Method::POST, "/", target)).await.unwrap();
+            let actual_body = get_body_as_str(&mut res).await;
+
+            assert_eq!(format!("{} :: /", target), actual_body);
+        }
+
+        let res = router
+            .call(aws_json_req(&Method::GET, "/", "Service.Operation"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+
+        let misses = vec![
+            aws_json_req(&Method::POST, "/", "Service.UnknownOperation"),
+            aws_json_req(&Method::POST, "/", "OtherService.Operation"),
+            aws_json_req(&Method::POST, "/operation", "Service.Operation"),
+            req(&Method::POST, "/"),
+        ];
+        for miss in misses {
+            let res = router.call(miss).await.unwrap();
+            assert_eq!(StatusCode::NOT_FOUND, res.status());
+        }
+    }
+
+    /// A service that returns the host labels bound to the request in the response body.
+    #[derive(Clone)]
+    struct EchoHostLabelsService;
+
+    impl<B> Service<Request<B>> for EchoHostLabelsService {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        #[inline]
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        #[inline]
+        fn call(&mut self, req: Request<B>) -> Self::Future {
+            let labels = req
+                .extensions()
+                .get::<crate::HostLabels>()
+                .map(|labels| labels.get("Bucket").unwrap_or_default().to_owned());
+            let body = boxed(Body::from(format!("{:?}", labels)));
+            let fut = async { Ok(Response::builder().status(&http::StatusCode::OK).body(body).unwrap()) };
+            Box::pin(fut)
+        }
+    }
+
+    #[tokio::test]
+    async fn host_prefix_routing() {
+        let path_and_query = || {
+            PathAndQuerySpec::new(
+                PathSpec::from_vector_unchecked(vec![PathSegment::Literal(String::from("a"))]),
+                QuerySpec::default(),
+            )
+        };
+        let request_specs = vec![
+            RequestSpec::new(
+                Method::GET,
+                UriSpec::new_with_host_prefix(
+                    vec![
+                        HostPrefixSegment::Label(String::from("Bucket")),
+                        HostPrefixSegment::Literal(String::from(".")),
+                    ],
+                    path_and_query(),
+                ),
+            ),
+            RequestSpec::new(Method::PUT, UriSpec::new(path_and_query())),
+        ];
+
+        let mut router = Router::from_box_clone_service_iter(
+            request_specs
+                .into_iter()
+                .map(|spec| (tower::util::BoxCloneService::new(EchoHostLabelsService), spec)),
+        );
+
+        let req_with_host = |method: &Method, host: &str| {
+            Request::builder()
+                .method(method)
+                .uri("/a")
+                .header(http::header::HOST, host)
+                .body(())
+                .unwrap()
+        };
+
+        let mut res = router
+            .call(req_with_host(&Method::GET, "bucket.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(r#"Some("bucket")"#, get_body_as_str(&mut res).await);
+
+        let mut res = router
+            .call(req_with_host(&Method::PUT, "bucket.example.com"))
+            .await
+            .unwrap();
+        assert_eq!("None", get_body_as_str(&mut res).await);
+
+        let res = router.call(req_with_host(&Method::GET, "localhost")).await.unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+    }
+
+    /// A layer that adds a `x-layered` header to the responses.
+    fn header_layer() -> tower::util::MapResponseLayer<fn(Response<BoxBody>) -> Response<BoxBody>> {
+        tower::util::MapResponseLayer::new(|mut res: Response<BoxBody>| {
+            res.headers_mut()
+                .insert("x-layered", http::HeaderValue::from_static("true"));
+            res
+        })
+    }
+
+    #[tokio::test]
+    async fn layer_operations() {
+        let request_specs = vec![
+            RequestSpec::from_parts(Method::GET, vec![PathSegment::Literal(String::from("a"))], Vec::new())
+                .with_operation_name("A"),
+            RequestSpec::from_parts(Method::GET, vec![PathSegment::Literal(String::from("b"))], Vec::new())
+                .with_operation_name("B"),
+        ];
+        let mut router = Router::from_box_clone_service_iter(request_specs.into_iter().map(|request_spec| {
+            (
+                tower::util::BoxCloneService::new(NamedEchoUriService(String::new())),
+                request_spec,
+            )
+        }))
+        .layer_operations(["A"].iter(), header_layer())
+        .unwrap();
+
+        let res = router.call(req(&Method::GET, "/a")).await.unwrap();
+        assert_eq!("true", res.headers()["x-layered"]);
+        let res = router.call(req(&Method::GET, "/b")).await.unwrap();
+        assert!(res.headers().get("x-layered").is_none());
+    }
+
+    #[tokio::test]
+    async fn aws_json_layer_operations() {
+        let mut router = Router::from_aws_json_box_clone_service_iter(
+            vec!["Service.Operation", "Service.OtherOperation"]
+                .into_iter()
+                .map(|target| {
+                    (
+                        tower::util::BoxCloneService::new(NamedEchoUriService(String::from(target))),
+                        String::from(target),
+                    )
+                }),
+        )
+        .layer_operations(vec!["OtherOperation"], header_layer())
+        .unwrap();
+
+        let aws_json_req = |target: &str| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/")
+                .header("X-Amz-Target", target)
+                .body(())
+                .unwrap()
+        };
+        let res = router.call(aws_json_req("Service.OtherOperation")).await.unwrap();
+        assert_eq!("true", res.headers()["x-layered"]);
+        let res = router.call(aws_json_req("Service.Operation")).await.unwrap();
+        assert!(res.headers().get("x-layered").is_none());
+    }
+
+    #[test]
+    fn layer_unknown_operations() {
+        let request_spec = RequestSpec::from_parts(Method::GET, Vec::new(), Vec::new()).with_operation_name("A");
+        let router: Router<()> = Router::from_box_clone_service_iter(vec![(
+            tower::util::BoxCloneService::new(NamedEchoUriService(String::new())),
+            request_spec,
+        )]);
+        let err = router
+            .layer_operations(vec!["D", "A", "C"], header_layer())
+            .unwrap_err();
+        assert_eq!(&["C", "D"], err.operation_names());
+        assert_eq!("cannot apply layer to unknown operations: C, D", err.to_string());
+    }
+
+    #[tokio::test]
+    async fn routing_errors_in_protocol_wire_format() {
+        let request_spec =
+            RequestSpec::from_parts(Method::GET, vec![PathSegment::Literal(String::from("a"))], Vec::new());
+        let mut router = Router::from_box_clone_service_iter(vec![(
+            tower::util::BoxCloneService::new(NamedEchoUriService(String::from("A"))),
+            request_spec,
+        )])
+        .with_protocol(Protocol::RestJson1);
+
+        let mut res = router.call(req(&Method::GET, "/b")).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert_eq!("UnknownOperationException", res.headers()["x-amzn-errortype"]);
+        assert_eq!(
+            r#"{"__type":"UnknownOperationException"}"#,
+            get_body_as_str(&mut res).await
+        );
+
+        let mut res = router.call(req(&Method::PUT, "/a")).await.unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+        assert_eq!("MethodNotAllowedException", res.headers()["x-amzn-errortype"]);
+        assert_eq!(
+            r#"{"__type":"MethodNotAllowedException"}"#,
+            get_body_as_str(&mut res).await
+        );
+    }
 }
@@ -3,6 +3,7 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
+use crate::extension::HostLabels;
 use http::Request;
 use regex::Regex;
 
@@ -20,9 +21,11 @@ pub enum QuerySegment {
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum HostPrefixSegment {
     Literal(String),
-    Label,
+    /// A label bound to the input member with the given name.
+    Label(String),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -65,15 +68,23 @@ pub struct UriSpec {
 }
 
 impl UriSpec {
-    // TODO When we add support for the endpoint trait, this constructor will take in
-    // a first argument `host_prefix`.
-    // https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
     pub fn new(path_and_query: PathAndQuerySpec) -> Self {
         UriSpec {
             host_prefix: None,
             path_and_query,
         }
     }
+
+    /// Creates a `UriSpec` for an operation with the [endpoint trait]: requests must additionally be
+    /// sent to a host that starts with `host_prefix`.
+    ///
+    /// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
+    pub fn new_with_host_prefix(host_prefix: Vec<HostPrefixSegment>, path_and_query: PathAndQuerySpec) -> Self {
+        UriSpec {
+            host_prefix: Some(host_prefix),
+            path_and_query,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -81,10 +92,13 @@ pub struct RequestSpec {
     method: http::Method,
     uri_spec: UriSpec,
     uri_path_regex: Regex,
+    host_prefix_regex: Option<Regex>,
+    /// The name of the operation this spec routes requests to.
+    operation_name: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
-pub(super) enum Match {
+pub enum Match {
     /// The request matches the URI pattern spec.
     Yes,
     /// The request matches the URI pattern spec, but the wrong HTTP method was used. `405 Method
@@ -114,25 +128,64 @@ impl From<&PathSpec> for Regex {
                 .fold(String::new(), |a, b| a + sep + b)
         };
 
-        Regex::new(&format!("{}$", re)).unwrap()
+        Regex::new(&format!("^{}$", re)).unwrap()
     }
 }
 
+/// Builds a regex matching the hosts that start with the host prefix, with one capture group per label.
+fn host_prefix_regex(host_prefix: &[HostPrefixSegment]) -> Regex {
+    let re: String = host_prefix
+        .iter()
+        .map(|segment| match segment {
+            HostPrefixSegment::Literal(literal) => regex::escape(literal),
+            // Labels are bound to (a part of) a single host label, so they can't contain dots.
+            HostPrefixSegment::Label(_) => String::from("([a-zA-Z0-9-]+)"),
+        })
+        .collect();
+
+    // Host names are case insensitive.
+    Regex::new(&format!("(?i)^{}", re)).unwrap()
+}
+
+/// Returns the host a request was sent to, taken from the URI for HTTP/2 requests or from the
+/// `Host` header for HTTP/1.1 requests.
+fn host<B>(req: &Request<B>) -> Option<&str> {
+    req.uri()
+        .host()
+        .or_else(|| req.headers().get(http::header::HOST)?.to_str().ok())
+}
+
 impl RequestSpec {
     pub fn new(method: http::Method, uri_spec: UriSpec) -> Self {
         let uri_path_regex = (&uri_spec.path_and_query.path_segments).into();
+        let host_prefix_regex = uri_spec.host_prefix.as_deref().map(host_prefix_regex);
         RequestSpec {
             method,
             uri_spec,
             uri_path_regex,
+            host_prefix_regex,
+            operation_name: None,
         }
     }
 
+    /// Records the name of the operation this spec routes requests to, so that layers can be
+    /// applied to it with [`Router::layer_operations`](super::Router::layer_operations).
+    pub fn with_operation_name<S: Into<String>>(mut self, operation_name: S) -> Self {
+        self.operation_name = Some(operation_name.into());
+        self
+    }
+
+    /// Returns the name of the operation this spec routes requests to, if it was recorded.
+    pub fn operation_name(&self) -> Option<&str> {
+        self.operation_name.as_deref()
+    }
+
     /// A measure of how "important" a `RequestSpec` is. The more specific a `RequestSpec` is, the
     /// higher it ranks in importance. Specificity is measured by the number of segments plus the
     /// number of query string literals in its URI pattern, so `/{Bucket}/{Key}?query` is more
     /// specific than `/{Bucket}/{Key}`, which is more specific than `/{Bucket}`, which is more
-    /// specific than `/`.
+    /// specific than `/`. The segments of the host prefix count too, so that a spec with a host
+    /// prefix is more specific than the same spec without one.
     ///
     /// This rank effectively induces a total order, but we don't implement as `Ord` for
     /// `RequestSpec` because it would appear in its public interface.
@@ -158,18 +211,38 @@ impl RequestSpec {
     ///
     /// [the TypeScript sSDK is implementing]: https://github.com/awslabs/smithy-typescript/blob/d263078b81485a6a2013d243639c0c680343ff47/smithy-typescript-ssdk-libs/server-common/src/httpbinding/mux.ts#L59.
     pub(super) fn rank(&self) -> usize {
-        self.uri_spec.path_and_query.path_segments.0.len() + self.uri_spec.path_and_query.query_segments.0.len()
+        self.uri_spec.path_and_query.path_segments.0.len()
+            + self.uri_spec.path_and_query.query_segments.0.len()
+            + self.uri_spec.host_prefix.as_ref().map_or(0, Vec::len)
     }
 
-    pub(super) fn matches<B>(&self, req: &Request<B>) -> Match {
-        if let Some(_host_prefix) = &self.uri_spec.host_prefix {
-            todo!("Look at host prefix");
-        }
+    pub(crate) fn path_segments(&self) -> &[PathSegment] {
+        &self.uri_spec.path_and_query.path_segments.0
+    }
 
+    /// Checks whether a request matches this spec.
+    ///
+    /// This tests the request against a single spec. The [`Router`](super::Router) does not call
+    /// this for every route; it looks up the routes whose URI path pattern matches in a
+    /// [`PathTree`](super::tree::PathTree) and then calls [`RequestSpec::matches_path_matched`] on them.
+    pub fn matches<B>(&self, req: &Request<B>) -> Match {
         if !self.uri_path_regex.is_match(req.uri().path()) {
             return Match::No;
         }
 
+        self.matches_path_matched(req)
+    }
+
+    /// Like [`RequestSpec::matches`], but assumes that the request's URI path is already known to
+    /// match this spec's path pattern.
+    pub(super) fn matches_path_matched<B>(&self, req: &Request<B>) -> Match {
+        if let Some(host_prefix_regex) = &self.host_prefix_regex {
+            match host(req) {
+                Some(host) if host_prefix_regex.is_match(host) => {}
+                _ => return Match::No,
+            }
+        }
+
         if self.uri_spec.path_and_query.query_segments.0.is_empty() {
             if self.method == req.method() {
                 return Match::Yes;
@@ -222,6 +295,23 @@ impl RequestSpec {
         }
     }
 
+    /// Returns the values bound to the labels of this spec's host prefix, if it has one and the
+    /// request's host matches it.
+    pub(super) fn host_labels<B>(&self, req: &Request<B>) -> Option<HostLabels> {
+        let captures = self.host_prefix_regex.as_ref()?.captures(host(req)?)?;
+        let names = self
+            .uri_spec
+            .host_prefix
+            .iter()
+            .flatten()
+            .filter_map(|segment| match segment {
+                HostPrefixSegment::Label(name) => Some(name.clone()),
+                HostPrefixSegment::Literal(_) => None,
+            });
+        let values = captures.iter().skip(1).flatten().map(|label| label.as_str().to_owned());
+        Some(HostLabels::new(names.zip(values).collect()))
+    }
+
     // Helper function to build a `RequestSpec`.
     #[cfg(test)]
     pub fn from_parts(
@@ -251,15 +341,15 @@ mod tests {
     #[test]
     fn path_spec_into_regex() {
         let cases = vec![
-            (PathSpec(vec![]), "/$"),
-            (PathSpec(vec![PathSegment::Literal(String::from("a"))]), "/a$"),
+            (PathSpec(vec![]), "^/$"),
+            (PathSpec(vec![PathSegment::Literal(String::from("a"))]), "^/a$"),
             (
                 PathSpec(vec![PathSegment::Literal(String::from("a")), PathSegment::Label]),
-                "/a/[^/]*$",
+                "^/a/[^/]*$",
             ),
             (
                 PathSpec(vec![PathSegment::Literal(String::from("a")), PathSegment::Greedy]),
-                "/a/.*$",
+                "^/a/.*$",
             ),
             (
                 PathSpec(vec![
@@ -267,7 +357,7 @@ mod tests {
                     PathSegment::Greedy,
                     PathSegment::Literal(String::from("suffix")),
                 ]),
-                "/a/.*/suffix$",
+                "^/a/.*/suffix$",
             ),
         ];
 
@@ -338,6 +428,11 @@ mod tests {
         );
     }
 
+    #[test]
+    fn path_patterns_are_anchored_at_the_start() {
+        assert_eq!(Match::No, ab_spec().matches(&req(&Method::GET, "/x/a/b")));
+    }
+
     fn ab_spec() -> RequestSpec {
         RequestSpec::from_parts(
             Method::GET,
@@ -442,4 +537,76 @@ mod tests {
             assert_eq!(Match::Yes, label_spec.matches(&req(method, uri)));
         }
     }
+
+    fn host_prefix_spec() -> RequestSpec {
+        RequestSpec::new(
+            Method::GET,
+            UriSpec::new_with_host_prefix(
+                vec![
+                    HostPrefixSegment::Label(String::from("Bucket")),
+                    HostPrefixSegment::Literal(String::from(".data-")),
+                    HostPrefixSegment::Label(String::from("Region")),
+                    HostPrefixSegment::Literal(String::from(".")),
+                ],
+                PathAndQuerySpec::new(
+                    PathSpec::from_vector_unchecked(vec![PathSegment::Literal(String::from("a"))]),
+                    QuerySpec::default(),
+                ),
+            ),
+        )
+    }
+
+    fn req_with_host(method: &Method, uri: &str, host: &str) -> Request<()> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(http::header::HOST, host)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn host_prefix_must_match() {
+        let hits = vec![
+            req_with_host(&Method::GET, "/a", "bucket.data-us-east-1.example.com"),
+            req_with_host(&Method::GET, "/a", "Bucket.DATA-us-east-1.example.com:8080"),
+            req(&Method::GET, "http://bucket.data-us-east-1.example.com/a"),
+        ];
+        for hit in &hits {
+            assert_eq!(Match::Yes, host_prefix_spec().matches(hit));
+        }
+
+        let misses = vec![
+            req_with_host(&Method::GET, "/a", "example.com"),
+            req_with_host(&Method::GET, "/a", "bucket.example.com"),
+            req_with_host(&Method::GET, "/a", "my.bucket.data-us-east-1.example.com"),
+            req_with_host(&Method::GET, "/a", ".data-us-east-1.example.com"),
+            req(&Method::GET, "/a"),
+        ];
+        for miss in &misses {
+            assert_eq!(Match::No, host_prefix_spec().matches(miss));
+        }
+    }
+
+    #[test]
+    fn host_prefixes_are_ranked() {
+        let without_host_prefix =
+            RequestSpec::from_parts(Method::GET, vec![PathSegment::Literal(String::from("a"))], Vec::new());
+        assert!(host_prefix_spec().rank() > without_host_prefix.rank());
+    }
+
+    #[test]
+    fn host_labels_are_captured() {
+        let labels = host_prefix_spec()
+            .host_labels(&req_with_host(&Method::GET, "/a", "bucket.data-us-east-1.example.com"))
+            .unwrap();
+        assert_eq!(Some("bucket"), labels.get("Bucket"));
+        assert_eq!(Some("us-east-1"), labels.get("Region"));
+        assert_eq!(None, labels.get("bucket"));
+
+        assert_eq!(
+            None,
+            ab_spec().host_labels(&req(&Method::GET, "http://example.com/a/b"))
+        );
+    }
 }
@@ -0,0 +1,173 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! A prefix tree of URI path patterns.
+//!
+//! The [`Router`](super::Router) uses a [`PathTree`] to find the routes whose URI path pattern matches
+//! an incoming request. Looking up a path only walks the tree as deep as the path has segments, so the
+//! cost of routing a request does not depend on the number of routes, only on the number of routes
+//! that share a prefix with it.
+
+use super::request_spec::{PathSegment, RequestSpec};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+struct Node {
+    /// Children reached by consuming a segment that is equal to the key.
+    literals: HashMap<String, Node>,
+    /// Child reached by binding a single segment to a label.
+    label: Option<Box<Node>>,
+    /// Child reached by binding one or more segments to a greedy label.
+    greedy: Option<Box<Node>>,
+    /// Indices of the routes whose path pattern ends at this node.
+    routes: Vec<usize>,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[PathSegment], index: usize) {
+        match segments.split_first() {
+            None => self.routes.push(index),
+            Some((PathSegment::Literal(literal), rest)) => {
+                self.literals.entry(literal.clone()).or_default().insert(rest, index)
+            }
+            Some((PathSegment::Label, rest)) => self.label.get_or_insert_with(Default::default).insert(rest, index),
+            Some((PathSegment::Greedy, rest)) => self.greedy.get_or_insert_with(Default::default).insert(rest, index),
+        }
+    }
+
+    fn collect(&self, segments: &[&str], matches: &mut Vec<usize>) {
+        let (first, rest) = match segments.split_first() {
+            None => {
+                matches.extend(&self.routes);
+                return;
+            }
+            Some(split) => split,
+        };
+
+        if let Some(child) = self.literals.get(*first) {
+            child.collect(rest, matches);
+        }
+        if let Some(child) = &self.label {
+            child.collect(rest, matches);
+        }
+        if let Some(child) = &self.greedy {
+            // A greedy label binds to at least one segment, and can swallow all of the remaining ones.
+            for consumed in 1..=segments.len() {
+                child.collect(&segments[consumed..], matches);
+            }
+        }
+    }
+}
+
+/// A prefix tree built from the [`PathSpec`](super::request_spec::PathSpec)s of a list of
+/// [`RequestSpec`]s.
+///
+/// Routes are identified by their index in the list the tree was built from.
+#[derive(Debug, Default)]
+pub(crate) struct PathTree {
+    root: Node,
+}
+
+impl PathTree {
+    /// Builds a tree from an iterator of request specs. The spec yielded in `n`-th position is
+    /// identified by index `n`.
+    pub(crate) fn new<'a, I>(request_specs: I) -> Self
+    where
+        I: IntoIterator<Item = &'a RequestSpec>,
+    {
+        let mut tree = PathTree::default();
+        for (index, request_spec) in request_specs.into_iter().enumerate() {
+            let path_segments = request_spec.path_segments();
+            if path_segments.is_empty() {
+                // The pattern `/` is equivalent to a single empty literal segment.
+                tree.root.insert(&[PathSegment::Literal(String::new())], index);
+            } else {
+                tree.root.insert(path_segments, index);
+            }
+        }
+        tree
+    }
+
+    /// Returns the indices of the routes whose path pattern matches `path`, in ascending order.
+    pub(crate) fn matches(&self, path: &str) -> Vec<usize> {
+        let mut matches = Vec::new();
+        if let Some(path) = path.strip_prefix('/') {
+            // Empty segments have meaning, so they're kept, see https://github.com/awslabs/smithy/issues/1024.
+            let segments: Vec<&str> = path.split('/').collect();
+            self.root.collect(&segments, &mut matches);
+        }
+        matches.sort_unstable();
+        matches.dedup();
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+
+    fn tree(path_specs: Vec<Vec<PathSegment>>) -> PathTree {
+        let request_specs: Vec<RequestSpec> = path_specs
+            .into_iter()
+            .map(|path_segments| RequestSpec::from_parts(Method::GET, path_segments, Vec::new()))
+            .collect();
+        PathTree::new(&request_specs)
+    }
+
+    fn literal(s: &str) -> PathSegment {
+        PathSegment::Literal(String::from(s))
+    }
+
+    #[test]
+    fn root_pattern() {
+        let tree = tree(vec![vec![], vec![PathSegment::Label]]);
+
+        assert_eq!(vec![0, 1], tree.matches("/"));
+        assert_eq!(vec![1], tree.matches("/a"));
+        assert!(tree.matches("//").is_empty());
+        assert!(tree.matches("").is_empty());
+    }
+
+    #[test]
+    fn literals_and_labels() {
+        let tree = tree(vec![
+            vec![literal("a"), PathSegment::Label],
+            vec![literal("a"), literal("b")],
+            vec![PathSegment::Label, literal("b")],
+        ]);
+
+        assert_eq!(vec![0, 1, 2], tree.matches("/a/b"));
+        assert_eq!(vec![0], tree.matches("/a/c"));
+        assert_eq!(vec![0], tree.matches("/a/"));
+        assert_eq!(vec![2], tree.matches("/c/b"));
+        assert!(tree.matches("/a").is_empty());
+        assert!(tree.matches("/a/b/").is_empty());
+        assert!(tree.matches("/x/a/b").is_empty());
+    }
+
+    #[test]
+    fn greedy_labels() {
+        let tree = tree(vec![
+            vec![literal("mg"), PathSegment::Greedy, literal("z")],
+            vec![literal("mg"), PathSegment::Greedy],
+        ]);
+
+        assert_eq!(vec![0, 1], tree.matches("/mg/a/z"));
+        assert_eq!(vec![0, 1], tree.matches("/mg//z"));
+        assert_eq!(vec![0, 1], tree.matches("/mg/a/z/b/z"));
+        assert_eq!(vec![1], tree.matches("/mg/z"));
+        assert_eq!(vec![1], tree.matches("/mg/"));
+        assert!(tree.matches("/mg").is_empty());
+    }
+
+    #[test]
+    fn greedy_label_followed_by_greedy_label_is_reported_once() {
+        let tree = tree(vec![vec![PathSegment::Greedy, PathSegment::Greedy]]);
+
+        assert_eq!(vec![0], tree.matches("/a/b/c/d"));
+        assert!(tree.matches("/a").is_empty());
+    }
+}
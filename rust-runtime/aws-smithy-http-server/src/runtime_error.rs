@@ -0,0 +1,351 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Runtime error type.
+//!
+//! Errors raised by the framework before a request reaches an operation handler, like requests that
+//! do not match any operation or that have the wrong `Content-Type`, are not part of the Smithy
+//! model. A [`RuntimeError`] serializes them in the wire format of the service's protocol so that
+//! clients can still parse them as errors.
+//!
+//! The [`Router`](crate::Router) raises them for requests that can't be routed, and the generated
+//! operation handlers raise them for the requests they can't deserialize, by extracting their
+//! input with [`extract`].
+
+use crate::protocols::Protocol;
+use crate::rejection::{ContentTypeRejection, ExtensionHandlingRejection, SmithyRejection};
+use crate::validation::ValidationException;
+use aws_smithy_json::serialize::JsonObjectWriter;
+use aws_smithy_xml::encode::XmlWriter;
+use axum_core::extract::{FromRequest, RequestParts};
+use axum_core::response::IntoResponse;
+use http::StatusCode;
+
+/// The kinds of errors that can be raised by the framework itself.
+#[derive(Debug)]
+pub enum RuntimeErrorKind {
+    /// The request does not match any of the service's operations.
+    UnknownOperation,
+    /// The request matches the URI pattern of an operation, but uses a different HTTP method.
+    MethodNotAllowed,
+    /// The request was rejected while being deserialized, or its response could not be serialized.
+    Rejection(SmithyRejection),
+    /// The request was deserialized, but its input fails to satisfy the constraints of the model.
+    Validation(ValidationException),
+}
+
+impl RuntimeErrorKind {
+    /// Returns the status code of the responses for this kind of error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RuntimeErrorKind::UnknownOperation => StatusCode::NOT_FOUND,
+            RuntimeErrorKind::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            RuntimeErrorKind::Rejection(rejection) => rejection.status_code(),
+            RuntimeErrorKind::Validation(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Returns the name of the error, which is used as its type or code in the response.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeErrorKind::UnknownOperation => "UnknownOperationException",
+            RuntimeErrorKind::MethodNotAllowed => "MethodNotAllowedException",
+            RuntimeErrorKind::Rejection(SmithyRejection::ContentTypeRejection(_)) => "UnsupportedMediaTypeException",
+            RuntimeErrorKind::Rejection(SmithyRejection::PayloadTooLarge(_)) => "PayloadTooLargeException",
+            RuntimeErrorKind::Rejection(rejection) if rejection.status_code().is_server_error() => {
+                "InternalFailureException"
+            }
+            RuntimeErrorKind::Rejection(_) => "SerializationException",
+            RuntimeErrorKind::Validation(_) => "ValidationException",
+        }
+    }
+
+    fn message(&self) -> Option<String> {
+        match self {
+            RuntimeErrorKind::UnknownOperation | RuntimeErrorKind::MethodNotAllowed => None,
+            RuntimeErrorKind::Rejection(rejection) => Some(rejection.to_string()),
+            RuntimeErrorKind::Validation(exception) => Some(exception.message().to_owned()),
+        }
+    }
+}
+
+impl From<SmithyRejection> for RuntimeErrorKind {
+    fn from(rejection: SmithyRejection) -> Self {
+        RuntimeErrorKind::Rejection(rejection)
+    }
+}
+
+impl From<ValidationException> for RuntimeErrorKind {
+    fn from(exception: ValidationException) -> Self {
+        RuntimeErrorKind::Validation(exception)
+    }
+}
+
+impl From<ContentTypeRejection> for RuntimeErrorKind {
+    fn from(rejection: ContentTypeRejection) -> Self {
+        RuntimeErrorKind::Rejection(SmithyRejection::ContentTypeRejection(rejection))
+    }
+}
+
+impl From<ExtensionHandlingRejection> for RuntimeErrorKind {
+    fn from(rejection: ExtensionHandlingRejection) -> Self {
+        RuntimeErrorKind::Rejection(rejection.into())
+    }
+}
+
+/// Extracts `T` from a request to an operation of a service using `protocol`.
+///
+/// If `T` can't be extracted, the rejection is returned as a [`RuntimeError`] that serializes it
+/// in the wire format of `protocol`.
+pub async fn extract<T, B>(protocol: Protocol, req: &mut RequestParts<B>) -> Result<T, RuntimeError>
+where
+    T: FromRequest<B>,
+    T::Rejection: Into<RuntimeErrorKind>,
+    B: Send,
+{
+    T::from_request(req)
+        .await
+        .map_err(|rejection| RuntimeError::new(protocol, rejection))
+}
+
+/// An error raised by the framework, to be serialized in the wire format of `protocol`.
+#[derive(Debug)]
+pub struct RuntimeError {
+    protocol: Protocol,
+    kind: RuntimeErrorKind,
+}
+
+impl RuntimeError {
+    /// Creates a new `RuntimeError`.
+    pub fn new(protocol: Protocol, kind: impl Into<RuntimeErrorKind>) -> Self {
+        Self {
+            protocol,
+            kind: kind.into(),
+        }
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> &RuntimeErrorKind {
+        &self.kind
+    }
+
+    fn json_body(&self) -> String {
+        let mut body = String::new();
+        let mut object = JsonObjectWriter::new(&mut body);
+        object.key("__type").string(self.kind.name());
+        if let Some(message) = self.kind.message() {
+            object.key("message").string(&message);
+        }
+        if let RuntimeErrorKind::Validation(exception) = &self.kind {
+            let mut field_list = object.key("fieldList").start_array();
+            for field in exception.field_list() {
+                let mut entry = field_list.value().start_object();
+                entry.key("path").string(field.path());
+                entry.key("message").string(field.message());
+                entry.finish();
+            }
+            field_list.finish();
+        }
+        object.finish();
+        body
+    }
+
+    fn xml_body(&self) -> String {
+        let mut body = String::new();
+        let mut writer = XmlWriter::new(&mut body);
+        let mut error_response = writer.start_el("ErrorResponse").finish();
+        let mut error = error_response.start_el("Error").finish();
+        let fault = if self.kind.status_code().is_server_error() {
+            "Receiver"
+        } else {
+            "Sender"
+        };
+        error.start_el("Type").finish().data(fault);
+        error.start_el("Code").finish().data(self.kind.name());
+        if let Some(message) = self.kind.message() {
+            error.start_el("Message").finish().data(&message);
+        }
+        if let RuntimeErrorKind::Validation(exception) = &self.kind {
+            let mut field_list = error.start_el("fieldList").finish();
+            for field in exception.field_list() {
+                let mut member = field_list.start_el("member").finish();
+                member.start_el("path").finish().data(field.path());
+                member.start_el("message").finish().data(field.message());
+                member.finish();
+            }
+            field_list.finish();
+        }
+        error.finish();
+        error_response.finish();
+        body
+    }
+}
+
+impl IntoResponse for RuntimeError {
+    fn into_response(self) -> axum_core::response::Response {
+        let (content_type, body) = match self.protocol {
+            Protocol::RestJson1 => ("application/json", self.json_body()),
+            Protocol::AwsJson10 => ("application/x-amz-json-1.0", self.json_body()),
+            Protocol::AwsJson11 => ("application/x-amz-json-1.1", self.json_body()),
+            Protocol::RestXml => ("application/xml", self.xml_body()),
+        };
+
+        let mut builder = http::Response::builder()
+            .status(self.kind.status_code())
+            .header(http::header::CONTENT_TYPE, content_type);
+        if self.protocol != Protocol::RestXml {
+            builder = builder.header("x-amzn-errortype", self.kind.name());
+        }
+        let mut response = builder
+            .body(crate::body::to_boxed(body))
+            .expect("the status code and headers are valid");
+        if let RuntimeErrorKind::Rejection(rejection) = &self.kind {
+            response
+                .extensions_mut()
+                .insert(crate::ExtensionRejection::new(rejection.to_string()));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rejection::MissingJsonContentType;
+    use crate::validation::ValidationExceptionField;
+
+    async fn body_as_str(response: axum_core::response::Response) -> String {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rest_json_unknown_operation() {
+        let response = RuntimeError::new(Protocol::RestJson1, RuntimeErrorKind::UnknownOperation).into_response();
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_eq!("application/json", response.headers()["content-type"]);
+        assert_eq!("UnknownOperationException", response.headers()["x-amzn-errortype"]);
+        assert_eq!(r#"{"__type":"UnknownOperationException"}"#, body_as_str(response).await);
+    }
+
+    #[tokio::test]
+    async fn aws_json_content_type_rejection() {
+        let rejection = ContentTypeRejection::MissingJsonContentType(MissingJsonContentType);
+        let response = RuntimeError::new(Protocol::AwsJson11, rejection).into_response();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_eq!("application/x-amz-json-1.1", response.headers()["content-type"]);
+        assert_eq!("UnsupportedMediaTypeException", response.headers()["x-amzn-errortype"]);
+        assert!(response.extensions().get::<crate::ExtensionRejection>().is_some());
+        assert_eq!(
+            r#"{"__type":"UnsupportedMediaTypeException","message":"Expected `Content-Type: application/json`"}"#,
+            body_as_str(response).await
+        );
+    }
+
+    #[tokio::test]
+    async fn rest_json_validation_exception() {
+        let exception =
+            ValidationException::new(vec![ValidationExceptionField::new("/name", "Value at '/name' failed")]);
+        let response = RuntimeError::new(Protocol::RestJson1, exception).into_response();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_eq!("ValidationException", response.headers()["x-amzn-errortype"]);
+        assert_eq!(
+            r#"{"__type":"ValidationException","message":"1 validation error detected. Value at '/name' failed","fieldList":[{"path":"/name","message":"Value at '/name' failed"}]}"#,
+            body_as_str(response).await
+        );
+    }
+
+    #[tokio::test]
+    async fn rest_xml_validation_exception() {
+        let exception = ValidationException::new(vec![ValidationExceptionField::new("/name", "failed")]);
+        let response = RuntimeError::new(Protocol::RestXml, exception).into_response();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_eq!(
+            "<ErrorResponse><Error><Type>Sender</Type><Code>ValidationException</Code><Message>1 validation error detected. failed</Message><fieldList><member><path>/name</path><message>failed</message></member></fieldList></Error></ErrorResponse>",
+            body_as_str(response).await
+        );
+    }
+
+    #[tokio::test]
+    async fn rest_xml_method_not_allowed() {
+        let response = RuntimeError::new(Protocol::RestXml, RuntimeErrorKind::MethodNotAllowed).into_response();
+
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+        assert_eq!("application/xml", response.headers()["content-type"]);
+        assert!(response.headers().get("x-amzn-errortype").is_none());
+        assert_eq!(
+            "<ErrorResponse><Error><Type>Sender</Type><Code>MethodNotAllowedException</Code></Error></ErrorResponse>",
+            body_as_str(response).await
+        );
+    }
+
+    /// An operation input that, like the generated ones, can only be extracted from JSON requests.
+    struct JsonInput;
+
+    #[async_trait::async_trait]
+    impl<B: Send> FromRequest<B> for JsonInput {
+        type Rejection = SmithyRejection;
+
+        async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+            crate::protocols::check_json_content_type(req)?;
+            Ok(JsonInput)
+        }
+    }
+
+    #[tokio::test]
+    async fn rejections_are_serialized_end_to_end() {
+        use crate::body::{to_boxed, Body};
+        use crate::routing::request_spec::{PathSegment, RequestSpec};
+        use http::Method;
+        use tower::{Service, ServiceExt};
+
+        // What the generated operation handlers do.
+        let handler = tower::service_fn(|req: http::Request<Body>| async move {
+            let mut req = RequestParts::new(req);
+            let response = match extract::<JsonInput, _>(Protocol::RestJson1, &mut req).await {
+                Ok(JsonInput) => http::Response::new(to_boxed("ok")),
+                Err(err) => err.into_response(),
+            };
+            Ok::<_, std::convert::Infallible>(response)
+        });
+        let mut router = crate::Router::from_box_clone_service_iter(vec![(
+            tower::util::BoxCloneService::new(handler),
+            RequestSpec::from_parts(Method::POST, vec![PathSegment::Literal(String::from("a"))], Vec::new()),
+        )])
+        .with_protocol(Protocol::RestJson1);
+        let request = |content_type: &str| {
+            http::Request::builder()
+                .method(Method::POST)
+                .uri("/a")
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(Body::from("{}"))
+                .unwrap()
+        };
+
+        let response = router.ready().await.unwrap().call(request("text/plain")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_eq!("application/json", response.headers()["content-type"]);
+        assert_eq!("UnsupportedMediaTypeException", response.headers()["x-amzn-errortype"]);
+        assert!(response.extensions().get::<crate::ExtensionRejection>().is_some());
+        assert_eq!(
+            r#"{"__type":"UnsupportedMediaTypeException","message":"Expected `Content-Type: application/json`"}"#,
+            body_as_str(response).await
+        );
+
+        let response = router
+            .ready()
+            .await
+            .unwrap()
+            .call(request("application/json"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("ok", body_as_str(response).await);
+    }
+}
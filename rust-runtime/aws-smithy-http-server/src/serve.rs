@@ -0,0 +1,447 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Serving a [`Router`](crate::Router) with graceful shutdown.
+//!
+//! [`serve`] accepts connections on a listener until a shutdown signal resolves. It then stops
+//! accepting new connections and waits for the requests that are in flight, including the ones
+//! whose response is still being streamed, to complete. Requests that have not completed when the
+//! shutdown deadline is reached are aborted, and their number is reported in the returned
+//! [`ShutdownReport`]:
+//!
+//! ```rust,ignore
+//! use aws_smithy_http_server::serve::serve;
+//! use std::time::Duration;
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+//! let shutdown_signal = async {
+//!     tokio::signal::ctrl_c().await.expect("failed to listen for the shutdown signal");
+//! };
+//! let report = serve(listener, router, shutdown_signal, Duration::from_secs(30)).await?;
+//! if report.in_flight_requests() > 0 {
+//!     tracing::warn!("aborted {} requests", report.in_flight_requests());
+//! }
+//! ```
+
+use crate::body::{Body, HttpBody};
+use crate::BoxError;
+use http::{HeaderMap, Request, Response};
+use hyper::server::conn::AddrIncoming;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tower::make::Shared;
+use tower::Service;
+
+/// What happened to the requests that were in flight when the server was shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    in_flight_requests: usize,
+}
+
+impl ShutdownReport {
+    /// Returns the number of requests that were still in flight when the shutdown deadline was
+    /// reached, and were therefore aborted. This is zero if the server drained gracefully.
+    pub fn in_flight_requests(&self) -> usize {
+        self.in_flight_requests
+    }
+}
+
+/// Serves `service` on `listener` until `shutdown_signal` resolves, then drains the in-flight
+/// requests for at most `deadline`. See the [module documentation](self).
+///
+/// `service` is usually a [`Router`](crate::Router), optionally wrapped in layers.
+pub async fn serve<S, ResBody, F>(
+    listener: TcpListener,
+    service: S,
+    shutdown_signal: F,
+    deadline: Duration,
+) -> Result<ShutdownReport, hyper::Error>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ResBody: HttpBody + Send + 'static,
+    ResBody::Data: Send,
+    ResBody::Error: Into<BoxError>,
+    F: Future<Output = ()>,
+{
+    let in_flight = InFlight::default();
+    let service = InFlightService {
+        inner: service,
+        in_flight: in_flight.clone(),
+    };
+
+    let executor = AbortingExecutor::default();
+    let (signalled_tx, mut signalled_rx) = tokio::sync::oneshot::channel();
+    let server = hyper::Server::builder(AddrIncoming::from_listener(listener)?)
+        .executor(executor.clone())
+        .serve(Shared::new(service))
+        .with_graceful_shutdown(async move {
+            shutdown_signal.await;
+            let _ = signalled_tx.send(());
+        });
+    tokio::pin!(server);
+
+    tokio::select! {
+        biased;
+        // The server only stops on its own if it fails to accept connections.
+        result = &mut server => {
+            result?;
+            return Ok(ShutdownReport { in_flight_requests: 0 });
+        }
+        _ = &mut signalled_rx => {}
+    }
+
+    tracing::info!(
+        in_flight_requests = in_flight.count(),
+        "shutting down, draining in-flight requests"
+    );
+    match tokio::time::timeout(deadline, server).await {
+        Ok(result) => {
+            result?;
+            Ok(ShutdownReport { in_flight_requests: 0 })
+        }
+        Err(_) => {
+            let in_flight_requests = in_flight.count();
+            tracing::warn!(
+                in_flight_requests,
+                "shutdown deadline reached, aborting in-flight requests"
+            );
+            // Dropping the server only stops it from driving the connections it has spawned, so
+            // the connection tasks, and the requests they are serving, are cancelled explicitly.
+            executor.abort_all();
+            Ok(ShutdownReport { in_flight_requests })
+        }
+    }
+}
+
+/// Spawns the connection tasks of the server on the Tokio runtime, and keeps a handle to each of
+/// them so that the ones still running at the shutdown deadline can be aborted.
+#[derive(Debug, Clone, Default)]
+struct AbortingExecutor(Arc<Mutex<Tasks>>);
+
+#[derive(Debug, Default)]
+struct Tasks {
+    next_id: u64,
+    running: HashMap<u64, tokio::task::JoinHandle<()>>,
+}
+
+impl AbortingExecutor {
+    fn abort_all(&self) {
+        for (_, task) in self.0.lock().unwrap().running.drain() {
+            task.abort();
+        }
+    }
+}
+
+impl<F> hyper::rt::Executor<F> for AbortingExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        // The lock is held while spawning so that the task cannot remove itself before it has
+        // been registered.
+        let mut tasks = self.0.lock().unwrap();
+        let id = tasks.next_id;
+        tasks.next_id += 1;
+        let registry = self.0.clone();
+        let task = tokio::spawn(async move {
+            fut.await;
+            registry.lock().unwrap().running.remove(&id);
+        });
+        tasks.running.insert(id, task);
+    }
+}
+
+/// Counter of the requests that are being processed.
+#[derive(Debug, Clone, Default)]
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn start(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+}
+
+/// Marks a request as in flight until it is dropped.
+#[derive(Debug)]
+struct InFlightGuard(InFlight);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        (self.0).0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a request as in flight until its response body has been fully sent, or the request has
+/// been aborted.
+#[derive(Debug, Clone)]
+struct InFlightService<S> {
+    inner: S,
+    in_flight: InFlight,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for InFlightService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = Response<InFlightBody<ResBody>>;
+    type Error = S::Error;
+    type Future = InFlightFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        InFlightFuture {
+            inner: self.inner.call(req),
+            guard: Some(self.in_flight.start()),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct InFlightFuture<F> {
+        #[pin]
+        inner: F,
+        guard: Option<InFlightGuard>,
+    }
+}
+
+impl<F, ResBody, E> Future for InFlightFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = Result<Response<InFlightBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = futures_util::ready!(this.inner.poll(cx))?;
+        let guard = this.guard.take();
+        Poll::Ready(Ok(response.map(|inner| InFlightBody { inner, _guard: guard })))
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct InFlightBody<B> {
+        #[pin]
+        inner: B,
+        _guard: Option<InFlightGuard>,
+    }
+}
+
+impl<B: HttpBody> HttpBody for InFlightBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.project().inner.poll_data(cx)
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+    use tower::util::BoxCloneService;
+    use tower::ServiceExt;
+
+    /// A service that streams a response whose second chunk is sent after `delay`.
+    fn slow_service(delay: Duration) -> BoxCloneService<Request<Body>, Response<Body>, Infallible> {
+        tower::service_fn(move |_req: Request<Body>| async move {
+            let chunks = futures_util::stream::unfold(0, move |sent| async move {
+                match sent {
+                    0 => Some((Ok::<_, Infallible>(Bytes::from_static(b"hello ")), 1)),
+                    1 => {
+                        tokio::time::sleep(delay).await;
+                        Some((Ok(Bytes::from_static(b"world")), 2))
+                    }
+                    _ => None,
+                }
+            });
+            Ok(Response::new(Body::wrap_stream(chunks)))
+        })
+        .boxed_clone()
+    }
+
+    /// Sends a request and waits for the beginning of the response to have been received.
+    async fn start_request(addr: SocketAddr) -> tokio::net::TcpStream {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0; 64];
+        let read = stream.read(&mut buf).await.unwrap();
+        assert!(buf[..read].starts_with(b"HTTP/1.1 200 OK"));
+        stream
+    }
+
+    async fn spawn_server(
+        delay: Duration,
+        deadline: Duration,
+    ) -> (
+        SocketAddr,
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<Result<ShutdownReport, hyper::Error>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            slow_service(delay),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            deadline,
+        ));
+        (addr, shutdown_tx, server)
+    }
+
+    #[tokio::test]
+    async fn drains_in_flight_requests() {
+        let (addr, shutdown_tx, server) = spawn_server(Duration::from_millis(200), Duration::from_secs(10)).await;
+
+        let mut stream = start_request(addr).await;
+        shutdown_tx.send(()).unwrap();
+
+        // The streaming response is completed even though the server is shutting down.
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(String::from_utf8(response).unwrap().contains("world"));
+
+        let report = server.await.unwrap().unwrap();
+        assert_eq!(0, report.in_flight_requests());
+    }
+
+    #[tokio::test]
+    async fn stops_accepting_connections() {
+        let (addr, shutdown_tx, server) = spawn_server(Duration::from_millis(0), Duration::from_secs(10)).await;
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn aborts_requests_in_flight_at_the_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Each handler hands over the receiving end of a channel whose sender it holds on to, so
+        // that the receiver is notified when the handler is dropped.
+        let (started_tx, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let hanging_service = tower::service_fn(move |_req: Request<Body>| {
+            let (dropped_tx, dropped_rx) = oneshot::channel::<()>();
+            started_tx.send(dropped_rx).unwrap();
+            async move {
+                let _dropped_tx = dropped_tx;
+                futures_util::future::pending::<Result<Response<Body>, Infallible>>().await
+            }
+        });
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            hanging_service,
+            async {
+                let _ = shutdown_rx.await;
+            },
+            Duration::from_millis(100),
+        ));
+
+        let mut connections = Vec::new();
+        let mut handlers = Vec::new();
+        for _ in 0..2 {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            handlers.push(started_rx.recv().await.unwrap());
+            connections.push(stream);
+        }
+        shutdown_tx.send(()).unwrap();
+
+        let report = server.await.unwrap().unwrap();
+        assert_eq!(2, report.in_flight_requests());
+        for handler in handlers {
+            let dropped = tokio::time::timeout(Duration::from_secs(5), handler).await;
+            assert!(matches!(dropped, Ok(Err(_))), "the handler should have been cancelled");
+        }
+        for mut stream in connections {
+            let mut response = Vec::new();
+            let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+            assert!(read.is_ok(), "the connection should have been closed");
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_requests_in_flight_at_the_deadline() {
+        let (addr, shutdown_tx, server) = spawn_server(Duration::from_secs(60), Duration::from_millis(100)).await;
+
+        let mut first = start_request(addr).await;
+        let _second = start_request(addr).await;
+        shutdown_tx.send(()).unwrap();
+
+        let report = server.await.unwrap().unwrap();
+        assert_eq!(2, report.in_flight_requests());
+        // The response being streamed is cut short rather than completed after the deadline.
+        let mut response = Vec::new();
+        let _ = tokio::time::timeout(Duration::from_secs(5), first.read_to_end(&mut response))
+            .await
+            .expect("the connection should have been closed");
+        assert!(!String::from_utf8_lossy(&response).contains("world"));
+    }
+
+    #[tokio::test]
+    async fn serves_a_router() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let report = serve(
+            listener,
+            crate::Router::<Body>::default(),
+            async {},
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(0, report.unwrap().in_flight_requests());
+    }
+
+    #[test]
+    fn traits() {
+        use crate::test_helpers::*;
+
+        assert_send::<ShutdownReport>();
+        assert_sync::<ShutdownReport>();
+    }
+}
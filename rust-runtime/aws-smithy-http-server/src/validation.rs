@@ -0,0 +1,389 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Validation of operation inputs against Smithy's [constraint traits].
+//!
+//! A [`Validator`] checks the members of a deserialized input against the `@required`, `@length`,
+//! `@range`, `@pattern` and `@uniqueItems` traits they are targeted by, and collects every violation
+//! into a [`ValidationException`]. Members are identified by their [JSON pointer] path from the root
+//! of the input, e.g. `/items/0/name`.
+//!
+//! Inputs implement [`Validate`] to describe their constraints, so that invalid input is rejected
+//! before it reaches an operation handler:
+//!
+//! ```rust,ignore
+//! impl Validate for PutItemInput {
+//!     fn validate(&self, path: &str, validator: &mut Validator) {
+//!         let name_path = member_path(path, "name");
+//!         validator.required(&name_path, self.name.as_ref());
+//!         if let Some(name) = &self.name {
+//!             validator.length(&name_path, name.chars().count(), Some(1), Some(64));
+//!         }
+//!         self.tags.validate(&member_path(path, "tags"), validator);
+//!     }
+//! }
+//!
+//! Validator::validate(&input).map_err(|exception| RuntimeError::new(Protocol::RestJson1, exception))?;
+//! ```
+//!
+//! [constraint traits]: https://awslabs.github.io/smithy/1.0/spec/core/constraint-traits.html
+//! [JSON pointer]: https://datatracker.ietf.org/doc/html/rfc6901
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// Returns the path of `member` within the structure, list or map at `parent`.
+///
+/// `~` and `/` in `member`, which can appear in map keys, are escaped as `~0` and `~1`.
+pub fn member_path(parent: &str, member: impl fmt::Display) -> String {
+    let member = member.to_string().replace('~', "~0").replace('/', "~1");
+    format!("{}/{}", parent, member)
+}
+
+/// Describes the constraints of a shape, checked with a [`Validator`].
+pub trait Validate {
+    /// Checks this value, found at `path`, recording the violations in `validator`.
+    fn validate(&self, path: &str, validator: &mut Validator);
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self, path: &str, validator: &mut Validator) {
+        if let Some(value) = self {
+            value.validate(path, validator);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self, path: &str, validator: &mut Validator) {
+        for (index, item) in self.iter().enumerate() {
+            item.validate(&member_path(path, index), validator);
+        }
+    }
+}
+
+/// A member that failed to satisfy a constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationExceptionField {
+    path: String,
+    message: String,
+}
+
+impl ValidationExceptionField {
+    /// Creates a new `ValidationExceptionField`.
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns the JSON pointer path of the member.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns a description of the constraint the member failed to satisfy.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The `smithy.framework#ValidationException` error, returned with a `400 Bad Request` when an input
+/// fails to satisfy its constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationException {
+    message: String,
+    field_list: Vec<ValidationExceptionField>,
+}
+
+impl ValidationException {
+    /// Creates a new `ValidationException` for the given violations.
+    pub fn new(field_list: Vec<ValidationExceptionField>) -> Self {
+        let plural = if field_list.len() == 1 { "" } else { "s" };
+        let messages: Vec<&str> = field_list.iter().map(ValidationExceptionField::message).collect();
+        let message = format!(
+            "{} validation error{} detected. {}",
+            field_list.len(),
+            plural,
+            messages.join("; ")
+        );
+        Self { message, field_list }
+    }
+
+    /// Returns a summary of all the violations.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the members that failed to satisfy a constraint.
+    pub fn field_list(&self) -> &[ValidationExceptionField] {
+        &self.field_list
+    }
+}
+
+impl fmt::Display for ValidationException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationException {}
+
+/// Collects constraint violations.
+///
+/// The messages follow the wording of the [`ValidationException`] messages of AWS services.
+#[derive(Debug, Default)]
+pub struct Validator {
+    field_list: Vec<ValidationExceptionField>,
+}
+
+impl Validator {
+    /// Creates a new `Validator`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates `input`, returning a [`ValidationException`] listing all of its violations, if any.
+    pub fn validate<T: Validate>(input: &T) -> Result<(), ValidationException> {
+        let mut validator = Validator::new();
+        input.validate("", &mut validator);
+        validator.finish()
+    }
+
+    /// Records a violation.
+    pub fn violation(&mut self, path: &str, message: impl Into<String>) {
+        self.field_list.push(ValidationExceptionField::new(path, message));
+    }
+
+    /// Checks a member targeted by the `@required` trait.
+    pub fn required<T>(&mut self, path: &str, value: Option<&T>) {
+        if value.is_none() {
+            self.violation(
+                path,
+                format!(
+                    "Value at '{}' failed to satisfy constraint: Member must not be null",
+                    path
+                ),
+            );
+        }
+    }
+
+    /// Checks the `length` of a member targeted by the `@length` trait. The length of strings is
+    /// measured in Unicode scalar values, the length of blobs in bytes, and the length of lists and
+    /// maps in entries.
+    pub fn length(&mut self, path: &str, length: usize, min: Option<usize>, max: Option<usize>) {
+        let too_short = matches!(min, Some(min) if length < min);
+        let too_long = matches!(max, Some(max) if length > max);
+        if too_short || too_long {
+            self.violation(
+                path,
+                format!(
+                    "Value with length {} at '{}' failed to satisfy constraint: Member must have length {}",
+                    length,
+                    path,
+                    bounds(min, max)
+                ),
+            );
+        }
+    }
+
+    /// Checks a member targeted by the `@range` trait.
+    pub fn range<N>(&mut self, path: &str, value: N, min: Option<N>, max: Option<N>)
+    where
+        N: PartialOrd + fmt::Display + Copy,
+    {
+        let too_small = matches!(min, Some(min) if value < min);
+        let too_large = matches!(max, Some(max) if value > max);
+        if too_small || too_large {
+            self.violation(
+                path,
+                format!(
+                    "Value {} at '{}' failed to satisfy constraint: Member must be {}",
+                    value,
+                    path,
+                    bounds(min, max)
+                ),
+            );
+        }
+    }
+
+    /// Checks a member targeted by the `@pattern` trait. As per the Smithy specification, the
+    /// pattern is not implicitly anchored.
+    pub fn pattern(&mut self, path: &str, value: &str, pattern: &Regex) {
+        if !pattern.is_match(value) {
+            self.violation(
+                path,
+                format!(
+                    "Value {} at '{}' failed to satisfy constraint: Member must satisfy regular expression pattern: {}",
+                    value,
+                    path,
+                    pattern.as_str()
+                ),
+            );
+        }
+    }
+
+    /// Checks a member targeted by the `@uniqueItems` trait.
+    pub fn unique_items<T: Hash + Eq>(&mut self, path: &str, items: &[T]) {
+        let mut occurrences: HashMap<&T, usize> = HashMap::with_capacity(items.len());
+        for item in items {
+            *occurrences.entry(item).or_default() += 1;
+        }
+        let repeated: Vec<String> = items
+            .iter()
+            .enumerate()
+            .filter(|(_index, item)| occurrences[item] > 1)
+            .map(|(index, _item)| index.to_string())
+            .collect();
+        if !repeated.is_empty() {
+            self.violation(
+                path,
+                format!(
+                    "Value with repeated values at indices [{}] at '{}' failed to satisfy constraint: Member must have unique values",
+                    repeated.join(", "),
+                    path
+                ),
+            );
+        }
+    }
+
+    /// Returns a [`ValidationException`] if any violation was recorded.
+    pub fn finish(self) -> Result<(), ValidationException> {
+        if self.field_list.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationException::new(self.field_list))
+        }
+    }
+}
+
+/// Describes the bounds of a `@length` or `@range` trait.
+fn bounds<N: fmt::Display>(min: Option<N>, max: Option<N>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("between {} and {}, inclusive", min, max),
+        (Some(min), None) => format!("greater than or equal to {}", min),
+        (None, Some(max)) => format!("less than or equal to {}", max),
+        (None, None) => unreachable!("constraint traits have at least one bound"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        name: Option<String>,
+        quantity: i32,
+    }
+
+    impl Validate for Item {
+        fn validate(&self, path: &str, validator: &mut Validator) {
+            let name_path = member_path(path, "name");
+            validator.required(&name_path, self.name.as_ref());
+            if let Some(name) = &self.name {
+                validator.length(&name_path, name.chars().count(), Some(1), Some(5));
+                validator.pattern(&name_path, name, &Regex::new("^[a-z]+$").unwrap());
+            }
+            validator.range(&member_path(path, "quantity"), self.quantity, Some(1), None);
+        }
+    }
+
+    struct Order {
+        items: Vec<Item>,
+        tags: Vec<String>,
+    }
+
+    impl Validate for Order {
+        fn validate(&self, path: &str, validator: &mut Validator) {
+            let items_path = member_path(path, "items");
+            validator.length(&items_path, self.items.len(), None, Some(2));
+            self.items.validate(&items_path, validator);
+            validator.unique_items(&member_path(path, "tags"), &self.tags);
+        }
+    }
+
+    fn item(name: Option<&str>, quantity: i32) -> Item {
+        Item {
+            name: name.map(String::from),
+            quantity,
+        }
+    }
+
+    #[test]
+    fn valid_input() {
+        let order = Order {
+            items: vec![item(Some("apple"), 1), item(Some("pear"), 10)],
+            tags: vec!["fruit".into(), "fresh".into()],
+        };
+        assert_eq!(Ok(()), Validator::validate(&order));
+    }
+
+    #[test]
+    fn violations_are_collected_with_their_path() {
+        let order = Order {
+            items: vec![item(None, 1), item(Some("Banana"), 0), item(Some("kiwi"), 1)],
+            tags: vec!["a".into(), "b".into(), "a".into()],
+        };
+        let exception = Validator::validate(&order).unwrap_err();
+
+        let paths: Vec<&str> = exception.field_list().iter().map(|field| field.path()).collect();
+        assert_eq!(
+            vec![
+                "/items",
+                "/items/0/name",
+                "/items/1/name",
+                "/items/1/name",
+                "/items/1/quantity",
+                "/tags"
+            ],
+            paths
+        );
+        let messages: Vec<&str> = exception.field_list().iter().map(|field| field.message()).collect();
+        assert_eq!(
+            vec![
+                "Value with length 3 at '/items' failed to satisfy constraint: Member must have length less than or equal to 2",
+                "Value at '/items/0/name' failed to satisfy constraint: Member must not be null",
+                "Value with length 6 at '/items/1/name' failed to satisfy constraint: Member must have length between 1 and 5, inclusive",
+                "Value Banana at '/items/1/name' failed to satisfy constraint: Member must satisfy regular expression pattern: ^[a-z]+$",
+                "Value 0 at '/items/1/quantity' failed to satisfy constraint: Member must be greater than or equal to 1",
+                "Value with repeated values at indices [0, 2] at '/tags' failed to satisfy constraint: Member must have unique values",
+            ],
+            messages
+        );
+        assert!(exception
+            .message()
+            .starts_with("6 validation errors detected. Value with length 3"));
+    }
+
+    #[test]
+    fn single_violation_message() {
+        let mut validator = Validator::new();
+        validator.range("/ratio", 1.5, Some(0.0), Some(1.0));
+        assert_eq!(
+            "1 validation error detected. Value 1.5 at '/ratio' failed to satisfy constraint: Member must be between 0 and 1, inclusive",
+            validator.finish().unwrap_err().message()
+        );
+    }
+
+    #[test]
+    fn map_keys_are_escaped_in_paths() {
+        assert_eq!("/tags/a~1b", member_path("/tags", "a/b"));
+        assert_eq!("/tags/~0home~01", member_path("/tags", "~home~1"));
+        assert_eq!("/items/0", member_path("/items", 0));
+    }
+
+    #[test]
+    fn repeated_items_are_reported_in_order() {
+        let mut validator = Validator::new();
+        validator.unique_items("/ids", &[3, 1, 3, 2, 1, 4]);
+        assert_eq!(
+            "Value with repeated values at indices [0, 1, 2, 4] at '/ids' failed to satisfy constraint: Member must have unique values",
+            validator.finish().unwrap_err().field_list()[0].message()
+        );
+    }
+}
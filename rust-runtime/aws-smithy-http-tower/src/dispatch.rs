@@ -9,8 +9,9 @@ use aws_smithy_http::operation;
 use aws_smithy_http::result::ConnectorError;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use tower::{Layer, Service};
+use tower::{BoxError, Layer, Service};
 use tracing::trace;
 
 /// Connects Operation driven middleware to an HTTP implementation.
@@ -20,8 +21,16 @@ use tracing::trace;
 #[derive(Clone)]
 pub struct DispatchService<S> {
     inner: S,
+    before_dispatch: Option<BeforeDispatch>,
 }
 
+/// A hook called with every request right before it is dispatched
+///
+/// If the hook returns an error, the request is not dispatched and fails with a
+/// [`SendOperationError::RequestConstructionError`].
+pub type BeforeDispatch =
+    Arc<dyn Fn(&operation::Request) -> Result<(), BoxError> + Send + Sync + 'static>;
+
 type BoxedResultFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
 
 impl<S> Service<operation::Request> for DispatchService<S>
@@ -41,6 +50,13 @@ where
     }
 
     fn call(&mut self, req: operation::Request) -> Self::Future {
+        if let Some(before_dispatch) = &self.before_dispatch {
+            if let Err(err) = before_dispatch(&req) {
+                return Box::pin(
+                    async move { Err(SendOperationError::RequestConstructionError(err)) },
+                );
+            }
+        }
         let (req, property_bag) = req.into_parts();
         let mut inner = self.inner.clone();
         let future = async move {
@@ -57,11 +73,19 @@ where
 
 #[derive(Clone, Default)]
 #[non_exhaustive]
-pub struct DispatchLayer;
+pub struct DispatchLayer {
+    before_dispatch: Option<BeforeDispatch>,
+}
 
 impl DispatchLayer {
     pub fn new() -> Self {
-        DispatchLayer
+        DispatchLayer::default()
+    }
+
+    /// Call `before_dispatch` with every request right before it is dispatched
+    pub fn with_before_dispatch(mut self, before_dispatch: Option<BeforeDispatch>) -> Self {
+        self.before_dispatch = before_dispatch;
+        self
     }
 }
 
@@ -72,6 +96,9 @@ where
     type Service = DispatchService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        DispatchService { inner }
+        DispatchService {
+            inner,
+            before_dispatch: self.before_dispatch.clone(),
+        }
     }
 }
@@ -104,7 +104,7 @@ mod tests {
         let mut svc = ServiceBuilder::new()
             .layer(ParseResponseLayer::<TestParseResponse, ()>::new())
             .layer(MapRequestLayer::for_mapper(AddHeader))
-            .layer(DispatchLayer)
+            .layer(DispatchLayer::new())
             .service(http_layer);
         let req = http::Request::new(SdkBody::from("hello"));
         let req = operation::Request::new(req);
@@ -210,9 +210,14 @@ impl http_body::Body for SdkBody {
 
     fn poll_trailers(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
-        Poll::Ready(Ok(None))
+        match self.project().inner.project() {
+            InnerProj::Once(_) => Poll::Ready(Ok(None)),
+            InnerProj::Streaming(body) => body.poll_trailers(cx).map_err(|e| e.into()),
+            InnerProj::Dyn(box_body) => box_body.poll_trailers(cx),
+            InnerProj::Taken => Poll::Ready(Err("A `Taken` body should never be polled".into())),
+        }
     }
 
     fn is_end_stream(&self) -> bool {
@@ -264,6 +269,19 @@ mod test {
         assert!(data.is_none());
     }
 
+    #[tokio::test]
+    async fn streaming_body_returns_trailers() {
+        let (mut sender, body) = hyper::Body::channel();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("x-checksum", http::HeaderValue::from_static("1234"));
+        sender.send_trailers(trailers.clone()).await.unwrap();
+        drop(sender);
+        let mut body = SdkBody::from(body);
+        let mut body = Pin::new(&mut body);
+        assert!(body.data().await.is_none());
+        assert_eq!(Some(trailers), body.trailers().await.unwrap());
+    }
+
     #[tokio::test]
     async fn empty_body_returns_none() {
         // Its important to avoid sending empty chunks of data to avoid H2 data frame problems
@@ -163,6 +163,12 @@ mod bytestream_util;
 ///     }
 ///     ```
 ///
+/// 3. With `rt-tokio` enabled, via [`.into_async_read()`](crate::byte_stream::ByteStream::into_async_read),
+/// which bridges the ByteStream to [`tokio::io::AsyncRead`], or by copying it into a
+/// [`tokio::io::AsyncWrite`] with [`.write_to()`](crate::byte_stream::ByteStream::write_to).
+/// Conversely, [`ByteStream::pipe`](crate::byte_stream::ByteStream::pipe) creates a ByteStream
+/// written to through a [`tokio::io::AsyncWrite`].
+///
 /// ## Getting data into a ByteStream
 /// ByteStreams can be created in one of three ways:
 /// 1. **From in-memory binary data**: ByteStreams created from in-memory data are always retryable. Data
@@ -183,6 +189,9 @@ mod bytestream_util;
 ///     # }
 ///     ```
 ///
+///     A range of a file can be streamed with [`ByteStream::from_path_range`](ByteStream::from_path_range), and
+///     [`ByteStream::from_path_parts`](ByteStream::from_path_parts) splits a file into parts for multipart uploads.
+///
 /// 3. **From an `SdkBody` directly**: For more advanced / custom use cases, a ByteStream can be created directly
 /// from an SdkBody. **When created from an SdkBody, care must be taken to ensure retriability.** An SdkBody is retryable
 /// when constructed from in-memory data or when using [`SdkBody::retryable`](crate::body::SdkBody::retryable).
@@ -276,6 +285,105 @@ impl ByteStream {
         Ok(ByteStream::new(SdkBody::retryable(body_loader)))
     }
 
+    /// Create a ByteStream that streams a range of a file from the filesystem
+    ///
+    /// The ByteStream streams `length` bytes of the file at `path`, starting at `offset`. If the
+    /// file ends before `offset + length`, the ByteStream ends with the file. Like
+    /// [`ByteStream::from_path`](ByteStream::from_path), the returned ByteStream is retryable and
+    /// provides an exact size hint. It is an error for `offset` to be past the end of the file.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use aws_smithy_http::byte_stream::ByteStream;
+    /// async fn second_mebibyte() -> ByteStream {
+    ///     ByteStream::from_path_range("docs/rows.csv", 1024 * 1024, 1024 * 1024)
+    ///         .await
+    ///         .expect("file should be readable")
+    /// }
+    /// ```
+    #[cfg(feature = "rt-tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rt-tokio")))]
+    pub async fn from_path_range(
+        path: impl AsRef<std::path::Path>,
+        offset: u64,
+        length: u64,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file_len = tokio::fs::metadata(path)
+            .await
+            .map_err(|err| Error(err.into()))?
+            .len();
+        if offset > file_len {
+            return Err(Error(
+                format!(
+                    "offset {} is past the end of {} ({} bytes)",
+                    offset,
+                    path.display(),
+                    file_len
+                )
+                .into(),
+            ));
+        }
+        Ok(Self::path_range(
+            path.to_path_buf(),
+            offset,
+            length.min(file_len - offset),
+        ))
+    }
+
+    /// Split a file into retryable ByteStreams of `part_size` bytes
+    ///
+    /// Every part except the last one contains exactly `part_size` bytes. An empty file results in
+    /// a single empty part. This is intended for multipart uploads, where each part is uploaded
+    /// (and possibly retried) independently.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use aws_smithy_http::byte_stream::ByteStream;
+    /// async fn upload_parts() {
+    ///     let parts = ByteStream::from_path_parts("docs/rows.csv", 5 * 1024 * 1024)
+    ///         .await
+    ///         .expect("file should be readable");
+    ///     for (part_number, part) in parts.into_iter().enumerate() {
+    ///         // upload `part` as part `part_number + 1`...
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "rt-tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rt-tokio")))]
+    pub async fn from_path_parts(
+        path: impl AsRef<std::path::Path>,
+        part_size: u64,
+    ) -> Result<Vec<Self>, Error> {
+        if part_size == 0 {
+            return Err(Error("part size must be greater than zero".into()));
+        }
+        let path = path.as_ref();
+        let file_len = tokio::fs::metadata(path)
+            .await
+            .map_err(|err| Error(err.into()))?
+            .len();
+        if file_len == 0 {
+            return Ok(vec![Self::path_range(path.to_path_buf(), 0, 0)]);
+        }
+        Ok((0..file_len)
+            .step_by(part_size as usize)
+            .map(|offset| {
+                Self::path_range(path.to_path_buf(), offset, part_size.min(file_len - offset))
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    fn path_range(path: std::path::PathBuf, offset: u64, len: u64) -> Self {
+        let body_loader = move || {
+            SdkBody::from_dyn(http_body::combinators::BoxBody::new(
+                bytestream_util::PathBody::from_path_range(path.as_path(), offset, len),
+            ))
+        };
+        ByteStream::new(SdkBody::retryable(body_loader))
+    }
+
     /// Create a ByteStream from a file
     ///
     /// NOTE: This will NOT result in a retryable ByteStream. For a ByteStream that can be retried in the case of
@@ -293,6 +401,87 @@ impl ByteStream {
         ));
         Ok(ByteStream::new(body))
     }
+
+    /// Create a ByteStream whose contents are written into the returned [`tokio::io::AsyncWrite`]
+    ///
+    /// The writer buffers up to `max_buf_size` bytes that were not read from the ByteStream yet,
+    /// and waits for the ByteStream to be read once the buffer is full. The ByteStream ends once the
+    /// writer is shut down or dropped. Writes fail once the ByteStream is dropped.
+    ///
+    /// NOTE: This will NOT result in a retryable ByteStream, and its size is unknown.
+    ///
+    /// ```no_run
+    /// use aws_smithy_http::byte_stream::ByteStream;
+    /// use tokio::io::AsyncWriteExt;
+    /// async fn compressed_upload(
+    ///     upload: impl FnOnce(ByteStream) -> tokio::task::JoinHandle<()>,
+    /// ) -> std::io::Result<()> {
+    ///     let (mut writer, stream) = ByteStream::pipe(64 * 1024);
+    ///     let uploaded = upload(stream);
+    ///     writer.write_all(b"hello world!").await?;
+    ///     writer.shutdown().await?;
+    ///     uploaded.await.expect("upload should not panic");
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "rt-tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rt-tokio")))]
+    pub fn pipe(
+        max_buf_size: usize,
+    ) -> (impl tokio::io::AsyncWrite + Send + Sync + Unpin, ByteStream) {
+        let (writer, reader) = tokio::io::duplex(max_buf_size);
+        let body = SdkBody::from_dyn(http_body::combinators::BoxBody::new(
+            bytestream_util::ReaderBody::new(reader),
+        ));
+        (writer, ByteStream::new(body))
+    }
+
+    /// Convert this ByteStream into a [`tokio::io::AsyncRead`]
+    ///
+    /// Errors in the underlying stream are returned as [`std::io::Error`]s.
+    ///
+    /// ```no_run
+    /// use aws_smithy_http::byte_stream::ByteStream;
+    /// use tokio::io::AsyncBufReadExt;
+    /// async fn count_lines(stream: ByteStream) -> std::io::Result<usize> {
+    ///     let mut lines = tokio::io::BufReader::new(stream.into_async_read()).lines();
+    ///     let mut count = 0;
+    ///     while lines.next_line().await?.is_some() {
+    ///         count += 1;
+    ///     }
+    ///     Ok(count)
+    /// }
+    /// ```
+    #[cfg(feature = "rt-tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rt-tokio")))]
+    pub fn into_async_read(self) -> impl tokio::io::AsyncRead + Send {
+        tokio_util::io::StreamReader::new(self)
+    }
+
+    /// Write the contents of this ByteStream into a [`tokio::io::AsyncWrite`], chunk by chunk
+    ///
+    /// Unlike [`collect`](ByteStream::collect), this never holds more than one chunk in memory.
+    /// Returns the number of bytes written. The writer is flushed but not shut down.
+    ///
+    /// ```no_run
+    /// use aws_smithy_http::byte_stream::{ByteStream, Error};
+    /// async fn stream_to_file(stream: ByteStream) -> Result<u64, Error> {
+    ///     let mut file = tokio::fs::File::create("audio.mp3").await?;
+    ///     stream.write_to(&mut file).await
+    /// }
+    /// ```
+    #[cfg(feature = "rt-tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rt-tokio")))]
+    pub async fn write_to<W>(self, writer: &mut W) -> Result<u64, Error>
+    where
+        W: tokio::io::AsyncWrite + Unpin + ?Sized,
+    {
+        let reader = self.into_async_read();
+        crate::pin_mut!(reader);
+        tokio::io::copy(&mut reader, writer)
+            .await
+            .map_err(|err| Error(err.into()))
+    }
 }
 
 impl Default for ByteStream {
@@ -349,6 +538,33 @@ impl StdError for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error(err.into())
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        match err.0.downcast::<std::io::Error>() {
+            Ok(err) => *err,
+            Err(err) => std::io::Error::new(io_error_kind(err.as_ref()), err),
+        }
+    }
+}
+
+/// Kind of the first I/O error in the source chain of `err`
+fn io_error_kind(err: &(dyn StdError + 'static)) -> std::io::ErrorKind {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return err.kind();
+        }
+        source = err.source();
+    }
+    std::io::ErrorKind::Other
+}
+
 impl futures_core::stream::Stream for ByteStream {
     type Item = Result<Bytes, Error>;
 
@@ -535,4 +751,123 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "rt-tokio")]
+    fn numbered_file() -> Result<tempfile::NamedTempFile, Box<dyn std::error::Error>> {
+        use std::io::Write;
+        let mut file = tempfile::NamedTempFile::new()?;
+        for i in 0..1000 {
+            write!(file, "{:04}", i)?;
+        }
+        Ok(file)
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn path_range_bytestreams() -> Result<(), Box<dyn std::error::Error>> {
+        use super::ByteStream;
+        use http_body::Body;
+        let file = numbered_file()?;
+
+        let body = ByteStream::from_path_range(&file, 40, 12)
+            .await?
+            .into_inner();
+        assert_eq!(body.size_hint().exact(), Some(12));
+        let retry = body.try_clone().expect("retryable bodies are cloneable");
+        assert_eq!(
+            ByteStream::new(body).collect().await?.into_bytes(),
+            Bytes::from("001000110012")
+        );
+        assert_eq!(
+            ByteStream::new(retry).collect().await?.into_bytes(),
+            Bytes::from("001000110012")
+        );
+
+        // ranges are truncated at the end of the file
+        let body = ByteStream::from_path_range(&file, 3992, 100)
+            .await?
+            .into_inner();
+        assert_eq!(body.size_hint().exact(), Some(8));
+        assert_eq!(
+            ByteStream::new(body).collect().await?.into_bytes(),
+            Bytes::from("09980999")
+        );
+
+        assert!(ByteStream::from_path_range(&file, 4001, 1).await.is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn path_parts_cover_the_whole_file() -> Result<(), Box<dyn std::error::Error>> {
+        use super::ByteStream;
+        use http_body::Body;
+        let file = numbered_file()?;
+
+        let parts = ByteStream::from_path_parts(&file, 1500).await?;
+        let sizes: Vec<_> = parts
+            .iter()
+            .map(|part| part.0.body.size_hint().exact())
+            .collect();
+        assert_eq!(vec![Some(1500), Some(1500), Some(1000)], sizes);
+        let mut contents = Vec::new();
+        for part in parts {
+            contents.extend_from_slice(&part.collect().await?.into_bytes());
+        }
+        assert_eq!(contents, std::fs::read(&file)?);
+
+        let empty = tempfile::NamedTempFile::new()?;
+        let parts = ByteStream::from_path_parts(&empty, 1500).await?;
+        assert_eq!(1, parts.len());
+        assert!(ByteStream::from_path_parts(&file, 0).await.is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn async_read_and_write_bridges() -> Result<(), Box<dyn std::error::Error>> {
+        use super::ByteStream;
+        use crate::body::SdkBody;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut sender, body) = hyper::Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("data 1")).await.unwrap();
+            sender.send_data(Bytes::from("data 2")).await.unwrap();
+        });
+        let mut contents = String::new();
+        ByteStream::from(body)
+            .into_async_read()
+            .read_to_string(&mut contents)
+            .await?;
+        assert_eq!("data 1data 2", contents);
+
+        let mut output = Vec::new();
+        let written = ByteStream::from_static(b"hello world!")
+            .write_to(&mut output)
+            .await?;
+        assert_eq!(12, written);
+        assert_eq!(b"hello world!", &output[..]);
+
+        // errors surface as io errors
+        let (sender, body) = hyper::Body::channel();
+        sender.abort();
+        let mut reader = ByteStream::new(SdkBody::from(body)).into_async_read();
+        assert!(reader.read_to_end(&mut Vec::new()).await.is_err());
+
+        let (mut writer, stream) = ByteStream::pipe(4);
+        let written = tokio::spawn(async move {
+            writer.write_all(b"hello world!").await?;
+            writer.shutdown().await
+        });
+        let collected = stream.collect().await?.into_bytes();
+        written.await??;
+        assert_eq!(Bytes::from_static(b"hello world!"), collected);
+
+        // writes fail once the stream is dropped
+        let (mut writer, stream) = ByteStream::pipe(4);
+        drop(stream);
+        assert!(writer.write_all(b"hello world!").await.is_err());
+        Ok(())
+    }
 }
@@ -8,11 +8,12 @@ use futures_core::{ready, Stream};
 use http::HeaderMap;
 use http_body::{Body, SizeHint};
 use std::future::Future;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::fs::File;
-use tokio::io;
+use tokio::io::{self, AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
 
 /// An HTTP Body designed to wrap files
@@ -21,6 +22,9 @@ use tokio_util::io::ReaderStream;
 /// 1. The underlying file is wrapped with StreamReader to implement HTTP body
 /// 2. It can be constructed directly from a path so it's easy to use during retries
 /// 3. Provide size hint
+///
+/// A PathBody may also cover a range of the file, which is how multipart uploads stream each part
+/// from the same file.
 pub struct PathBody {
     state: State,
     len: u64,
@@ -28,8 +32,15 @@ pub struct PathBody {
 
 impl PathBody {
     pub fn from_path(path: &Path, len: u64) -> Self {
+        Self::from_path_range(path, 0, len)
+    }
+    /// Create a body that streams `len` bytes of the file at `path`, starting at `offset`
+    pub fn from_path_range(path: &Path, offset: u64, len: u64) -> Self {
         PathBody {
-            state: State::Unloaded(path.to_path_buf()),
+            state: State::Unloaded {
+                path: path.to_path_buf(),
+                offset,
+            },
             len,
         }
     }
@@ -42,9 +53,14 @@ impl PathBody {
 }
 
 enum State {
-    Unloaded(PathBuf),
+    Unloaded {
+        path: PathBuf,
+        offset: u64,
+    },
     Loading(Pin<Box<dyn Future<Output = io::Result<File>> + Send + Sync + 'static>>),
-    Loaded(tokio_util::io::ReaderStream<File>),
+    Loaded(ReaderStream<File>),
+    /// A range of a file, limited to the length of the body
+    LoadedRange(ReaderStream<io::Take<File>>),
 }
 
 impl Body for PathBody {
@@ -57,28 +73,27 @@ impl Body for PathBody {
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
         loop {
             match self.state {
-                State::Unloaded(ref path_buf) => {
-                    let buf = path_buf.clone();
+                State::Unloaded { ref path, offset } => {
+                    let buf = path.clone();
                     self.state = State::Loading(Box::pin(async move {
-                        let file = tokio::fs::File::open(&buf).await?;
+                        let mut file = tokio::fs::File::open(&buf).await?;
+                        if offset > 0 {
+                            file.seek(SeekFrom::Start(offset)).await?;
+                        }
                         Ok(file)
                     }));
                 }
                 State::Loading(ref mut future) => {
                     match ready!(Pin::new(future).poll(cx)) {
                         Ok(file) => {
-                            self.state = State::Loaded(ReaderStream::new(file));
+                            let len = self.len;
+                            self.state = State::LoadedRange(ReaderStream::new(file.take(len)));
                         }
                         Err(e) => return Poll::Ready(Some(Err(e.into()))),
                     };
                 }
-                State::Loaded(ref mut stream) => {
-                    return match ready!(Pin::new(stream).poll_next(cx)) {
-                        Some(Ok(bytes)) => Poll::Ready(Some(Ok(bytes))),
-                        None => Poll::Ready(None),
-                        Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
-                    }
-                }
+                State::Loaded(ref mut stream) => return poll_chunk(Pin::new(stream), cx),
+                State::LoadedRange(ref mut stream) => return poll_chunk(Pin::new(stream), cx),
             };
         }
     }
@@ -99,3 +114,52 @@ impl Body for PathBody {
         SizeHint::with_exact(self.len)
     }
 }
+
+/// An HTTP Body streaming the contents of an [`AsyncRead`](tokio::io::AsyncRead) of unknown size
+pub struct ReaderBody<R> {
+    stream: ReaderStream<R>,
+}
+
+impl<R: io::AsyncRead> ReaderBody<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderBody {
+            stream: ReaderStream::new(reader),
+        }
+    }
+}
+
+impl<R> Body for ReaderBody<R>
+where
+    R: io::AsyncRead + Unpin,
+{
+    type Data = Bytes;
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        poll_chunk(Pin::new(&mut self.stream), cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+fn poll_chunk<S>(
+    stream: Pin<&mut S>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<Result<Bytes, Box<dyn std::error::Error + Send + Sync + 'static>>>>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    match ready!(stream.poll_next(cx)) {
+        Some(Ok(bytes)) => Poll::Ready(Some(Ok(bytes))),
+        None => Poll::Ready(None),
+        Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
+    }
+}
@@ -193,6 +193,10 @@ impl<H, R> Operation<H, R> {
         self
     }
 
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.parts.metadata.as_ref()
+    }
+
     pub fn with_retry_policy<R2>(self, retry_policy: R2) -> Operation<H, R2> {
         Operation {
             request: self.request,
@@ -208,6 +212,10 @@ impl<H, R> Operation<H, R> {
         &self.parts.retry_policy
     }
 
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
     pub fn try_clone(&self) -> Option<Self>
     where
         H: Clone,
@@ -93,7 +93,7 @@ pub enum RetryMode {
     Adaptive,
 }
 
-const VALID_RETRY_MODES: &[RetryMode] = &[RetryMode::Standard];
+const VALID_RETRY_MODES: &[RetryMode] = &[RetryMode::Standard, RetryMode::Adaptive];
 
 /// Failure to parse a `RetryMode` from string.
 #[derive(Debug)]
@@ -119,21 +119,46 @@ impl FromStr for RetryMode {
         // eq_ignore_ascii_case is OK here because the only strings we need to check for are ASCII
         if string.eq_ignore_ascii_case("standard") {
             Ok(RetryMode::Standard)
-        // TODO(https://github.com/awslabs/aws-sdk-rust/issues/247): adaptive retries
-        // } else if string.eq_ignore_ascii_case("adaptive") {
-        //     Ok(RetryMode::Adaptive)
+        } else if string.eq_ignore_ascii_case("adaptive") {
+            Ok(RetryMode::Adaptive)
         } else {
             Err(RetryModeParseErr(string.to_owned()))
         }
     }
 }
 
+/// How long to wait before retrying a request, as a function of the number of attempts made.
+///
+/// Jittered backoffs are computed from a random factor `b` between 0 and 1, and are bounded by the
+/// maximum backoff of the retry policy.
+#[non_exhaustive]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum BackoffStrategy {
+    /// Wait for `b * 2^(attempt - 1)` seconds. This is the default.
+    FullJitter,
+
+    /// Wait for half of `2^(attempt - 1)` seconds, plus `b` times the other half.
+    EqualJitter,
+
+    /// Wait for a random duration between one second and three times the previous backoff.
+    DecorrelatedJitter,
+
+    /// Always wait for the given duration, regardless of the maximum backoff.
+    Fixed(Duration),
+}
+
 /// Builder for [`RetryConfig`].
 #[non_exhaustive]
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct RetryConfigBuilder {
     mode: Option<RetryMode>,
     max_attempts: Option<u32>,
+    initial_retry_tokens: Option<usize>,
+    retry_cost: Option<usize>,
+    timeout_retry_cost: Option<usize>,
+    no_retry_increment: Option<usize>,
+    max_backoff: Option<Duration>,
+    backoff_strategy: Option<BackoffStrategy>,
 }
 
 impl RetryConfigBuilder {
@@ -166,6 +191,78 @@ impl RetryConfigBuilder {
         self
     }
 
+    /// Sets the retry quota each retry partition starts with.
+    pub fn set_initial_retry_tokens(&mut self, initial_retry_tokens: Option<usize>) -> &mut Self {
+        self.initial_retry_tokens = initial_retry_tokens;
+        self
+    }
+
+    /// Sets the retry quota each retry partition starts with.
+    pub fn initial_retry_tokens(mut self, initial_retry_tokens: usize) -> Self {
+        self.set_initial_retry_tokens(Some(initial_retry_tokens));
+        self
+    }
+
+    /// Sets the retry quota consumed by retrying a request.
+    pub fn set_retry_cost(&mut self, retry_cost: Option<usize>) -> &mut Self {
+        self.retry_cost = retry_cost;
+        self
+    }
+
+    /// Sets the retry quota consumed by retrying a request.
+    pub fn retry_cost(mut self, retry_cost: usize) -> Self {
+        self.set_retry_cost(Some(retry_cost));
+        self
+    }
+
+    /// Sets the retry quota consumed by retrying a request that failed with a transient error.
+    pub fn set_timeout_retry_cost(&mut self, timeout_retry_cost: Option<usize>) -> &mut Self {
+        self.timeout_retry_cost = timeout_retry_cost;
+        self
+    }
+
+    /// Sets the retry quota consumed by retrying a request that failed with a transient error.
+    pub fn timeout_retry_cost(mut self, timeout_retry_cost: usize) -> Self {
+        self.set_timeout_retry_cost(Some(timeout_retry_cost));
+        self
+    }
+
+    /// Sets the retry quota given back by a request that succeeded on its first attempt.
+    pub fn set_no_retry_increment(&mut self, no_retry_increment: Option<usize>) -> &mut Self {
+        self.no_retry_increment = no_retry_increment;
+        self
+    }
+
+    /// Sets the retry quota given back by a request that succeeded on its first attempt.
+    pub fn no_retry_increment(mut self, no_retry_increment: usize) -> Self {
+        self.set_no_retry_increment(Some(no_retry_increment));
+        self
+    }
+
+    /// Sets the maximum time to wait before retrying a request.
+    pub fn set_max_backoff(&mut self, max_backoff: Option<Duration>) -> &mut Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the maximum time to wait before retrying a request.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.set_max_backoff(Some(max_backoff));
+        self
+    }
+
+    /// Sets the backoff strategy.
+    pub fn set_backoff_strategy(&mut self, backoff_strategy: Option<BackoffStrategy>) -> &mut Self {
+        self.backoff_strategy = backoff_strategy;
+        self
+    }
+
+    /// Sets the backoff strategy.
+    pub fn backoff_strategy(mut self, backoff_strategy: BackoffStrategy) -> Self {
+        self.set_backoff_strategy(Some(backoff_strategy));
+        self
+    }
+
     /// Merge two builders together. Values from `other` will only be used as a fallback for values
     /// from `self` Useful for merging configs from different sources together when you want to
     /// handle "precedence" per value instead of at the config level
@@ -186,6 +283,12 @@ impl RetryConfigBuilder {
         Self {
             mode: self.mode.or(other.mode),
             max_attempts: self.max_attempts.or(other.max_attempts),
+            initial_retry_tokens: self.initial_retry_tokens.or(other.initial_retry_tokens),
+            retry_cost: self.retry_cost.or(other.retry_cost),
+            timeout_retry_cost: self.timeout_retry_cost.or(other.timeout_retry_cost),
+            no_retry_increment: self.no_retry_increment.or(other.no_retry_increment),
+            max_backoff: self.max_backoff.or(other.max_backoff),
+            backoff_strategy: self.backoff_strategy.or(other.backoff_strategy),
         }
     }
 
@@ -194,16 +297,31 @@ impl RetryConfigBuilder {
         RetryConfig {
             mode: self.mode.unwrap_or(RetryMode::Standard),
             max_attempts: self.max_attempts.unwrap_or(3),
+            initial_retry_tokens: self.initial_retry_tokens,
+            retry_cost: self.retry_cost,
+            timeout_retry_cost: self.timeout_retry_cost,
+            no_retry_increment: self.no_retry_increment,
+            max_backoff: self.max_backoff,
+            backoff_strategy: self.backoff_strategy,
         }
     }
 }
 
 /// Retry configuration for requests.
+///
+/// The retry quota, backoff and backoff strategy settings are optional: when they are unset, the
+/// retry policy uses its own defaults.
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
 pub struct RetryConfig {
     mode: RetryMode,
     max_attempts: u32,
+    initial_retry_tokens: Option<usize>,
+    retry_cost: Option<usize>,
+    timeout_retry_cost: Option<usize>,
+    no_retry_increment: Option<usize>,
+    max_backoff: Option<Duration>,
+    backoff_strategy: Option<BackoffStrategy>,
 }
 
 impl RetryConfig {
@@ -234,10 +352,77 @@ impl RetryConfig {
         self.mode
     }
 
+    /// Changes the retry quota each retry partition starts with.
+    pub fn with_initial_retry_tokens(mut self, initial_retry_tokens: usize) -> Self {
+        self.initial_retry_tokens = Some(initial_retry_tokens);
+        self
+    }
+
+    /// Changes the retry quota consumed by retrying a request.
+    pub fn with_retry_cost(mut self, retry_cost: usize) -> Self {
+        self.retry_cost = Some(retry_cost);
+        self
+    }
+
+    /// Changes the retry quota consumed by retrying a request that failed with a transient error.
+    pub fn with_timeout_retry_cost(mut self, timeout_retry_cost: usize) -> Self {
+        self.timeout_retry_cost = Some(timeout_retry_cost);
+        self
+    }
+
+    /// Changes the retry quota given back by a request that succeeded on its first attempt.
+    pub fn with_no_retry_increment(mut self, no_retry_increment: usize) -> Self {
+        self.no_retry_increment = Some(no_retry_increment);
+        self
+    }
+
+    /// Changes the maximum time to wait before retrying a request.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = Some(max_backoff);
+        self
+    }
+
+    /// Changes the backoff strategy.
+    pub fn with_backoff_strategy(mut self, backoff_strategy: BackoffStrategy) -> Self {
+        self.backoff_strategy = Some(backoff_strategy);
+        self
+    }
+
     /// Returns the max attempts.
     pub fn max_attempts(&self) -> u32 {
         self.max_attempts
     }
+
+    /// Returns the retry quota each retry partition starts with, if set.
+    pub fn initial_retry_tokens(&self) -> Option<usize> {
+        self.initial_retry_tokens
+    }
+
+    /// Returns the retry quota consumed by retrying a request, if set.
+    pub fn retry_cost(&self) -> Option<usize> {
+        self.retry_cost
+    }
+
+    /// Returns the retry quota consumed by retrying a request that failed with a transient error,
+    /// if set.
+    pub fn timeout_retry_cost(&self) -> Option<usize> {
+        self.timeout_retry_cost
+    }
+
+    /// Returns the retry quota given back by a request that succeeded on its first attempt, if set.
+    pub fn no_retry_increment(&self) -> Option<usize> {
+        self.no_retry_increment
+    }
+
+    /// Returns the maximum time to wait before retrying a request, if set.
+    pub fn max_backoff(&self) -> Option<Duration> {
+        self.max_backoff
+    }
+
+    /// Returns the backoff strategy, if set.
+    pub fn backoff_strategy(&self) -> Option<BackoffStrategy> {
+        self.backoff_strategy
+    }
 }
 
 impl Default for RetryConfig {
@@ -245,6 +430,12 @@ impl Default for RetryConfig {
         Self {
             mode: RetryMode::Standard,
             max_attempts: 3,
+            initial_retry_tokens: None,
+            retry_cost: None,
+            timeout_retry_cost: None,
+            no_retry_increment: None,
+            max_backoff: None,
+            backoff_strategy: None,
         }
     }
 }
@@ -316,8 +507,9 @@ impl std::error::Error for RetryConfigErr {
 
 #[cfg(test)]
 mod tests {
-    use crate::retry::{RetryConfigBuilder, RetryMode};
+    use crate::retry::{BackoffStrategy, RetryConfigBuilder, RetryMode};
     use std::str::FromStr;
+    use std::time::Duration;
 
     #[test]
     fn retry_config_builder_merge_with_favors_self_values_over_other_values() {
@@ -347,18 +539,18 @@ mod tests {
             RetryMode::from_str("StAnDaRd").ok(),
             Some(RetryMode::Standard)
         );
-        // assert_eq!(
-        //     RetryMode::from_str("adaptive").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("ADAPTIVE").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("aDaPtIvE").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
+        assert_eq!(
+            RetryMode::from_str("adaptive").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("ADAPTIVE").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("aDaPtIvE").ok(),
+            Some(RetryMode::Adaptive)
+        );
     }
 
     #[test]
@@ -375,18 +567,18 @@ mod tests {
             RetryMode::from_str("  StAnDaRd   ").ok(),
             Some(RetryMode::Standard)
         );
-        // assert_eq!(
-        //     RetryMode::from_str("  adaptive  ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("   ADAPTIVE ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("  aDaPtIvE    ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
+        assert_eq!(
+            RetryMode::from_str("  adaptive  ").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("   ADAPTIVE ").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("  aDaPtIvE    ").ok(),
+            Some(RetryMode::Adaptive)
+        );
     }
 
     #[test]
@@ -396,4 +588,26 @@ mod tests {
         assert_eq!(RetryMode::from_str("s t a n d a r d").ok(), None);
         assert_eq!(RetryMode::from_str("a d a p t i v e").ok(), None);
     }
+
+    #[test]
+    fn retry_config_builder_merges_every_setting() {
+        let self_builder = RetryConfigBuilder::new()
+            .retry_cost(1)
+            .backoff_strategy(BackoffStrategy::Fixed(Duration::from_secs(2)));
+        let other_builder = RetryConfigBuilder::new()
+            .retry_cost(2)
+            .initial_retry_tokens(100)
+            .max_backoff(Duration::from_secs(5));
+        let retry_config = self_builder.take_unset_from(other_builder).build();
+
+        assert_eq!(retry_config.retry_cost(), Some(1));
+        assert_eq!(
+            retry_config.backoff_strategy(),
+            Some(BackoffStrategy::Fixed(Duration::from_secs(2)))
+        );
+        assert_eq!(retry_config.initial_retry_tokens(), Some(100));
+        assert_eq!(retry_config.max_backoff(), Some(Duration::from_secs(5)));
+        assert_eq!(retry_config.timeout_retry_cost(), None);
+        assert_eq!(retry_config.no_retry_increment(), None);
+    }
 }